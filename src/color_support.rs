@@ -0,0 +1,296 @@
+/*
+ *   Copyright (c) 2022 R3BL LLC
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use crossterm::style::Color;
+use r3bl_rs_utils::*;
+
+/// How many colors the terminal can show, so that the truecolor ([Color::Rgb]) colors of the
+/// stylesheets & of lolcat can be replaced by the nearest ones that it has (see
+/// [ColorSupport::degrade]), instead of turning into garbage (eg: in CI logs).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ColorSupport {
+  NoColor,
+  Ansi16,
+  Ansi256,
+  #[default]
+  Truecolor,
+}
+
+/// Colors of [ColorSupport::Ansi16] in the order of their ANSI values, w/ xterm's RGB values.
+const ANSI_16_COLORS: [(Color, (u8, u8, u8)); 16] = [
+  (Color::Black, (0, 0, 0)),
+  (Color::DarkRed, (205, 0, 0)),
+  (Color::DarkGreen, (0, 205, 0)),
+  (Color::DarkYellow, (205, 205, 0)),
+  (Color::DarkBlue, (0, 0, 238)),
+  (Color::DarkMagenta, (205, 0, 205)),
+  (Color::DarkCyan, (0, 205, 205)),
+  (Color::Grey, (229, 229, 229)),
+  (Color::DarkGrey, (127, 127, 127)),
+  (Color::Red, (255, 0, 0)),
+  (Color::Green, (0, 255, 0)),
+  (Color::Yellow, (255, 255, 0)),
+  (Color::Blue, (92, 92, 255)),
+  (Color::Magenta, (255, 0, 255)),
+  (Color::Cyan, (0, 255, 255)),
+  (Color::White, (255, 255, 255)),
+];
+
+/// Levels of each of red, green & blue in the 6x6x6 color cube of [ColorSupport::Ansi256].
+const ANSI_256_CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+impl ColorSupport {
+  /// Color support of the terminal, from the `NO_COLOR`, `COLORTERM` & `TERM` env vars.
+  pub fn detect() -> Self {
+    let get_env_var = |name: &str| std::env::var(name).ok();
+    Self::from_env_vars(
+      get_env_var("NO_COLOR").as_deref(),
+      get_env_var("COLORTERM").as_deref(),
+      get_env_var("TERM").as_deref(),
+    )
+  }
+
+  /// Color support for the values of the `NO_COLOR`, `COLORTERM` & `TERM` env vars (`None` for
+  /// the ones that aren't set).
+  pub fn from_env_vars(
+    maybe_no_color: Option<&str>, maybe_colorterm: Option<&str>, maybe_term: Option<&str>,
+  ) -> Self {
+    // See https://no-color.org, it only counts if it isn't empty.
+    if matches!(maybe_no_color, Some(it) if !it.is_empty()) {
+      return Self::NoColor;
+    }
+    if let Some("truecolor" | "24bit") = maybe_colorterm {
+      return Self::Truecolor;
+    }
+    match maybe_term.unwrap_or_default() {
+      // Windows terminals don't set `TERM`, & all the ones that crossterm supports have truecolor.
+      "" if cfg!(windows) => Self::Truecolor,
+      "" | "dumb" => Self::NoColor,
+      term if term.contains("truecolor") || term.contains("24bit") || term.contains("direct") => {
+        Self::Truecolor
+      }
+      term if term.contains("256") => Self::Ansi256,
+      _ => Self::Ansi16,
+    }
+  }
+
+  /// The nearest color to `color` that is supported, `None` if there are no colors at all.
+  pub fn degrade(&self, color: Color) -> Option<Color> {
+    let (r, g, b) = match color {
+      Color::Rgb { r, g, b } => (r, g, b),
+      Color::AnsiValue(value) if value >= 16 => get_ansi_256_rgb(value),
+      Color::AnsiValue(value) => ANSI_16_COLORS[value as usize].1,
+      // Named colors are one of the 16 (or the default color of the terminal).
+      _ => return (*self != Self::NoColor).then_some(color),
+    };
+    match self {
+      Self::Truecolor => Some(color),
+      Self::Ansi256 => match color {
+        Color::AnsiValue(_) => Some(color),
+        _ => Some(Color::AnsiValue(get_nearest_ansi_256_value(r, g, b))),
+      },
+      Self::Ansi16 => Some(get_nearest_ansi_16_color(r, g, b)),
+      Self::NoColor => None,
+    }
+  }
+
+  /// Replace the colors of `style` w/ the nearest supported ones (see [ColorSupport::degrade]).
+  pub fn degrade_style(&self, style: &mut Style) {
+    let degrade = |maybe_color: &Option<TWColor>| {
+      maybe_color
+        .as_ref()
+        .and_then(|it| self.degrade(**it))
+        .map(TWColor::from)
+    };
+    style.color_fg = degrade(&style.color_fg);
+    style.color_bg = degrade(&style.color_bg);
+  }
+
+  /// Like [ColorSupport::degrade_style], for a highlight (eg: the selection in the editor) that is
+  /// painted in reverse video instead when there are no colors, so that it can still be seen.
+  pub fn degrade_highlight_style(&self, style: &mut Style) {
+    self.degrade_style(style);
+    if *self == Self::NoColor {
+      style.reverse = true;
+    }
+  }
+
+  /// Replace the colors of all the styles in `stylesheet` w/ the nearest supported ones.
+  pub fn degrade_stylesheet(&self, stylesheet: &mut Stylesheet) {
+    for style in stylesheet.styles.iter_mut() {
+      self.degrade_style(style);
+    }
+  }
+}
+
+fn get_distance((r1, g1, b1): (u8, u8, u8), (r2, g2, b2): (u8, u8, u8)) -> u32 {
+  let get_square = |a: u8, b: u8| (a as i32 - b as i32).pow(2) as u32;
+  get_square(r1, r2) + get_square(g1, g2) + get_square(b1, b2)
+}
+
+fn get_nearest_ansi_16_color(r: u8, g: u8, b: u8) -> Color {
+  ANSI_16_COLORS
+    .iter()
+    .min_by_key(|(_, rgb)| get_distance(*rgb, (r, g, b)))
+    .map(|(color, _)| *color)
+    .unwrap_or(Color::Reset)
+}
+
+/// The nearest of the colors in the 6x6x6 cube (16..=231) & in the grayscale ramp (232..=255),
+/// the first 16 are left out since terminals often change them.
+fn get_nearest_ansi_256_value(r: u8, g: u8, b: u8) -> u8 {
+  let get_cube_index = |component: u8| {
+    (0..ANSI_256_CUBE_LEVELS.len())
+      .min_by_key(|index| (ANSI_256_CUBE_LEVELS[*index] as i32 - component as i32).abs())
+      .unwrap_or_default() as u8
+  };
+  let cube_value = 16 + 36 * get_cube_index(r) + 6 * get_cube_index(g) + get_cube_index(b);
+
+  let average = (r as u32 + g as u32 + b as u32) / 3;
+  let gray_value = 232 + (average.saturating_sub(3) / 10).min(23) as u8;
+
+  match get_distance(get_ansi_256_rgb(cube_value), (r, g, b))
+    <= get_distance(get_ansi_256_rgb(gray_value), (r, g, b))
+  {
+    true => cube_value,
+    false => gray_value,
+  }
+}
+
+/// RGB values of the colors of [ColorSupport::Ansi256] (w/ xterm's values for the first 16).
+fn get_ansi_256_rgb(value: u8) -> (u8, u8, u8) {
+  match value {
+    0..=15 => ANSI_16_COLORS[value as usize].1,
+    16..=231 => {
+      let index = value - 16;
+      (
+        ANSI_256_CUBE_LEVELS[(index / 36) as usize],
+        ANSI_256_CUBE_LEVELS[(index / 6 % 6) as usize],
+        ANSI_256_CUBE_LEVELS[(index % 6) as usize],
+      )
+    }
+    _ => {
+      let level = 8 + 10 * (value - 232);
+      (level, level, level)
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_from_env_vars_no_color() {
+    let detect = |no_color| ColorSupport::from_env_vars(no_color, Some("truecolor"), Some("xterm"));
+    assert_eq!(detect(Some("1")), ColorSupport::NoColor);
+    // An empty NO_COLOR doesn't count.
+    assert_eq!(detect(Some("")), ColorSupport::Truecolor);
+  }
+
+  #[test]
+  fn test_from_env_vars_colorterm() {
+    for colorterm in ["truecolor", "24bit"] {
+      let color_support = ColorSupport::from_env_vars(None, Some(colorterm), Some("xterm"));
+      assert_eq!(color_support, ColorSupport::Truecolor);
+    }
+    let color_support = ColorSupport::from_env_vars(None, Some("yes"), Some("xterm"));
+    assert_eq!(color_support, ColorSupport::Ansi16);
+  }
+
+  #[test]
+  fn test_from_env_vars_term() {
+    let detect = |term| ColorSupport::from_env_vars(None, None, term);
+    assert_eq!(detect(Some("xterm-256color")), ColorSupport::Ansi256);
+    assert_eq!(detect(Some("screen-256color")), ColorSupport::Ansi256);
+    assert_eq!(detect(Some("xterm-direct")), ColorSupport::Truecolor);
+    assert_eq!(detect(Some("xterm")), ColorSupport::Ansi16);
+    assert_eq!(detect(Some("dumb")), ColorSupport::NoColor);
+    if !cfg!(windows) {
+      assert_eq!(detect(None), ColorSupport::NoColor);
+    }
+  }
+
+  #[test]
+  fn test_degrade_rgb() {
+    let orange = Color::Rgb {
+      r: 255,
+      g: 135,
+      b: 0,
+    };
+    assert_eq!(ColorSupport::Truecolor.degrade(orange), Some(orange));
+    assert_eq!(
+      ColorSupport::Ansi256.degrade(orange),
+      Some(Color::AnsiValue(208))
+    );
+    assert_eq!(ColorSupport::Ansi16.degrade(orange), Some(Color::DarkYellow));
+    assert_eq!(ColorSupport::NoColor.degrade(orange), None);
+  }
+
+  #[test]
+  fn test_degrade_gray_to_grayscale_ramp() {
+    let gray = Color::Rgb {
+      r: 88,
+      g: 88,
+      b: 88,
+    };
+    assert_eq!(
+      ColorSupport::Ansi256.degrade(gray),
+      Some(Color::AnsiValue(240))
+    );
+  }
+
+  #[test]
+  fn test_degrade_ansi_value() {
+    assert_eq!(
+      ColorSupport::Ansi256.degrade(Color::AnsiValue(208)),
+      Some(Color::AnsiValue(208))
+    );
+    assert_eq!(
+      ColorSupport::Ansi16.degrade(Color::AnsiValue(196)),
+      Some(Color::Red)
+    );
+    assert_eq!(
+      ColorSupport::Ansi16.degrade(Color::AnsiValue(1)),
+      Some(Color::DarkRed)
+    );
+  }
+
+  #[test]
+  fn test_degrade_named_color() {
+    assert_eq!(ColorSupport::Ansi16.degrade(Color::Blue), Some(Color::Blue));
+    assert_eq!(ColorSupport::NoColor.degrade(Color::Blue), None);
+  }
+
+  #[test]
+  fn test_degrade_highlight_style() {
+    let mut style = Style {
+      color_bg: Some(
+        Color::Rgb {
+          r: 255,
+          g: 135,
+          b: 0,
+        }
+        .into(),
+      ),
+      ..Default::default()
+    };
+    ColorSupport::NoColor.degrade_highlight_style(&mut style);
+    assert!(style.color_bg.is_none());
+    assert!(style.reverse);
+  }
+}
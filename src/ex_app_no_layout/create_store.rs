@@ -28,20 +28,17 @@ pub async fn create_store() -> Store<AppNoLayoutState, AppNoLayoutAction> {
 }
 
 /// Action.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 #[non_exhaustive]
 pub enum AppNoLayoutAction {
   Startup,
   AddPop(i32),
   SubPop(i32),
   Clear,
+  #[default]
   Noop,
 }
 
-impl Default for AppNoLayoutAction {
-  fn default() -> Self { AppNoLayoutAction::Noop }
-}
-
 impl Display for AppNoLayoutAction {
   fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result { write!(f, "{:?}", self) }
 }
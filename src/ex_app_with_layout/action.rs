@@ -18,20 +18,17 @@
 use std::fmt::{Display, Formatter};
 
 /// Action.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 #[non_exhaustive]
 pub enum AppWithLayoutAction {
   Startup,
   AddPop(i32),
   SubPop(i32),
   Clear,
+  #[default]
   Noop,
 }
 
-impl Default for AppWithLayoutAction {
-  fn default() -> Self { AppWithLayoutAction::Noop }
-}
-
 impl Display for AppWithLayoutAction {
   fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result { write!(f, "{:?}", self) }
 }
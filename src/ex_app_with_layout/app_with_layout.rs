@@ -152,18 +152,16 @@ impl AppWithLayout {
   }
 
   async fn create_components_populate_registry_init_focus(&mut self) {
-    let _component = ColumnRenderComponent::default();
-    let shared_component_r1 = Arc::new(RwLock::new(_component));
-    let shared_component_r2 = shared_component_r1.clone();
-
-    // Construct COL_1_ID.
+    // Construct COL_1_ID. Each column gets its own component, so they don't share state.
     if self.component_registry.id_does_not_exist(COL_1_ID) {
-      self.component_registry.put(COL_1_ID, shared_component_r1);
+      let shared_component = Arc::new(RwLock::new(ColumnRenderComponent::default()));
+      self.component_registry.put(COL_1_ID, shared_component);
     }
 
     // Construct COL_2_ID.
     if self.component_registry.id_does_not_exist(COL_2_ID) {
-      self.component_registry.put(COL_2_ID, shared_component_r2);
+      let shared_component = Arc::new(RwLock::new(ColumnRenderComponent::default()));
+      self.component_registry.put(COL_2_ID, shared_component);
     }
 
     // Init has focus.
@@ -270,6 +268,7 @@ impl AppWithLayout {
           color_bg: Color::Rgb { r: 85, g: 85, b: 255 }
         },
       ])?;
+      ColorSupport::detect().degrade_stylesheet(&mut stylesheet);
 
       stylesheet
     })
@@ -20,10 +20,12 @@ use crossterm::event::*;
 use r3bl_rs_utils::*;
 
 use super::*;
+use crate::{ColorWheel, EditorLine};
 
 #[derive(Debug, Clone, Default)]
 pub struct ColumnRenderComponent {
-  pub lolcat: Lolcat,
+  /// The rainbow is made of the nearest colors that the terminal has (see [ColorWheel]).
+  pub color_wheel: ColorWheel,
 }
 
 #[async_trait]
@@ -107,38 +109,40 @@ impl Component<AppWithLayoutState, AppWithLayoutAction> for ColumnRenderComponen
       let mut content_cursor_pos = Position { col: 0, row: 0 };
       let mut queue: TWCommandQueue = tw_command_queue!();
 
-      // Line 1.
-      tw_command_queue! {
-        queue push
-        TWCommand::MoveCursorPositionRelTo(box_origin_pos, content_cursor_pos),
-        TWCommand::ApplyColors(current_box.get_computed_style()),
-        TWCommand::PrintWithAttributes(
-          colorize_using_lolcat! {
-            &mut self.lolcat,
-            "{}",
-            line_1.unicode_string().truncate_to_fit_size(box_bounding_size)
-          },
-          current_box.get_computed_style(),
-        )
-      };
+      // Soft wrap the lines to the width of the box (instead of truncating them), each visual line
+      // goes on its own row, until the box is full.
+      let box_cols = box_bounding_size.cols as usize;
+      let visual_lines: Vec<String> = [line_1, line_2]
+        .iter()
+        .flat_map(|line| {
+          line
+            .wrap_to_display_cols(box_cols)
+            .into_iter()
+            .map(move |range| {
+              // Whitespace at a break may hang past the width of the box.
+              let width = (range.end - range.start).min(box_cols);
+              line.clip_to_display_cols(range.start, width)
+            })
+        })
+        .take(box_bounding_size.rows as usize)
+        .collect();
 
-      // Line 2.
-      tw_command_queue! {
-        queue push
-        TWCommand::MoveCursorPositionRelTo(
-          box_origin_pos,
-          content_cursor_pos.add_row_with_bounds(1, box_bounding_size)
-        ),
-        TWCommand::PrintWithAttributes(
-          colorize_using_lolcat! {
-            &mut self.lolcat,
-            "{}",
-            line_2.unicode_string().truncate_to_fit_size(box_bounding_size)
-          },
-          current_box.get_computed_style(),
-        ),
-        TWCommand::ResetColor
-      };
+      for (row, visual_line) in visual_lines.iter().enumerate() {
+        content_cursor_pos = Position {
+          col: 0,
+          row: row as UnitType,
+        };
+        tw_command_queue! {
+          queue push
+          TWCommand::MoveCursorPositionRelTo(box_origin_pos, content_cursor_pos),
+          TWCommand::ApplyColors(current_box.get_computed_style()),
+          TWCommand::PrintWithAttributes(
+            self.color_wheel.paint_line(visual_line),
+            current_box.get_computed_style(),
+          ),
+          TWCommand::ResetColor
+        };
+      }
 
       // Paint is_focused.
       if has_focus.does_current_box_have_focus(current_box) {
@@ -0,0 +1,137 @@
+/*
+ *   Copyright (c) 2022 R3BL LLC
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use std::fmt::{Display, Formatter};
+
+use super::*;
+
+/// Action. The first field of each variant that has an id is the id of the editor component (and
+/// its buffer) that the action applies to.
+#[derive(Clone, Debug, Default)]
+#[non_exhaustive]
+pub enum EditorAction {
+  /// Type a char, w/ auto-pairing of brackets & quotes.
+  InsertChar(String, char),
+  /// Split the line at the carets, w/ auto-indent.
+  InsertNewLine(String),
+  Delete(String),
+  /// Delete the char before the carets, & the closing bracket or quote after them if it pairs w/
+  /// it.
+  Backspace(String),
+  MoveCaret(String, CaretDirection),
+  /// Move the carets & extend their selections (eg: Shift+arrow).
+  ExtendSelection(String, CaretDirection),
+  /// Extend the selection of the caret to the given position (eg: mouse drag).
+  ExtendSelectionTo(String, CaretPos),
+  /// Copy the selected text to the clipboard in [EditorState].
+  Copy(String),
+  /// Copy the selected text to the clipboard in [EditorState] & delete it.
+  Cut(String),
+  /// Insert the text at the carets, as a single edit (eg: Ctrl+V).
+  Paste(String, String),
+  /// Insert the text at the carets as is (w/out auto-indent or auto-pairing), grouped w/ the typing
+  /// before it into one undo step (eg: the keys of a paste in the terminal, see
+  /// [PASTE_BURST_INTERVAL]).
+  InsertText(String, String),
+  /// Kill to the end of the line onto the [KillRing] (eg: `C-k`).
+  Kill(String),
+  /// Insert the newest entry of the [KillRing] (eg: `C-y`).
+  Yank(String),
+  /// Replace the text of the last yank w/ the previous entry of the [KillRing] (eg: `M-y`).
+  YankPop(String),
+  /// Replace the text in the clipboard in [EditorState] (eg: vi `yy` yanks whole lines).
+  SetClipboard(String),
+  /// Move the caret to the given position (eg: click), & drop the other carets.
+  SetCaret(String, CaretPos),
+  /// Add a caret at the given position, or remove it if there already is one (eg: Alt+Click).
+  ToggleCaret(String, CaretPos),
+  AddCaretAbove(String),
+  AddCaretBelow(String),
+  SelectNextOccurrence(String),
+  ClearExtraCarets(String),
+  Undo(String),
+  Redo(String),
+  /// Fold the region at the caret, or open the fold that starts at its line (see [FoldKind]).
+  ToggleFold(String),
+  FoldAll(String),
+  UnfoldAll(String),
+  /// Open the search bar to search in the buffer for the given id.
+  StartSearch(String),
+  EndSearch,
+  /// Close the search bar & move the caret back to where the search started.
+  CancelSearch,
+  /// Type into the active field of the search bar.
+  SearchInsertChar(char),
+  SearchBackspace,
+  /// Move the cursor in the active field of the search bar.
+  SearchMoveCursor(CaretDirection),
+  /// Kill to the end of the active field of the search bar onto the [KillRing].
+  SearchKill,
+  /// Insert the newest entry of the [KillRing] in the active field of the search bar.
+  SearchYank,
+  /// Switch between the query & replacement fields of the search bar.
+  SearchToggleField,
+  SearchToggleRegex,
+  SearchToggleCaseSensitive,
+  SearchNext,
+  SearchPrev,
+  ReplaceOne,
+  ReplaceAll,
+  /// Replace the buffer (eg: after a file has been loaded).
+  SetBuffer(String, EditorBuffer),
+  /// Append the next lines of a large file to the buffer that is loading it (see [PendingLoad]).
+  AppendLines(String, Vec<String>),
+  /// A large file has been loaded, or loading it failed w/ the given message.
+  LoadFinished(String, Option<String>),
+  /// Open a file that isn't valid UTF-8 in the hex view (see [HexBuffer]).
+  SetHexBuffer(String, HexBuffer),
+  /// Move the cursor of the hex view to the given offset (clamped to the last byte).
+  HexSetCursor(String, usize),
+  /// Overwrite the nibble under the cursor of the hex view w/ a hex digit.
+  HexWriteNibble(String, u8),
+  /// Overwrite the byte under the cursor of the hex view.
+  HexWriteByte(String, u8),
+  /// Switch between the hex & ASCII panes of the hex view.
+  HexTogglePane(String),
+  HexOpenPrompt(String, HexPromptKind),
+  HexPromptInsertChar(String, char),
+  HexPromptBackspace(String),
+  HexPromptSubmit(String),
+  HexPromptCancel(String),
+  /// Move the cursor of the hex view to the next match of the last byte pattern.
+  HexFindNext(String),
+  /// Share the buffer for the given id w/ other instances (see [CollabState]).
+  StartCollab(String, CollabRole),
+  /// A guest connected to the host, w/ the given peer id.
+  CollabPeerJoined(u32),
+  /// A message arrived from the peer w/ the given id.
+  CollabReceive(u32, CollabMessage),
+  /// The peer w/ the given id disconnected.
+  CollabPeerLeft(u32),
+  /// The buffer has been written to the given file path, w/ its content at the given
+  /// `change_count` (it is still dirty if it was edited while it was being written).
+  FileSaved(String, String, u64),
+  ShowMessage(String),
+  /// Run the actions in order, as a single update of the store (eg: vi `d3w`).
+  Batch(Vec<EditorAction>),
+  #[default]
+  Noop,
+}
+
+impl Display for EditorAction {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result { write!(f, "{:?}", self) }
+}
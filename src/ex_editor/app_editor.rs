@@ -0,0 +1,694 @@
+/*
+ *   Copyright (c) 2022 R3BL LLC
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use std::{collections::HashMap, fmt::Debug, sync::Arc};
+
+use async_trait::async_trait;
+use crossterm::event::*;
+use r3bl_rs_utils::*;
+use tokio::sync::RwLock;
+
+use super::*;
+use crate::ColorSupport;
+
+/// Async trait object that implements the [TWApp] trait.
+#[derive(Default)]
+pub struct AppEditor {
+  pub component_registry: ComponentRegistry<EditorState, EditorAction>,
+  /// The same components as in `component_registry` (by id), w/out type erasure, so that the
+  /// gutter & status line can use what they painted.
+  pub editor_components: HashMap<String, Arc<RwLock<EditorComponent>>>,
+  pub has_focus: HasFocus,
+  /// Key bindings of the editor components, they are created w/ them.
+  pub key_bindings: KeyBindings,
+  /// Gets the buffers on each render, to write their swap files.
+  pub autosaver: Autosaver,
+  /// For vi's `:q` (or emacs' `C-x C-c`), the editor components get it when they are created.
+  pub quit_signal: QuitSignal,
+  /// Gets the state on each render, to send the edits of the shared buffer to the peers.
+  pub collab_session: CollabSession,
+  pub line_number_mode: LineNumberMode,
+  /// The outline side panel (OUTLINE_ID), w/out type erasure like `editor_components`.
+  pub outline_component: Arc<RwLock<OutlineComponent>>,
+  /// Id of the editor whose outline is shown in the side panel, while it is open.
+  pub maybe_outline_editor_id: Option<String>,
+  /// Large files that are loaded lazily, keyed by buffer id. Loading starts on the first render,
+  /// when there is a [SharedStore] to dispatch the lines to.
+  pub pending_loads: Vec<(String, PendingLoad)>,
+  /// Ctrl+Q was pressed while there were unsaved changes, pressing it again quits.
+  pub quit_requested: bool,
+}
+
+impl Debug for AppEditor {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("AppEditor")
+      .field("component_registry", &self.component_registry)
+      .field("has_focus", &self.has_focus)
+      .field("key_bindings", &self.key_bindings)
+      .field("autosaver", &self.autosaver)
+      .field("quit_signal", &self.quit_signal)
+      .field("collab_session", &self.collab_session)
+      .field("line_number_mode", &self.line_number_mode)
+      .field("maybe_outline_editor_id", &self.maybe_outline_editor_id)
+      .field("pending_loads", &self.pending_loads)
+      .field("quit_requested", &self.quit_requested)
+      .finish()
+  }
+}
+
+// Constants for the ids.
+const CONTAINER_ID: &str = "container";
+const EDITORS_ID: &str = "editors";
+const STATUS_LINE_ID: &str = "status_line";
+const COL_1_ID: &str = "col_1";
+const COL_2_ID: &str = "col_2";
+const OUTLINE_ID: &str = "outline";
+
+/// Width of the outline side panel, the editor columns share the rest of the width.
+const OUTLINE_COL_PERCENT: UnitType = 20;
+
+/// Ids of the editor components (& their buffers), from left to right.
+pub const EDITOR_IDS: [&str; 2] = [COL_1_ID, COL_2_ID];
+
+#[async_trait]
+impl TWApp<EditorState, EditorAction> for AppEditor {
+  async fn app_handle_event(
+    &mut self, input_event: &TWInputEvent, state: &EditorState,
+    shared_store: &SharedStore<EditorState, EditorAction>, _terminal_size: Size,
+  ) -> CommonResult<EventPropagation> {
+    throws_with_return!({
+      // Ask for confirmation before the exit key (Ctrl+Q) throws away unsaved changes.
+      if let Continuation::Return =
+        self.handle_quit_input_when_dirty(input_event, state, shared_store)
+      {
+        return Ok(EventPropagation::Consumed);
+      }
+
+      // While the search bar is open, it gets all the input events.
+      if let Some(search) = &state.search {
+        return self.handle_search_input(input_event, search, shared_store);
+      }
+
+      // Ctrl+F opens the search bar for the editor that has focus (the hex view has its own).
+      if let TWInputEvent::NonDisplayableKeypress(KeyEvent {
+        code: KeyCode::Char('f'),
+        modifiers: KeyModifiers::CONTROL,
+      }) = input_event
+      {
+        let maybe_id = self
+          .get_focused_editor_id()
+          .filter(|id| state.get_hex_buffer(id).is_none());
+        if let Some(id) = maybe_id {
+          spawn_dispatch_action!(shared_store, EditorAction::StartSearch(id));
+          return Ok(EventPropagation::Consumed);
+        }
+      }
+
+      // Alt+N switches between absolute & relative line numbers.
+      if let TWInputEvent::NonDisplayableKeypress(KeyEvent {
+        code: KeyCode::Char('n'),
+        modifiers: KeyModifiers::ALT,
+      }) = input_event
+      {
+        self.line_number_mode = self.line_number_mode.toggle();
+        return Ok(EventPropagation::ConsumedRerender);
+      }
+
+      // Alt+O opens (or closes) the outline side panel for the editor that has focus.
+      if let TWInputEvent::NonDisplayableKeypress(KeyEvent {
+        code: KeyCode::Char('o'),
+        modifiers: KeyModifiers::ALT,
+      }) = input_event
+      {
+        self.toggle_outline(state).await;
+        return Ok(EventPropagation::ConsumedRerender);
+      }
+
+      // Enter & Esc in the outline side panel.
+      if let Some(event_propagation) = self.handle_outline_input(input_event, shared_store).await {
+        return Ok(event_propagation);
+      }
+
+      // Try to handle the Tab key input event (to switch focus) & return if handled.
+      if let Continuation::Return = self.handle_tab_input_to_switch_focus(input_event) {
+        return Ok(EventPropagation::ConsumedRerender);
+      }
+
+      // If component has focus, then route input_event to it. Return its propagation enum.
+      if let Some(shared_component_has_focus) =
+        self.component_registry.get_has_focus(&self.has_focus)
+      {
+        let result_event_propagation = shared_component_has_focus
+          .write()
+          .await
+          .handle_event(input_event, state, shared_store)
+          .await?;
+        return Ok(result_event_propagation);
+      };
+
+      // input_event not handled, propagate it.
+      EventPropagation::Propagate
+    });
+  }
+
+  async fn app_render(
+    &mut self, state: &EditorState, shared_store: &SharedStore<EditorState, EditorAction>,
+    window_size: Size,
+  ) -> CommonResult<TWCommandQueue> {
+    throws_with_return!({
+      self.create_components_populate_registry_init_focus().await;
+      for (id, pending_load) in self.pending_loads.drain(..) {
+        spawn_load_rest(shared_store, &id, pending_load);
+      }
+      self.autosaver.update(state);
+      if let Some(message) = self.autosaver.take_write_error() {
+        spawn_dispatch_action!(shared_store, EditorAction::ShowMessage(message));
+      }
+      self.collab_session.spawn(shared_store);
+      self.collab_session.update(state);
+      let mut tw_surface = TWSurface {
+        stylesheet: self.create_stylesheet()?,
+        ..TWSurface::default()
+      };
+      tw_surface.surface_start(TWSurfaceProps {
+        pos: (0, 0).into(),
+        size: window_size,
+      })?;
+      self
+        .create_main_container(&mut tw_surface, state, shared_store, window_size)
+        .await?;
+      tw_surface.surface_end()?;
+      self.sync_carets_into_has_focus(state).await;
+      tw_surface.render_buffer
+    });
+  }
+}
+
+impl AppEditor {
+  fn handle_quit_input_when_dirty(
+    &mut self, input_event: &TWInputEvent, state: &EditorState,
+    shared_store: &SharedStore<EditorState, EditorAction>,
+  ) -> Continuation {
+    let is_quit_input = matches!(
+      input_event,
+      TWInputEvent::NonDisplayableKeypress(KeyEvent {
+        code: KeyCode::Char('q'),
+        modifiers: KeyModifiers::CONTROL,
+      })
+    );
+
+    // Any other input cancels a pending quit request.
+    let quit_requested = std::mem::take(&mut self.quit_requested);
+    if !is_quit_input || quit_requested || !state.has_dirty_buffers() {
+      return Continuation::Continue;
+    }
+
+    self.quit_requested = true;
+    spawn_dispatch_action!(
+      shared_store,
+      EditorAction::ShowMessage(
+        "Unsaved changes! Ctrl+Q again to quit w/out saving, Ctrl+S to save".into()
+      )
+    );
+    Continuation::Return
+  }
+
+  /// Handle following input events while the search bar is open (and consume them):
+  /// - Displayable char       : fire `SearchInsertChar(char)`
+  /// - Backspace              : fire `SearchBackspace`
+  /// - Enter, Down            : fire `SearchNext`
+  /// - Shift+Enter, Up        : fire `SearchPrev`
+  /// - Left, Right, Home, End : fire `SearchMoveCursor(direction)` (in the active field)
+  /// - Tab                    : fire `SearchToggleField` (query / replacement)
+  /// - Alt+R, Alt+C           : fire `SearchToggleRegex`, `SearchToggleCaseSensitive`
+  /// - Ctrl+R, Ctrl+A         : fire `ReplaceOne`, `ReplaceAll`
+  /// - Esc                    : fire `EndSearch`
+  ///
+  /// W/ [KeyBindings::Emacs], [action_for_emacs_search_key] is tried first.
+  fn handle_search_input(
+    &mut self, input_event: &TWInputEvent, search: &EditorSearch,
+    shared_store: &SharedStore<EditorState, EditorAction>,
+  ) -> CommonResult<EventPropagation> {
+    throws_with_return!({
+      let maybe_emacs_action = match (self.key_bindings, input_event) {
+        (KeyBindings::Emacs, TWInputEvent::NonDisplayableKeypress(key_event)) => {
+          action_for_emacs_search_key(key_event, search)
+        }
+        _ => None,
+      };
+      let maybe_action = maybe_emacs_action.or(match input_event {
+        TWInputEvent::DisplayableKeypress(typed_char) => {
+          Some(EditorAction::SearchInsertChar(*typed_char))
+        }
+        TWInputEvent::NonDisplayableKeypress(key_event) => {
+          match (key_event.code, key_event.modifiers) {
+            (KeyCode::Left, KeyModifiers::NONE) => {
+              Some(EditorAction::SearchMoveCursor(CaretDirection::Left))
+            }
+            (KeyCode::Right, KeyModifiers::NONE) => {
+              Some(EditorAction::SearchMoveCursor(CaretDirection::Right))
+            }
+            (KeyCode::Home, KeyModifiers::NONE) => {
+              Some(EditorAction::SearchMoveCursor(CaretDirection::LineStart))
+            }
+            (KeyCode::End, KeyModifiers::NONE) => {
+              Some(EditorAction::SearchMoveCursor(CaretDirection::LineEnd))
+            }
+            (KeyCode::Backspace, KeyModifiers::NONE) => Some(EditorAction::SearchBackspace),
+            (KeyCode::Enter, KeyModifiers::NONE) | (KeyCode::Down, KeyModifiers::NONE) => {
+              Some(EditorAction::SearchNext)
+            }
+            (KeyCode::Enter, KeyModifiers::SHIFT) | (KeyCode::Up, KeyModifiers::NONE) => {
+              Some(EditorAction::SearchPrev)
+            }
+            (KeyCode::Tab, KeyModifiers::NONE) => Some(EditorAction::SearchToggleField),
+            (KeyCode::Char('r'), KeyModifiers::ALT) => Some(EditorAction::SearchToggleRegex),
+            (KeyCode::Char('c'), KeyModifiers::ALT) => {
+              Some(EditorAction::SearchToggleCaseSensitive)
+            }
+            (KeyCode::Char('r'), KeyModifiers::CONTROL) => Some(EditorAction::ReplaceOne),
+            (KeyCode::Char('a'), KeyModifiers::CONTROL) => Some(EditorAction::ReplaceAll),
+            (KeyCode::Esc, KeyModifiers::NONE) => Some(EditorAction::EndSearch),
+            _ => None,
+          }
+        }
+        _ => None,
+      });
+
+      if let Some(action) = maybe_action {
+        debug_log_editor_action(
+          stringify!(AppEditor::handle_search_input).into(),
+          action.clone(),
+        );
+        spawn_dispatch_action!(shared_store, action);
+        return Ok(EventPropagation::Consumed);
+      }
+
+      EventPropagation::Propagate
+    });
+  }
+
+  fn handle_tab_input_to_switch_focus(&mut self, input_event: &TWInputEvent) -> Continuation {
+    // Handle Tab to switch focus between columns. Left & Right are used to move the caret.
+    if let TWInputEvent::NonDisplayableKeypress(KeyEvent {
+      code: KeyCode::Tab,
+      modifiers: KeyModifiers::NONE,
+    }) = input_event
+    {
+      self.switch_focus();
+      debug_log_editor_has_focus(
+        stringify!(AppEditor::app_handle_event).into(),
+        &self.has_focus,
+      );
+      return Continuation::Return;
+    }
+
+    Continuation::Continue
+  }
+
+  /// The editor that has focus, or the one whose outline has focus.
+  fn get_focused_editor_id(&self) -> Option<String> {
+    match self.has_focus.get_id() {
+      Some(id) if id == OUTLINE_ID => self.maybe_outline_editor_id.clone(),
+      maybe_id => maybe_id,
+    }
+  }
+
+  async fn toggle_outline(&mut self, state: &EditorState) {
+    if let Some(editor_id) = self.maybe_outline_editor_id.take() {
+      if self.has_focus.get_id().as_deref() == Some(OUTLINE_ID) {
+        self.has_focus.set_id(&editor_id);
+      }
+      return;
+    }
+    if let Some(editor_id) = self.get_focused_editor_id() {
+      let default_buffer = EditorBuffer::default();
+      let buffer = state.get_buffer(&editor_id).unwrap_or(&default_buffer);
+      self
+        .outline_component
+        .write()
+        .await
+        .open(&editor_id, buffer);
+      self.has_focus.set_id(OUTLINE_ID);
+      self.maybe_outline_editor_id = Some(editor_id);
+    }
+  }
+
+  /// Handle following input events while the outline side panel has focus:
+  /// - Enter : fire `SetCaret(id, pos)` w/ the start of the selected item, & move the focus back to
+  ///   the editor (the panel stays open)
+  /// - Esc   : close the panel & move the focus back to the editor
+  async fn handle_outline_input(
+    &mut self, input_event: &TWInputEvent, shared_store: &SharedStore<EditorState, EditorAction>,
+  ) -> Option<EventPropagation> {
+    if self.has_focus.get_id().as_deref() != Some(OUTLINE_ID) {
+      return None;
+    }
+    let editor_id = self.maybe_outline_editor_id.clone()?;
+    let key_code = match input_event {
+      TWInputEvent::NonDisplayableKeypress(KeyEvent {
+        code,
+        modifiers: KeyModifiers::NONE,
+      }) => *code,
+      _ => return None,
+    };
+
+    match key_code {
+      KeyCode::Enter => {
+        let maybe_row = self
+          .outline_component
+          .read()
+          .await
+          .get_selected_item()
+          .map(|it| it.row);
+        if let Some(row) = maybe_row {
+          let action = EditorAction::SetCaret(editor_id.clone(), CaretPos { row, col: 0 });
+          debug_log_editor_action(
+            stringify!(AppEditor::handle_outline_input).into(),
+            action.clone(),
+          );
+          spawn_dispatch_action!(shared_store, action);
+        }
+        self.has_focus.set_id(&editor_id);
+        Some(EventPropagation::ConsumedRerender)
+      }
+      KeyCode::Esc => {
+        self.maybe_outline_editor_id = None;
+        self.has_focus.set_id(&editor_id);
+        Some(EventPropagation::ConsumedRerender)
+      }
+      _ => None,
+    }
+  }
+
+  /// Each column has its own buffer (& caret), so switching focus leaves the caret of the column
+  /// that loses focus where it was. From the outline side panel,
+  /// the focus goes back to its editor.
+  fn switch_focus(&mut self) {
+    if let Some(id) = self.has_focus.get_id() {
+      if id == OUTLINE_ID {
+        let editor_id = self
+          .maybe_outline_editor_id
+          .clone()
+          .unwrap_or_else(|| COL_1_ID.into());
+        self.has_focus.set_id(&editor_id)
+      } else if id == COL_1_ID {
+        self.has_focus.set_id(COL_2_ID)
+      } else {
+        self.has_focus.set_id(COL_1_ID)
+      }
+    } else {
+      log_no_err!(ERROR, "No focus id has been set, and it should be set!");
+    }
+  }
+
+  /// Copy the position of the terminal cursor for each editor into `has_focus`, which is its
+  /// (primary) caret relative to the viewport that was just rendered. The editor components paint
+  /// the carets from the buffers, since [HasFocus] can't address the rows of a large file.
+  async fn sync_carets_into_has_focus(&mut self, state: &EditorState) {
+    for id in EDITOR_IDS {
+      let maybe_pos = match (state.get_buffer(id), self.editor_components.get(id)) {
+        (Some(buffer), Some(editor_component)) => {
+          editor_component.read().await.get_caret_viewport_pos(buffer)
+        }
+        _ => None,
+      };
+      self.has_focus.set_cursor_position_for_id(id, maybe_pos);
+    }
+  }
+
+  async fn create_components_populate_registry_init_focus(&mut self) {
+    // Construct COL_1_ID & COL_2_ID. Each column gets its own component (& buffer). They share the
+    // syntax highlighter, which is expensive to create.
+    if EDITOR_IDS
+      .iter()
+      .any(|id| self.component_registry.id_does_not_exist(id))
+    {
+      let syntax_highlighter = Arc::new(SyntaxHighlighter::default());
+      for id in EDITOR_IDS {
+        if self.component_registry.id_does_not_exist(id) {
+          let component = Arc::new(RwLock::new(EditorComponent::new(
+            id,
+            &syntax_highlighter,
+            self.key_bindings,
+            &self.quit_signal,
+          )));
+          self
+            .editor_components
+            .insert(id.to_string(), component.clone());
+          self.component_registry.put(id, component);
+        }
+      }
+    }
+
+    // Construct OUTLINE_ID, it is only painted while the side panel is open.
+    if self.component_registry.id_does_not_exist(OUTLINE_ID) {
+      self
+        .component_registry
+        .put(OUTLINE_ID, self.outline_component.clone());
+    }
+
+    // Init has focus.
+    if self.has_focus.get_id().is_none() {
+      self.has_focus.set_id(COL_1_ID);
+    }
+  }
+
+  /// Main container CONTAINER_ID, w/ the editor columns on top of the status line.
+  async fn create_main_container<'a>(
+    &mut self, tw_surface: &mut TWSurface, state: &'a EditorState,
+    shared_store: &'a SharedStore<EditorState, EditorAction>, window_size: Size,
+  ) -> CommonResult<()> {
+    throws!({
+      let status_line_percent = get_percent_for_cells(1, window_size.rows);
+      tw_surface.box_start(TWBoxProps {
+        id: CONTAINER_ID.into(),
+        dir: Direction::Vertical,
+        req_size: (100, 100).try_into()?,
+        ..Default::default()
+      })?;
+
+      tw_surface.box_start(TWBoxProps {
+        id: EDITORS_ID.into(),
+        dir: Direction::Horizontal,
+        req_size: (100, i32::from(100 - status_line_percent)).try_into()?,
+        ..Default::default()
+      })?;
+      let mut editor_col_percent = 50;
+      if self.maybe_outline_editor_id.is_some() {
+        self
+          .create_outline_col(tw_surface, state, shared_store)
+          .await?;
+        editor_col_percent = (100 - OUTLINE_COL_PERCENT) / 2;
+      }
+      self
+        .create_editor_col(
+          tw_surface,
+          state,
+          shared_store,
+          (COL_1_ID, "style1", editor_col_percent),
+          window_size,
+        )
+        .await?;
+      self
+        .create_editor_col(
+          tw_surface,
+          state,
+          shared_store,
+          (COL_2_ID, "style2", editor_col_percent),
+          window_size,
+        )
+        .await?;
+      tw_surface.box_end()?;
+
+      self
+        .create_status_line(tw_surface, state, status_line_percent)
+        .await?;
+      tw_surface.box_end()?;
+    });
+  }
+
+  /// Outline side panel OUTLINE_ID, on the left of the editor columns (like the columns of
+  /// [AppWithLayout](crate::AppWithLayout)).
+  async fn create_outline_col<'a>(
+    &mut self, tw_surface: &mut TWSurface, state: &'a EditorState,
+    shared_store: &'a SharedStore<EditorState, EditorAction>,
+  ) -> CommonResult<()> {
+    throws!({
+      tw_surface.box_start(TWBoxProps {
+        styles: tw_surface.stylesheet.find_styles_by_ids(vec!["outline"]),
+        id: OUTLINE_ID.into(),
+        dir: Direction::Vertical,
+        req_size: (i32::from(OUTLINE_COL_PERCENT), 100).try_into()?,
+      })?;
+
+      if let Some(shared_component) = self.component_registry.get(OUTLINE_ID) {
+        let current_box = tw_surface.current_box()?;
+        let queue = shared_component
+          .write()
+          .await
+          .render(&self.has_focus, current_box, state, shared_store)
+          .await?;
+        tw_surface.render_buffer += queue;
+      }
+
+      tw_surface.box_end()?;
+    });
+  }
+
+  /// Editor column (COL_1_ID or COL_2_ID), `width_percent` of the width. It has the line number
+  /// gutter on the left & the editor on the right. The gutter is painted after the editor, since
+  /// it numbers the lines that the editor painted.
+  async fn create_editor_col<'a>(
+    &mut self, tw_surface: &mut TWSurface, state: &'a EditorState,
+    shared_store: &'a SharedStore<EditorState, EditorAction>,
+    (id, style_id, width_percent): (&str, &str, UnitType), window_size: Size,
+  ) -> CommonResult<()> {
+    throws!({
+      let maybe_buffer = state.get_buffer(id);
+      let gutter_width = get_gutter_width(maybe_buffer.map(EditorBuffer::line_count).unwrap_or(1));
+      let col_width = (window_size.cols as usize * width_percent as usize / 100) as UnitType;
+      let gutter_percent = get_percent_for_cells(gutter_width, col_width);
+      tw_surface.box_start(TWBoxProps {
+        id: format!("{}_container", id),
+        dir: Direction::Horizontal,
+        req_size: (i32::from(width_percent), 100).try_into()?,
+        ..Default::default()
+      })?;
+
+      // Gutter.
+      tw_surface.box_start(TWBoxProps {
+        styles: tw_surface.stylesheet.find_styles_by_ids(vec!["gutter"]),
+        id: format!("{}_gutter", id),
+        dir: Direction::Vertical,
+        req_size: (i32::from(gutter_percent), 100).try_into()?,
+      })?;
+      let gutter_box = tw_surface.current_box()?.clone();
+      tw_surface.box_end()?;
+
+      // Editor.
+      tw_surface.box_start(TWBoxProps {
+        styles: tw_surface.stylesheet.find_styles_by_ids(vec![style_id]),
+        id: id.into(),
+        dir: Direction::Vertical,
+        req_size: (i32::from(100 - gutter_percent), 100).try_into()?,
+      })?;
+      if let Some(shared_component) = self.component_registry.get(id) {
+        let current_box = tw_surface.current_box()?;
+        let queue = shared_component
+          .write()
+          .await
+          .render(&self.has_focus, current_box, state, shared_store)
+          .await?;
+        tw_surface.render_buffer += queue;
+      }
+      tw_surface.box_end()?;
+
+      if let Some(editor_component) = self.editor_components.get(id) {
+        let caret_row = maybe_buffer.map(|it| it.caret.row).unwrap_or_default();
+        tw_surface.render_buffer += render_gutter(
+          &gutter_box,
+          &editor_component.read().await.visual_lines,
+          caret_row,
+          self.line_number_mode,
+        );
+      }
+
+      tw_surface.box_end()?;
+    });
+  }
+
+  /// Status line STATUS_LINE_ID for the editor that has focus, one row at the bottom of the
+  /// window. While the search bar is open, it is shown here instead.
+  async fn create_status_line(
+    &mut self, tw_surface: &mut TWSurface, state: &EditorState, height_percent: UnitType,
+  ) -> CommonResult<()> {
+    throws!({
+      tw_surface.box_start(TWBoxProps {
+        styles: tw_surface
+          .stylesheet
+          .find_styles_by_ids(vec!["status_line"]),
+        id: STATUS_LINE_ID.into(),
+        dir: Direction::Vertical,
+        req_size: (100, i32::from(height_percent)).try_into()?,
+      })?;
+
+      let id = self
+        .get_focused_editor_id()
+        .unwrap_or_else(|| COL_1_ID.into());
+      let text = match (&state.search, state.get_hex_buffer(&id)) {
+        (Some(search), _) => get_search_bar_text(state, search),
+        (None, Some(hex_buffer)) => get_hex_status_text(hex_buffer, state.message.as_deref()),
+        (None, None) => {
+          let default_buffer = EditorBuffer::default();
+          let buffer = state.get_buffer(&id).unwrap_or(&default_buffer);
+          let mode = match self.editor_components.get(&id) {
+            Some(editor_component) => editor_component.read().await.get_mode_name(buffer),
+            None => String::new(),
+          };
+          get_status_text(buffer, &mode, state.message.as_deref())
+        }
+      };
+      let status_line = render_status_line(tw_surface.current_box()?, &text);
+      tw_surface.render_buffer += status_line;
+
+      tw_surface.box_end()?;
+    });
+  }
+
+  fn create_stylesheet(&mut self) -> CommonResult<Stylesheet> {
+    throws_with_return!({
+      let mut stylesheet = Stylesheet::new();
+
+      stylesheet.add_styles(vec![
+        style! {
+          id: style1
+          color_bg: Color::Rgb { r: 55, g: 55, b: 248 }
+        },
+        style! {
+          id: style2
+          color_bg: Color::Rgb { r: 85, g: 85, b: 255 }
+        },
+        style! {
+          id: gutter
+          color_fg: Color::Rgb { r: 160, g: 160, b: 210 }
+          color_bg: Color::Rgb { r: 35, g: 35, b: 150 }
+        },
+        style! {
+          id: outline
+          color_fg: Color::Rgb { r: 200, g: 200, b: 240 }
+          color_bg: Color::Rgb { r: 45, g: 45, b: 200 }
+        },
+        style! {
+          id: status_line
+          color_fg: Color::Rgb { r: 20, g: 20, b: 60 }
+          color_bg: Color::Rgb { r: 190, g: 190, b: 255 }
+        },
+      ])?;
+      ColorSupport::detect().degrade_stylesheet(&mut stylesheet);
+
+      stylesheet
+    })
+  }
+}
+
+/// Box sizes are requested in percent (of the parent box), this is the smallest percentage of
+/// `total` cells that has room for `cells` cells.
+fn get_percent_for_cells(cells: usize, total: UnitType) -> UnitType {
+  let total = (total as usize).max(1);
+  (cells * 100).div_ceil(total).min(100) as UnitType
+}
@@ -0,0 +1,147 @@
+/*
+ *   Copyright (c) 2022 R3BL LLC
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::*;
+
+/// Brackets that are matched & auto-paired (opening, closing).
+const BRACKET_PAIRS: [(&str, &str); 3] = [("(", ")"), ("[", "]"), ("{", "}")];
+
+/// Quotes that are auto-paired. `'` isn't paired in Rust files, where it also starts lifetimes.
+const QUOTES: [&str; 3] = ["\"", "'", "`"];
+
+/// Lines that are searched for a matching bracket (in either direction), so that a bracket w/out
+/// a match doesn't scan all of a large file on each render.
+const MAX_BRACKET_MATCH_ROWS: usize = 1000;
+
+/// Bracket matching & auto-pairing of brackets & quotes.
+impl EditorBuffer {
+  /// If there is a bracket under `caret` (or right before it), then return its position & the
+  /// position of the bracket that matches it. Brackets in strings & comments aren't skipped.
+  pub fn get_matching_bracket(&self, caret: CaretPos) -> Option<(CaretPos, CaretPos)> {
+    let line = self.lines.get(caret.row)?;
+    let candidates = [Some(caret.col), caret.col.checked_sub(1)];
+    candidates.into_iter().flatten().find_map(|col| {
+      let bracket_pos = CaretPos {
+        row: caret.row,
+        col,
+      };
+      let grapheme = line.grapheme_at(col)?;
+      BRACKET_PAIRS.iter().find_map(|(opener, closer)| {
+        if grapheme == *opener {
+          self.find_bracket(bracket_pos, opener, closer, true)
+        } else if grapheme == *closer {
+          self.find_bracket(bracket_pos, closer, opener, false)
+        } else {
+          None
+        }
+        .map(|match_pos| (bracket_pos, match_pos))
+      })
+    })
+  }
+
+  /// Type `ch` at the caret. A closing bracket or quote that is already under the caret is typed
+  /// over (the caret moves past it). An opening bracket or quote is inserted w/ its closing one
+  /// after the caret, unless it is typed right before a word.
+  pub fn insert_char_paired(&mut self, ch: char) {
+    let CaretPos { row, col } = self.caret;
+    let typed = ch.to_string();
+    let line = &self.lines[row];
+    let maybe_next = line.grapheme_at(col);
+    let maybe_prev = col.checked_sub(1).and_then(|col| line.grapheme_at(col));
+
+    let is_closer = BRACKET_PAIRS.iter().any(|(_, closer)| *closer == typed);
+    if (is_closer || QUOTES.contains(&typed.as_str())) && maybe_next == Some(typed.as_str()) {
+      self.caret.col += 1;
+      return;
+    }
+
+    let is_before_word = maybe_next.is_some_and(|next| {
+      !next.trim().is_empty() && !BRACKET_PAIRS.iter().any(|(_, closer)| *closer == next)
+    });
+    let is_after_word =
+      maybe_prev.is_some_and(|prev| prev.chars().any(|it| it.is_alphanumeric() || it == '_'));
+    let maybe_closer = match typed.as_str() {
+      "'" if self.get_fold_kind() == FoldKind::Braces => None,
+      quote if QUOTES.contains(&quote) && !is_after_word => Some(quote),
+      opener => BRACKET_PAIRS
+        .iter()
+        .find(|(it, _)| *it == opener)
+        .map(|(_, closer)| *closer),
+    };
+
+    match maybe_closer {
+      Some(closer) if !is_before_word => {
+        self.insert_str(&format!("{}{}", typed, closer));
+        self.caret.col -= 1;
+      }
+      _ => self.insert_char(ch),
+    }
+  }
+
+  /// Delete the grapheme cluster before the caret (like [EditorBuffer::backspace]). If it is an
+  /// opening bracket or quote & its closing one is right after the caret (eg: after typing `(`),
+  /// then delete that as well.
+  pub fn backspace_paired(&mut self) {
+    let CaretPos { row, col } = self.caret;
+    let line = &self.lines[row];
+    let maybe_pair = col
+      .checked_sub(1)
+      .and_then(|col| line.grapheme_at(col))
+      .zip(line.grapheme_at(col));
+    let is_pair = maybe_pair.is_some_and(|(prev, next)| {
+      BRACKET_PAIRS.contains(&(prev, next)) || (prev == next && QUOTES.contains(&prev))
+    });
+    if is_pair {
+      self.delete();
+    }
+    self.backspace();
+  }
+
+  /// Find the bracket that matches the one at `pos` (`bracket`), scanning forward or back. Nested
+  /// pairs of the same brackets are skipped.
+  fn find_bracket(
+    &self, pos: CaretPos, bracket: &str, match_bracket: &str, is_forward: bool,
+  ) -> Option<CaretPos> {
+    let mut depth = 0;
+    let rows: Box<dyn Iterator<Item = usize>> = match is_forward {
+      true => Box::new(pos.row..self.line_count().min(pos.row + MAX_BRACKET_MATCH_ROWS)),
+      false => Box::new((pos.row.saturating_sub(MAX_BRACKET_MATCH_ROWS)..=pos.row).rev()),
+    };
+    for row in rows {
+      let graphemes: Vec<&str> = self.lines[row].graphemes(true).collect();
+      let cols: Box<dyn Iterator<Item = usize>> = match (is_forward, row == pos.row) {
+        (true, true) => Box::new(pos.col + 1..graphemes.len()),
+        (true, false) => Box::new(0..graphemes.len()),
+        (false, true) => Box::new((0..pos.col).rev()),
+        (false, false) => Box::new((0..graphemes.len()).rev()),
+      };
+      for col in cols {
+        if graphemes[col] == bracket {
+          depth += 1;
+        } else if graphemes[col] == match_bracket {
+          if depth == 0 {
+            return Some(CaretPos { row, col });
+          }
+          depth -= 1;
+        }
+      }
+    }
+    None
+  }
+}
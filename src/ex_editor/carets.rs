@@ -0,0 +1,519 @@
+/*
+ *   Copyright (c) 2022 R3BL LLC
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use std::cmp::Reverse;
+
+use super::*;
+
+/// A caret & its selection. The selection goes from `anchor` (where it was started) to `pos`, in
+/// either direction. There is no selection if `anchor` is `None` or the same as `pos`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Caret {
+  pub pos: CaretPos,
+  pub anchor: Option<CaretPos>,
+}
+
+impl From<CaretPos> for Caret {
+  fn from(pos: CaretPos) -> Self { Self { pos, anchor: None } }
+}
+
+impl Caret {
+  /// Start & end of the selection (in buffer order), if there is one.
+  pub fn get_selection(&self) -> Option<(CaretPos, CaretPos)> {
+    let anchor = self.anchor.filter(|it| *it != self.pos)?;
+    Some((anchor.min(self.pos), anchor.max(self.pos)))
+  }
+
+  /// Start of the selection, or the position of the caret if there is no selection.
+  pub fn get_start(&self) -> CaretPos {
+    self
+      .get_selection()
+      .map(|(start, _)| start)
+      .unwrap_or(self.pos)
+  }
+
+  /// Start & end of the selection, or the position of the caret twice if there is no selection.
+  fn get_range(&self) -> (CaretPos, CaretPos) {
+    self.get_selection().unwrap_or((self.pos, self.pos))
+  }
+
+  /// Whether the selections (or positions) of `self` & `other` overlap or touch, so that editing
+  /// at both would edit the same text twice.
+  fn is_touching(&self, other: &Caret) -> bool {
+    let ((start, end), (other_start, other_end)) = (self.get_range(), other.get_range());
+    start <= other_end && other_start <= end
+  }
+
+  /// One caret that selects both the selections of `self` & `other`, in the direction of
+  /// `self`'s selection (or of `other`'s, if `self` has none).
+  fn merge(&self, other: &Caret) -> Caret {
+    let ((start, end), (other_start, other_end)) = (self.get_range(), other.get_range());
+    let (start, end) = (start.min(other_start), end.max(other_end));
+    if start == end {
+      return start.into();
+    }
+    let is_backward = match (self.get_selection(), other.get_selection()) {
+      (Some(_), _) => self.anchor > Some(self.pos),
+      (None, Some(_)) => other.anchor > Some(other.pos),
+      (None, None) => false,
+    };
+    match is_backward {
+      true => Caret {
+        pos: start,
+        anchor: Some(end),
+      },
+      false => Caret {
+        pos: end,
+        anchor: Some(start),
+      },
+    }
+  }
+}
+
+/// What an edit at the carets does w/ the text that is selected.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EditSelection {
+  /// Delete the selected text & then run the edit (eg: typing replaces the selection).
+  Replace,
+  /// Only delete the selected text (eg: Backspace & Delete).
+  DeleteOnly,
+}
+
+/// Multiple carets. The primary caret is [EditorBuffer::caret] (w/ its selection in
+/// [EditorBuffer::selection_anchor]), it is the one that the viewport follows. Any other carets are
+/// in [EditorBuffer::extra_carets].
+impl EditorBuffer {
+  /// All the carets, the primary one first.
+  pub fn get_carets(&self) -> Vec<Caret> {
+    let primary = Caret {
+      pos: self.caret,
+      anchor: self.selection_anchor,
+    };
+    std::iter::once(primary)
+      .chain(self.extra_carets.iter().copied())
+      .collect()
+  }
+
+  /// Replace all the carets, the first one becomes the primary caret. Carets whose selections (or
+  /// positions) overlap or touch are merged into one (which takes the place of the first of them),
+  /// so that no text is edited twice by [EditorBuffer::edit_at_carets].
+  pub fn set_carets(&mut self, carets: Vec<Caret>) {
+    let mut merged_carets: Vec<Caret> = vec![];
+    for caret in carets {
+      merged_carets.push(caret);
+      // A merged caret can reach carets that the ones it was merged from didn't.
+      let mut index = merged_carets.len() - 1;
+      while let Some(other_index) = (0..merged_carets.len())
+        .find(|it| *it != index && merged_carets[*it].is_touching(&merged_carets[index]))
+      {
+        let (first_index, last_index) = (index.min(other_index), index.max(other_index));
+        merged_carets[first_index] = merged_carets[first_index].merge(&merged_carets[last_index]);
+        merged_carets.remove(last_index);
+        index = first_index;
+      }
+    }
+
+    let mut carets = merged_carets.into_iter();
+    let primary = carets.next().unwrap_or_default();
+    self.caret = primary.pos;
+    self.selection_anchor = primary.anchor;
+
+    let mut extra_carets: Vec<Caret> = carets.collect();
+    extra_carets.sort_by_key(|it| it.pos);
+    self.extra_carets = extra_carets;
+  }
+
+  pub fn has_extra_carets(&self) -> bool { !self.extra_carets.is_empty() }
+
+  /// Only keep the primary caret, w/out its selection.
+  pub fn clear_extra_carets(&mut self) { self.set_carets(vec![self.caret.into()]); }
+
+  /// Start & end of the selection of the primary caret, if there is one.
+  pub fn get_selection(&self) -> Option<(CaretPos, CaretPos)> {
+    self.get_carets()[0].get_selection()
+  }
+
+  /// Text between `start` & `end` (which must be in buffer order), lines are joined w/ `\n`.
+  pub fn get_text_in_range(&self, start: CaretPos, end: CaretPos) -> String {
+    let byte_index = |pos: CaretPos| self.lines[pos.row].byte_index_of_grapheme(pos.col);
+    if start.row == end.row {
+      return self.lines[start.row][byte_index(start)..byte_index(end)].to_string();
+    }
+    let mut text = self.lines[start.row][byte_index(start)..].to_string();
+    for line in self.lines.iter_range(start.row + 1..end.row) {
+      text.push('\n');
+      text.push_str(line);
+    }
+    text.push('\n');
+    text.push_str(&self.lines[end.row][..byte_index(end)]);
+    text
+  }
+
+  /// Delete the text between `start` & `end` (which must be in buffer order) & move the caret to
+  /// `start`. Positions past the end of the buffer are clamped to it.
+  pub fn delete_range(&mut self, start: CaretPos, end: CaretPos) {
+    let (start, end) = (self.clamp_caret_pos(start), self.clamp_caret_pos(end));
+    if end <= start {
+      self.caret = start;
+      return;
+    }
+    let end_byte_index = self.lines[end.row].byte_index_of_grapheme(end.col);
+    let rest = self.lines[end.row][end_byte_index..].to_string();
+    let start_line = &mut self.lines[start.row];
+    start_line.truncate(start_line.byte_index_of_grapheme(start.col));
+    start_line.push_str(&rest);
+    self.lines.remove_range(start.row + 1..end.row + 1);
+    self.caret = start;
+    self.mark_changed();
+  }
+
+  /// Delete the text that is selected by the primary caret (if any) & clear the selection.
+  /// Returns whether there was a selection.
+  pub fn delete_selection(&mut self) -> bool {
+    let maybe_selection = self.get_selection();
+    self.selection_anchor = None;
+    match maybe_selection {
+      Some((start, end)) => {
+        self.delete_range(start, end);
+        true
+      }
+      None => false,
+    }
+  }
+
+  /// Run `edit` (which works on the primary caret) at each caret, as a single change of the
+  /// buffer. If a caret has a selection, then it is handled as given by `edit_selection` first.
+  ///
+  /// The carets are edited from the bottom of the buffer up, so an edit never moves the carets
+  /// that are still to be edited. The carets that have already been edited are kept as offsets
+  /// from the end of the buffer, which don't change when text before them is edited.
+  pub fn edit_at_carets(
+    &mut self, edit_selection: EditSelection, mut edit: impl FnMut(&mut EditorBuffer),
+  ) {
+    let carets = self.get_carets();
+    let mut order: Vec<usize> = (0..carets.len()).collect();
+    order.sort_by_key(|index| Reverse(carets[*index].get_start()));
+
+    let mut offsets_from_end = vec![(0, 0); carets.len()];
+    for index in order {
+      self.caret = carets[index].pos;
+      self.selection_anchor = carets[index].anchor;
+      let had_selection = self.delete_selection();
+      if !had_selection || edit_selection == EditSelection::Replace {
+        edit(self);
+      }
+      offsets_from_end[index] = (
+        self.lines.len() - self.caret.row,
+        self.line_len(self.caret.row) - self.caret.col,
+      );
+    }
+
+    let carets = offsets_from_end
+      .into_iter()
+      .map(|(rows_from_end, cols_from_end)| {
+        let row = self.lines.len().saturating_sub(rows_from_end);
+        let col = self.line_len(row).saturating_sub(cols_from_end);
+        Caret::from(CaretPos { row, col })
+      })
+      .collect();
+    self.set_carets(carets);
+  }
+
+  /// Move each caret in `direction` & clear the selections.
+  pub fn move_carets(&mut self, direction: CaretDirection) {
+    let carets = self
+      .get_carets()
+      .into_iter()
+      .map(|caret| {
+        self.caret = caret.pos;
+        self.move_caret(direction);
+        Caret::from(self.caret)
+      })
+      .collect();
+    self.set_carets(carets);
+  }
+
+  /// Move each caret in `direction` & extend its selection (or start one where the caret was).
+  pub fn move_carets_selecting(&mut self, direction: CaretDirection) {
+    let carets = self
+      .get_carets()
+      .into_iter()
+      .map(|caret| {
+        self.caret = caret.pos;
+        self.move_caret(direction);
+        Caret {
+          pos: self.caret,
+          anchor: caret.anchor.or(Some(caret.pos)),
+        }
+      })
+      .collect();
+    self.set_carets(carets);
+  }
+
+  /// Move the primary caret to `pos` & extend its selection (eg: mouse drag). The other carets are
+  /// dropped.
+  pub fn select_to(&mut self, pos: CaretPos) {
+    let caret = Caret {
+      pos: self.clamp_caret_pos(pos),
+      anchor: self.selection_anchor.or(Some(self.caret)),
+    };
+    self.set_carets(vec![caret]);
+  }
+
+  /// Text that is selected by all the carets (in buffer order, joined w/ new lines), `None` if
+  /// nothing is selected.
+  pub fn get_selected_text(&self) -> Option<String> {
+    let mut selections: Vec<(CaretPos, CaretPos)> = self
+      .get_carets()
+      .iter()
+      .filter_map(Caret::get_selection)
+      .collect();
+    if selections.is_empty() {
+      return None;
+    }
+    selections.sort();
+    let texts: Vec<String> = selections
+      .into_iter()
+      .map(|(start, end)| self.get_text_in_range(start, end))
+      .collect();
+    Some(texts.join("\n"))
+  }
+
+  /// Add a caret at `pos` (eg: Alt+Click), which becomes the primary caret. If there already is a
+  /// caret there (& it isn't the only one), then it is removed instead.
+  pub fn toggle_caret(&mut self, pos: CaretPos) {
+    let pos = self.clamp_caret_pos(pos);
+    let mut carets = self.get_carets();
+    if carets.len() > 1 && carets.iter().any(|it| it.pos == pos) {
+      carets.retain(|it| it.pos != pos);
+    } else {
+      carets.insert(0, pos.into());
+    }
+    self.set_carets(carets);
+  }
+
+  /// Add a caret on the line above the top most caret (or below the bottom most one), at the same
+  /// display column as the primary caret. This is a column selection when it is repeated. The new
+  /// caret becomes the primary caret.
+  pub fn add_caret_vertically(&mut self, is_down: bool) {
+    let display_col = self.caret_display_col();
+    let carets = self.get_carets();
+    let maybe_row = match is_down {
+      true => carets
+        .iter()
+        .map(|it| it.pos.row + 1)
+        .max()
+        .filter(|row| *row < self.lines.len()),
+      false => carets
+        .iter()
+        .map(|it| it.pos.row)
+        .min()
+        .and_then(|row| row.checked_sub(1)),
+    };
+    if let Some(row) = maybe_row {
+      let col = self.lines[row].grapheme_index_at_display_col(display_col);
+      let mut carets = carets;
+      carets.insert(0, CaretPos { row, col }.into());
+      self.set_carets(carets);
+    }
+  }
+
+  /// Ctrl+D. If the primary caret has no selection, select the word under it. Otherwise add a
+  /// caret that selects the next occurrence of the selected text (after the primary caret,
+  /// wrapping around to the start of the buffer), which becomes the primary caret. Occurrences
+  /// that are already selected are skipped. Only selections inside of a line are supported.
+  pub fn select_next_occurrence(&mut self) {
+    let (start, end) = match self.get_selection() {
+      Some(selection) => selection,
+      None => {
+        if let Some(range) = self.lines[self.caret.row].word_range_at(self.caret.col) {
+          self.selection_anchor = Some(CaretPos {
+            row: self.caret.row,
+            col: range.start,
+          });
+          self.caret.col = range.end;
+        }
+        return;
+      }
+    };
+    if start.row != end.row {
+      return;
+    }
+
+    let needle = self.get_text_in_range(start, end);
+    let carets = self.get_carets();
+    let is_selected = |start: CaretPos, end: CaretPos| {
+      carets
+        .iter()
+        .any(|it| it.get_selection() == Some((start, end)))
+    };
+
+    let row_count = self.lines.len();
+    for offset in 0..=row_count {
+      let row = (end.row + offset) % row_count;
+      let line = self.lines[row].as_str();
+      // On the first row only search after the selection (& on the last, which is the same row
+      // after wrapping around, before it).
+      let search_start = if offset == 0 {
+        line.byte_index_of_grapheme(end.col)
+      } else {
+        0
+      };
+      for (byte_index, _) in line[search_start..].match_indices(&needle) {
+        let byte_index = search_start + byte_index;
+        let found_start = CaretPos {
+          row,
+          col: line[..byte_index].grapheme_count(),
+        };
+        let found_end = CaretPos {
+          row,
+          col: line[..byte_index + needle.len()].grapheme_count(),
+        };
+        if !is_selected(found_start, found_end) {
+          let mut carets = carets.clone();
+          carets.insert(
+            0,
+            Caret {
+              pos: found_end,
+              anchor: Some(found_start),
+            },
+          );
+          self.set_carets(carets);
+          return;
+        }
+      }
+    }
+  }
+
+  /// Closest position to `pos` that is inside of the buffer.
+  pub fn clamp_caret_pos(&self, pos: CaretPos) -> CaretPos {
+    let row = pos.row.min(self.lines.len() - 1);
+    CaretPos {
+      row,
+      col: pos.col.min(self.line_len(row)),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn pos(row: usize, col: usize) -> CaretPos { CaretPos { row, col } }
+
+  /// A caret at `to` w/ a selection that was started at `from`.
+  fn select(from: CaretPos, to: CaretPos) -> Caret {
+    Caret {
+      pos: to,
+      anchor: Some(from),
+    }
+  }
+
+  fn get_carets_after_set(carets: Vec<Caret>) -> Vec<Caret> {
+    let mut buffer = EditorBuffer::from_content("abcdefgh\nijklmnop\nqrstuvwx");
+    buffer.set_carets(carets);
+    buffer.get_carets()
+  }
+
+  #[test]
+  fn test_carets_at_same_pos_are_merged() {
+    let carets = get_carets_after_set(vec![pos(0, 2).into(), pos(1, 3).into(), pos(0, 2).into()]);
+    assert_eq!(carets, vec![pos(0, 2).into(), pos(1, 3).into()]);
+  }
+
+  #[test]
+  fn test_carets_that_dont_touch_are_kept_in_order() {
+    let carets = get_carets_after_set(vec![
+      pos(1, 0).into(),
+      select(pos(2, 5), pos(2, 1)),
+      select(pos(0, 1), pos(0, 3)),
+    ]);
+    // The first one stays the primary caret, the others are sorted.
+    assert_eq!(
+      carets,
+      vec![
+        pos(1, 0).into(),
+        select(pos(0, 1), pos(0, 3)),
+        select(pos(2, 5), pos(2, 1)),
+      ]
+    );
+  }
+
+  #[test]
+  fn test_overlapping_selections_are_merged() {
+    let carets = get_carets_after_set(vec![
+      select(pos(0, 1), pos(0, 4)),
+      select(pos(0, 3), pos(1, 2)),
+    ]);
+    assert_eq!(carets, vec![select(pos(0, 1), pos(1, 2))]);
+  }
+
+  #[test]
+  fn test_merged_selection_keeps_direction_of_first() {
+    let carets = get_carets_after_set(vec![
+      select(pos(0, 4), pos(0, 1)),
+      select(pos(0, 3), pos(0, 6)),
+    ]);
+    assert_eq!(carets, vec![select(pos(0, 6), pos(0, 1))]);
+    // W/out a selection, the direction of the other one is kept.
+    let carets = get_carets_after_set(vec![pos(0, 3).into(), select(pos(0, 5), pos(0, 2))]);
+    assert_eq!(carets, vec![select(pos(0, 5), pos(0, 2))]);
+  }
+
+  #[test]
+  fn test_touching_selections_are_merged() {
+    let carets = get_carets_after_set(vec![
+      select(pos(0, 1), pos(0, 3)),
+      select(pos(0, 3), pos(0, 5)),
+    ]);
+    assert_eq!(carets, vec![select(pos(0, 1), pos(0, 5))]);
+  }
+
+  #[test]
+  fn test_caret_inside_selection_is_merged() {
+    let carets = get_carets_after_set(vec![
+      pos(2, 0).into(),
+      pos(1, 4).into(),
+      select(pos(0, 6), pos(1, 6)),
+    ]);
+    assert_eq!(carets, vec![pos(2, 0).into(), select(pos(0, 6), pos(1, 6))]);
+  }
+
+  #[test]
+  fn test_merged_caret_reaches_more_carets() {
+    // The last selection joins the first two, which don't touch each other.
+    let carets = get_carets_after_set(vec![
+      select(pos(0, 0), pos(0, 2)),
+      select(pos(0, 4), pos(0, 6)),
+      pos(2, 2).into(),
+      select(pos(0, 1), pos(0, 5)),
+    ]);
+    assert_eq!(carets, vec![select(pos(0, 0), pos(0, 6)), pos(2, 2).into()]);
+  }
+
+  #[test]
+  fn test_edit_at_carets() {
+    let mut buffer = EditorBuffer::from_content("ab\ncd");
+    buffer.set_carets(vec![pos(0, 1).into(), select(pos(1, 0), pos(1, 2))]);
+    buffer.edit_at_carets(EditSelection::Replace, |buffer| buffer.insert_char('X'));
+    assert_eq!(buffer.get_as_string(), "aXb\nX");
+    assert_eq!(
+      buffer.get_carets(),
+      vec![pos(0, 2).into(), pos(1, 1).into()]
+    );
+  }
+}
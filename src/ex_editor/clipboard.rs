@@ -0,0 +1,28 @@
+/*
+ *   Copyright (c) 2022 R3BL LLC
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use std::io::{stdout, Write};
+
+/// Copy `text` to the clipboard of the terminal w/ an OSC 52 escape sequence. This works over SSH
+/// (the terminal on the local machine sets its clipboard), but not every terminal supports it, so
+/// copied text is also kept in [EditorState::clipboard](crate::EditorState), which is what is
+/// pasted from.
+pub fn copy_to_terminal_clipboard(text: &str) -> std::io::Result<()> {
+  let mut stdout = stdout();
+  write!(stdout, "\x1b]52;c;{}\x07", base64::encode(text))?;
+  stdout.flush()
+}
@@ -0,0 +1,593 @@
+/*
+ *   Copyright (c) 2022 R3BL LLC
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use std::{collections::{HashMap, VecDeque},
+          io,
+          net::SocketAddr,
+          sync::{Arc, Mutex}};
+
+use r3bl_rs_utils::*;
+use serde::{Deserialize, Serialize};
+use tokio::{io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader},
+            net::{TcpListener, TcpStream},
+            sync::mpsc::{self, UnboundedSender}};
+
+use super::*;
+
+/// Peer id of the host, in the carets it sends & in the links of its guests.
+pub const HOST_PEER_ID: u32 = 0;
+
+/// Colors of the carets of the peers (by peer id), so that each one is told apart.
+pub const PEER_CARET_COLORS: [(u8, u8, u8); 4] = [
+  (230, 90, 90),
+  (90, 200, 110),
+  (230, 160, 50),
+  (190, 100, 230),
+];
+
+/// How this instance shares its first buffer w/ other instances (`edit --share <address>` or
+/// `edit --join <address>`). An address w/ a `/` in it is the path of a Unix socket, any other one
+/// is a TCP address (eg: `127.0.0.1:7777`).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CollabRole {
+  /// Listen for guests, & share the buffer w/ them.
+  Host(String),
+  /// Connect to a host, & edit its buffer (which replaces the one of this instance).
+  Guest(String),
+}
+
+/// Message between a host & a guest. They are sent as JSON, one per line.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CollabMessage {
+  /// An edit of the shared buffer. `received_count` is the number of edits that the sender had
+  /// received from the receiver when it made this one.
+  Edit {
+    op: TextOperation,
+    received_count: u64,
+  },
+  /// The caret of a peer moved (to a char offset), or it left (`None`). The host replaces the peer
+  /// id of the carets that it gets from its guests w/ their own.
+  Caret {
+    peer_id: u32,
+    maybe_offset: Option<usize>,
+    received_count: u64,
+  },
+}
+
+/// State of the buffer that is shared w/ other instances (see [CollabRole]).
+///
+/// Edits are merged w/ operational transformation (see [TextOperation]): each link between the
+/// host & a guest keeps the edits that the peer hasn't acknowledged yet, & transforms the edits
+/// that it receives against them (the host's edits go first when both insert at the same
+/// position). The host applies the edits of each guest & sends them on to the others as its own.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CollabState {
+  /// Id of the shared buffer.
+  pub id: String,
+  pub is_host: bool,
+  /// Links to the peers, keyed by peer id (a guest only has one, to the host).
+  pub links: HashMap<u32, CollabLink>,
+  /// Carets of the other instances, as char offsets in the shared buffer, keyed by peer id.
+  pub peer_carets: HashMap<u32, usize>,
+}
+
+/// One end of the connection between the host & a guest.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CollabLink {
+  /// Edits that were made for the peer (& are sent to it by [CollabSession]) that it hasn't
+  /// acknowledged yet.
+  pub pending_edits: VecDeque<PendingEdit>,
+  /// Number of edits that were made for the peer, & received from it.
+  pub sent_count: u64,
+  pub received_count: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PendingEdit {
+  /// The message for the edit, as it is sent.
+  pub message: CollabMessage,
+  /// The edit, transformed against the edits that were received since it was made.
+  pub op: TextOperation,
+}
+
+impl CollabLink {
+  /// Index of the first pending edit.
+  fn get_first_pending_index(&self) -> u64 { self.sent_count - self.pending_edits.len() as u64 }
+
+  fn push_edit(&mut self, op: &TextOperation) {
+    self.pending_edits.push_back(PendingEdit {
+      message: CollabMessage::Edit {
+        op: op.clone(),
+        received_count: self.received_count,
+      },
+      op: op.clone(),
+    });
+    self.sent_count += 1;
+  }
+
+  /// Drop the pending edits that the peer had received before it sent a message.
+  fn drop_acknowledged_edits(&mut self, received_count: u64) {
+    while !self.pending_edits.is_empty() && self.get_first_pending_index() < received_count {
+      self.pending_edits.pop_front();
+    }
+  }
+
+  /// Transform a caret of the peer (a char offset in its buffer) into one in this buffer.
+  fn transform_offset(&self, offset: usize) -> usize {
+    self
+      .pending_edits
+      .iter()
+      .fold(offset, |offset, it| it.op.transform_index(offset))
+  }
+
+  /// Transform an edit of the peer against the pending edits, into one that applies to this
+  /// buffer (`None` if they don't apply to the same text).
+  fn transform_edit(&mut self, op: &TextOperation, is_host: bool) -> Option<TextOperation> {
+    let mut op = op.clone();
+    for pending_edit in self.pending_edits.iter_mut() {
+      // The host's edit is always on the left, so both ends put the same text first.
+      let (pending_op, transformed_op) = match is_host {
+        true => TextOperation::transform(&pending_edit.op, &op)?,
+        false => {
+          let (op, pending_op) = TextOperation::transform(&op, &pending_edit.op)?;
+          (pending_op, op)
+        }
+      };
+      pending_edit.op = pending_op;
+      op = transformed_op;
+    }
+    self.received_count += 1;
+    Some(op)
+  }
+}
+
+impl CollabState {
+  pub fn new(id: &str, is_host: bool) -> Self {
+    let mut links = HashMap::new();
+    if !is_host {
+      links.insert(HOST_PEER_ID, CollabLink::default());
+    }
+    Self {
+      id: id.into(),
+      is_host,
+      links,
+      peer_carets: HashMap::new(),
+    }
+  }
+
+  /// A guest joined the host, its buffer starts out empty & is filled by the first edit.
+  pub fn add_guest(&mut self, peer_id: u32, text: &str) {
+    let mut link = CollabLink::default();
+    let mut op = TextOperation::default();
+    op.insert(text);
+    link.push_edit(&op);
+    self.links.insert(peer_id, link);
+  }
+
+  pub fn remove_peer(&mut self, peer_id: u32) {
+    self.links.remove(&peer_id);
+    self.peer_carets.remove(&peer_id);
+  }
+
+  /// Send an edit of the shared buffer that was made in this instance to all the peers.
+  pub fn apply_local_edit(&mut self, op: &TextOperation) {
+    self.transform_peer_carets(op);
+    for link in self.links.values_mut() {
+      link.push_edit(op);
+    }
+  }
+
+  /// Handle a message from the peer w/ `peer_id`, & return the edit to apply to the shared buffer
+  /// (if it is one). An `Err` means that the instances are out of sync.
+  pub fn receive(
+    &mut self, peer_id: u32, message: &CollabMessage,
+  ) -> Result<Option<TextOperation>, String> {
+    let is_host = self.is_host;
+    let link = match self.links.get_mut(&peer_id) {
+      Some(link) => link,
+      None => return Ok(None),
+    };
+    match message {
+      CollabMessage::Edit { op, received_count } => {
+        link.drop_acknowledged_edits(*received_count);
+        let op = link
+          .transform_edit(op, is_host)
+          .ok_or_else(|| format!("Out of sync w/ peer {}, it was disconnected", peer_id))?;
+        self.transform_peer_carets(&op);
+        // The host sends the edits of each guest on to the others.
+        for (other_peer_id, other_link) in self.links.iter_mut() {
+          if *other_peer_id != peer_id {
+            other_link.push_edit(&op);
+          }
+        }
+        Ok(Some(op))
+      }
+      CollabMessage::Caret {
+        peer_id: caret_peer_id,
+        maybe_offset,
+        received_count,
+      } => {
+        link.drop_acknowledged_edits(*received_count);
+        let caret_peer_id = if is_host { peer_id } else { *caret_peer_id };
+        match maybe_offset {
+          Some(offset) => {
+            let offset = link.transform_offset(*offset);
+            self.peer_carets.insert(caret_peer_id, offset);
+          }
+          None => {
+            self.peer_carets.remove(&caret_peer_id);
+          }
+        }
+        Ok(None)
+      }
+    }
+  }
+
+  fn transform_peer_carets(&mut self, op: &TextOperation) {
+    for offset in self.peer_carets.values_mut() {
+      *offset = op.transform_index(*offset);
+    }
+  }
+}
+
+/// Char offsets of the shared buffer, for [TextOperation]s. They are found w/ the char counts of
+/// the chunks of the [LineRope], so only the lines around them are counted.
+impl EditorBuffer {
+  pub fn get_char_offset(&self, pos: CaretPos) -> usize {
+    let col_len = self.lines.get(pos.row).map_or(0, |line| {
+      line[..line.byte_index_of_grapheme(pos.col)].chars().count()
+    });
+    self.lines.get_char_offset(pos.row) + col_len
+  }
+
+  /// The position of the grapheme cluster that the char at `offset` is in (clamped to the end of
+  /// the buffer).
+  pub fn get_pos_at_char_offset(&self, offset: usize) -> CaretPos {
+    let (row, char_index) = self.lines.locate_char_offset(offset);
+    let line = match self.lines.get(row) {
+      Some(line) => line,
+      None => return CaretPos::default(),
+    };
+    let byte_index = line
+      .char_indices()
+      .nth(char_index)
+      .map_or(line.len(), |(byte_index, _)| byte_index);
+    CaretPos {
+      row,
+      col: line[..byte_index].grapheme_count(),
+    }
+  }
+
+  /// Apply an edit of a peer, & keep the carets (& selections) & the folds where they were in the
+  /// text. Only the lines that it changes are replaced. `None` if the edit doesn't apply to the
+  /// buffer.
+  pub fn apply_operation(&mut self, op: &TextOperation) -> Option<()> {
+    if op.base_len != self.lines.get_char_len() {
+      return None;
+    }
+    let changed_range = match op.get_changed_range() {
+      Some(changed_range) => changed_range,
+      None => return Some(()),
+    };
+
+    let transform_pos =
+      |buffer: &EditorBuffer, pos: CaretPos| op.transform_index(buffer.get_char_offset(pos));
+    let offsets: Vec<(usize, Option<usize>)> = self
+      .get_carets()
+      .into_iter()
+      .map(|caret| {
+        let maybe_anchor = caret.anchor.map(|anchor| transform_pos(self, anchor));
+        (transform_pos(self, caret.pos), maybe_anchor)
+      })
+      .collect();
+
+    // The part of `op` between the first & the last change, applied to the lines that they are in.
+    let (start_row, _) = self.lines.locate_char_offset(changed_range.start);
+    let (end_row, _) = self.lines.locate_char_offset(changed_range.end);
+    let start_offset = self.lines.get_char_offset(start_row);
+    let old_lines: Vec<&str> = self
+      .lines
+      .iter_range(start_row..end_row + 1)
+      .map(String::as_str)
+      .collect();
+    let old_text = old_lines.join("\n");
+    let mut lines_op = TextOperation::default();
+    lines_op.retain(changed_range.start - start_offset);
+    let skip_start = matches!(op.components.first(), Some(OpComponent::Retain(_))) as usize;
+    let skip_end = matches!(op.components.last(), Some(OpComponent::Retain(_))) as usize;
+    for component in &op.components[skip_start..op.components.len() - skip_end] {
+      lines_op.push(component.clone());
+    }
+    lines_op.retain(start_offset + old_text.chars().count() - changed_range.end);
+    let new_text = lines_op.apply(&old_text)?;
+
+    let old_line_count = end_row + 1 - start_row;
+    self.lines.remove_range(start_row..end_row + 1);
+    let mut new_line_count = 0;
+    for line in new_text.split('\n') {
+      self.lines.insert(start_row + new_line_count, line.into());
+      new_line_count += 1;
+    }
+    let delta = new_line_count as isize - old_line_count as isize;
+    if delta != 0 {
+      self.shift_folds(start_row + 1, delta);
+    }
+
+    let carets = offsets
+      .into_iter()
+      .map(|(offset, maybe_anchor)| Caret {
+        pos: self.get_pos_at_char_offset(offset),
+        anchor: maybe_anchor.map(|anchor| self.get_pos_at_char_offset(anchor)),
+      })
+      .collect();
+    self.set_carets(carets);
+    self.mark_changed();
+    Some(())
+  }
+}
+
+/// Connects the instances that share a buffer (see [CollabState]). It gets the state on each render
+/// (like [Autosaver]), & sends the edits & the carets that the peers don't have yet. The
+/// connections are started on the first render, when there is a [SharedStore] to dispatch the
+/// messages that arrive to.
+#[derive(Clone, Debug, Default)]
+pub struct CollabSession {
+  inner: Arc<Mutex<CollabSessionInner>>,
+}
+
+#[derive(Debug, Default)]
+struct CollabSessionInner {
+  maybe_pending_connection: Option<PendingConnection>,
+  /// Channels to the tasks that write to the connection of each peer, keyed by peer id.
+  senders: HashMap<u32, UnboundedSender<CollabMessage>>,
+  /// Number of edits that were sent to each peer.
+  sent_counts: HashMap<u32, u64>,
+  /// Last caret (& received count) that was sent to each peer for each peer id, keyed by both.
+  sent_carets: HashMap<(u32, u32), (Option<usize>, u64)>,
+}
+
+#[derive(Debug)]
+enum PendingConnection {
+  TcpListener(TcpListener),
+  TcpStream(TcpStream),
+  #[cfg(unix)]
+  UnixListener(tokio::net::UnixListener),
+  #[cfg(unix)]
+  UnixStream(tokio::net::UnixStream),
+}
+
+impl CollabSession {
+  /// Listen on (or connect to) the address of `role`.
+  pub async fn connect(role: &CollabRole) -> io::Result<CollabSession> {
+    let pending_connection = match role {
+      #[cfg(unix)]
+      CollabRole::Host(address) if address.contains('/') => {
+        PendingConnection::UnixListener(bind_unix_socket(address).await?)
+      }
+      #[cfg(unix)]
+      CollabRole::Guest(address) if address.contains('/') => {
+        PendingConnection::UnixStream(tokio::net::UnixStream::connect(address).await?)
+      }
+      CollabRole::Host(address) => {
+        PendingConnection::TcpListener(TcpListener::bind(address).await?)
+      }
+      CollabRole::Guest(address) => {
+        PendingConnection::TcpStream(TcpStream::connect(address).await?)
+      }
+    };
+    let session = CollabSession::default();
+    lock(&session.inner).maybe_pending_connection = Some(pending_connection);
+    Ok(session)
+  }
+
+  /// The address that the host listens on (eg: w/ the port that was picked for `127.0.0.1:0`),
+  /// until it is spawned. `None` for a guest or a Unix socket.
+  pub fn get_local_addr(&self) -> Option<SocketAddr> {
+    match &lock(&self.inner).maybe_pending_connection {
+      Some(PendingConnection::TcpListener(listener)) => listener.local_addr().ok(),
+      _ => None,
+    }
+  }
+
+  /// Start accepting guests (for the host), or talking to the host (for a guest).
+  pub fn spawn(&self, shared_store: &SharedStore<EditorState, EditorAction>) {
+    let pending_connection = match lock(&self.inner).maybe_pending_connection.take() {
+      Some(pending_connection) => pending_connection,
+      None => return,
+    };
+    let session = self.clone();
+    let shared_store = shared_store.clone();
+    match pending_connection {
+      PendingConnection::TcpStream(stream) => {
+        tokio::spawn(session.run_connection(stream, HOST_PEER_ID, shared_store));
+      }
+      PendingConnection::TcpListener(listener) => {
+        tokio::spawn(async move {
+          for peer_id in HOST_PEER_ID + 1.. {
+            match listener.accept().await {
+              Ok((stream, _)) => session.add_guest(stream, peer_id, &shared_store).await,
+              Err(_) => break,
+            }
+          }
+        });
+      }
+      #[cfg(unix)]
+      PendingConnection::UnixStream(stream) => {
+        tokio::spawn(session.run_connection(stream, HOST_PEER_ID, shared_store));
+      }
+      #[cfg(unix)]
+      PendingConnection::UnixListener(listener) => {
+        tokio::spawn(async move {
+          for peer_id in HOST_PEER_ID + 1.. {
+            match listener.accept().await {
+              Ok((stream, _)) => session.add_guest(stream, peer_id, &shared_store).await,
+              Err(_) => break,
+            }
+          }
+        });
+      }
+    }
+  }
+
+  /// Send the edits & carets in `state` that the peers don't have yet.
+  pub fn update(&self, state: &EditorState) {
+    let collab = match &state.collab {
+      Some(collab) => collab,
+      None => return,
+    };
+    let maybe_offset = state
+      .get_buffer(&collab.id)
+      .map(|buffer| buffer.get_char_offset(buffer.caret));
+
+    let mut inner = lock(&self.inner);
+    let inner = &mut *inner;
+    for (peer_id, link) in &collab.links {
+      let sender = match inner.senders.get(peer_id) {
+        Some(sender) => sender,
+        None => continue,
+      };
+      let sent_count = inner.sent_counts.entry(*peer_id).or_default();
+      let first_index = link.get_first_pending_index();
+      for (index, pending_edit) in (first_index..).zip(&link.pending_edits) {
+        if index >= *sent_count {
+          let _ = sender.send(pending_edit.message.clone());
+        }
+      }
+      *sent_count = link.sent_count;
+
+      // The caret of this instance, & the ones of the other guests (for the host).
+      let mut carets: Vec<(u32, Option<usize>)> = vec![(HOST_PEER_ID, maybe_offset)];
+      for (caret_peer_id, offset) in &collab.peer_carets {
+        if collab.is_host && caret_peer_id != peer_id {
+          carets.push((*caret_peer_id, Some(*offset)));
+        }
+      }
+      for (to_peer_id, caret_peer_id) in inner.sent_carets.keys() {
+        let is_gone = !carets.iter().any(|(it, _)| it == caret_peer_id);
+        if to_peer_id == peer_id && is_gone {
+          carets.push((*caret_peer_id, None));
+        }
+      }
+      for (caret_peer_id, maybe_offset) in carets {
+        let key = (*peer_id, caret_peer_id);
+        let value = (maybe_offset, link.received_count);
+        if inner.sent_carets.get(&key) == Some(&value) {
+          continue;
+        }
+        let _ = sender.send(CollabMessage::Caret {
+          peer_id: caret_peer_id,
+          maybe_offset,
+          received_count: link.received_count,
+        });
+        match maybe_offset {
+          Some(_) => inner.sent_carets.insert(key, value),
+          None => inner.sent_carets.remove(&key),
+        };
+      }
+    }
+  }
+
+  async fn add_guest<S>(
+    &self, stream: S, peer_id: u32, shared_store: &SharedStore<EditorState, EditorAction>,
+  ) where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+  {
+    // The guest is added to the state before its messages are read.
+    let (reader, writer) = tokio::io::split(stream);
+    self.spawn_writer(writer, peer_id);
+    shared_store
+      .write()
+      .await
+      .dispatch_action(EditorAction::CollabPeerJoined(peer_id))
+      .await;
+    tokio::spawn(
+      self
+        .clone()
+        .read_messages(reader, peer_id, shared_store.clone()),
+    );
+  }
+
+  async fn run_connection<S>(
+    self, stream: S, peer_id: u32, shared_store: SharedStore<EditorState, EditorAction>,
+  ) where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+  {
+    let (reader, writer) = tokio::io::split(stream);
+    self.spawn_writer(writer, peer_id);
+    self.read_messages(reader, peer_id, shared_store).await;
+  }
+
+  fn spawn_writer<W>(&self, mut writer: W, peer_id: u32)
+  where
+    W: AsyncWrite + Unpin + Send + 'static,
+  {
+    let (sender, mut receiver) = mpsc::unbounded_channel::<CollabMessage>();
+    lock(&self.inner).senders.insert(peer_id, sender);
+    tokio::spawn(async move {
+      while let Some(message) = receiver.recv().await {
+        let mut line = serde_json::to_string(&message).unwrap_or_default();
+        line.push('\n');
+        if writer.write_all(line.as_bytes()).await.is_err() {
+          break;
+        }
+      }
+    });
+  }
+
+  /// Dispatch the messages from a peer, until it disconnects (or sends one that isn't valid).
+  async fn read_messages<R>(
+    self, reader: R, peer_id: u32, shared_store: SharedStore<EditorState, EditorAction>,
+  ) where
+    R: AsyncRead + Unpin,
+  {
+    let mut lines = BufReader::new(reader).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+      let message = match serde_json::from_str(&line) {
+        Ok(message) => message,
+        Err(_) => break,
+      };
+      let action = EditorAction::CollabReceive(peer_id, message);
+      shared_store.write().await.dispatch_action(action).await;
+    }
+    {
+      let mut inner = lock(&self.inner);
+      inner.senders.remove(&peer_id);
+      inner.sent_counts.remove(&peer_id);
+      inner
+        .sent_carets
+        .retain(|(to_peer_id, _), _| *to_peer_id != peer_id);
+    }
+    let action = EditorAction::CollabPeerLeft(peer_id);
+    shared_store.write().await.dispatch_action(action).await;
+  }
+}
+
+/// A socket file that is left over from a host that didn't exit normally (nothing accepts
+/// connections on it) is replaced.
+#[cfg(unix)]
+async fn bind_unix_socket(path: &str) -> io::Result<tokio::net::UnixListener> {
+  match tokio::net::UnixListener::bind(path) {
+    Err(error) if error.kind() == io::ErrorKind::AddrInUse => {
+      if tokio::net::UnixStream::connect(path).await.is_ok() {
+        return Err(error);
+      }
+      std::fs::remove_file(path)?;
+      tokio::net::UnixListener::bind(path)
+    }
+    result => result,
+  }
+}
@@ -0,0 +1,75 @@
+/*
+ *   Copyright (c) 2022 R3BL LLC
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use std::{env, io::ErrorKind, path::PathBuf};
+
+use serde::Deserialize;
+
+use super::*;
+
+/// Editor settings from `$XDG_CONFIG_HOME/r3bl-cmdr/editor.toml` (or
+/// `~/.config/r3bl-cmdr/editor.toml`), eg:
+///
+/// ```toml
+/// # One of: default, vi, emacs.
+/// key_bindings = "emacs"
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EditorConfig {
+  pub key_bindings: KeyBindings,
+}
+
+impl EditorConfig {
+  /// Load the config file. If it can't be loaded, then the default config and a message describing
+  /// what went wrong are returned (like [load_file]), a missing config file is not an error.
+  pub async fn load() -> (EditorConfig, Option<String>) {
+    let file_path = match get_config_file_path() {
+      Some(file_path) => file_path,
+      None => return (EditorConfig::default(), None),
+    };
+    let content = match tokio::fs::read_to_string(&file_path).await {
+      Ok(content) => content,
+      Err(error) if error.kind() == ErrorKind::NotFound => return (EditorConfig::default(), None),
+      Err(error) => {
+        let message = format!("Can't read {}: {}", file_path.display(), error);
+        return (EditorConfig::default(), Some(message));
+      }
+    };
+    match toml::from_str(&content) {
+      Ok(config) => (config, None),
+      Err(error) => {
+        let message = format!("Invalid config in {}: {}", file_path.display(), error);
+        (EditorConfig::default(), Some(message))
+      }
+    }
+  }
+}
+
+fn get_config_file_path() -> Option<PathBuf> {
+  Some(get_app_dir("XDG_CONFIG_HOME", ".config")?.join("editor.toml"))
+}
+
+/// The app's directory under the XDG base directory in `env_var`, or under `fallback_dir` in the
+/// home directory if it isn't set (eg: `~/.config/r3bl-cmdr`).
+pub fn get_app_dir(env_var: &str, fallback_dir: &str) -> Option<PathBuf> {
+  let base_dir = match env::var_os(env_var) {
+    Some(base_dir) if !base_dir.is_empty() => PathBuf::from(base_dir),
+    _ => PathBuf::from(env::var_os("HOME")?).join(fallback_dir),
+  };
+  Some(base_dir.join("r3bl-cmdr"))
+}
@@ -0,0 +1,448 @@
+/*
+ *   Copyright (c) 2022 R3BL LLC
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use std::{fmt::{Display, Formatter},
+          ops::Range};
+
+use super::*;
+
+/// Buffers w/ more lines than this are too large to count all the matches of the search query on
+/// each render, or to highlight from the first line down to the viewport.
+pub const LARGE_BUFFER_LINE_COUNT: usize = 50_000;
+
+/// Logical position of the caret inside an [EditorBuffer]. `row` is the index of the line and
+/// `col` is the index of the grapheme cluster in that line. This is not a display position, use
+/// [EditorBuffer::caret_display_col] to get the display column (which accounts for wide grapheme
+/// clusters like CJK characters & emoji).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CaretPos {
+  pub row: usize,
+  pub col: usize,
+}
+
+impl Display for CaretPos {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(f, "[row: {}, col: {}]", self.row, self.col)
+  }
+}
+
+/// Direction in which to move the caret.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CaretDirection {
+  Up,
+  Down,
+  Left,
+  Right,
+  /// Move up by the given number of rows (the height of the viewport).
+  PageUp(usize),
+  /// Move down by the given number of rows (the height of the viewport).
+  PageDown(usize),
+  /// Start of the current line.
+  LineStart,
+  /// End of the current line.
+  LineEnd,
+  /// Start of the first line.
+  BufferStart,
+  /// End of the last line.
+  BufferEnd,
+  /// Move up by one visual line, when the lines are soft wrapped at the given width.
+  VisualLineUp(usize),
+  /// Move down by one visual line, when the lines are soft wrapped at the given width.
+  VisualLineDown(usize),
+  /// Start of the next word, or of the next empty line (vi `w`).
+  WordForward,
+  /// Start of the previous word, or of the previous empty line (vi `b`).
+  WordBackward,
+  /// Last grapheme cluster of the next word (vi `e`).
+  WordEnd,
+  /// After the end of the next word, where only letters, digits & `_` make up words (emacs
+  /// `M-f`).
+  WordRight,
+  /// Start of the previous word, where only letters, digits & `_` make up words (emacs `M-b`).
+  WordLeft,
+}
+
+/// Line ending of the file that a buffer was loaded from, it is used when the buffer is saved.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LineEnding {
+  #[default]
+  Lf,
+  CrLf,
+}
+
+impl LineEnding {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      LineEnding::Lf => "\n",
+      LineEnding::CrLf => "\r\n",
+    }
+  }
+}
+
+impl Display for LineEnding {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      LineEnding::Lf => write!(f, "LF"),
+      LineEnding::CrLf => write!(f, "CRLF"),
+    }
+  }
+}
+
+/// Data buffer for the editor. There is always at least one (possibly empty) line in the buffer.
+///
+/// `is_dirty` is set by any method that changes the content of the buffer, and it is cleared when
+/// the buffer is saved. `change_count` is incremented by any such method, and is never reset, so it
+/// can be used to detect whether an operation changed the content.
+///
+/// `saved_lines` are the lines of the file when it was last loaded or saved (`None` if they aren't
+/// known), so that undo & redo can tell whether they took the buffer back to the file's content
+/// (see [EditorBuffer::update_is_dirty]).
+///
+/// `caret` is the primary caret, see [Caret] for multiple carets & selections.
+///
+/// `is_loading` is set while the rest of a large file is being loaded in the background (see
+/// [spawn_load_rest]), the buffer can't be edited or saved until it is done.
+///
+/// `folds` are the ranges of rows that are hidden by code folding (see [FoldKind]).
+///
+/// `indent_style` is used to auto-indent new lines, it comes from the config files of the file's
+/// project (see [IndentStyle::load]).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EditorBuffer {
+  pub lines: LineRope,
+  pub caret: CaretPos,
+  pub selection_anchor: Option<CaretPos>,
+  pub extra_carets: Vec<Caret>,
+  pub file_path: Option<String>,
+  pub line_ending: LineEnding,
+  pub is_dirty: bool,
+  pub is_loading: bool,
+  pub change_count: u64,
+  pub saved_lines: Option<LineRope>,
+  pub folds: Vec<Range<usize>>,
+  pub indent_style: IndentStyle,
+}
+
+impl Default for EditorBuffer {
+  fn default() -> Self {
+    Self {
+      lines: LineRope::from(vec![String::new()]),
+      caret: CaretPos::default(),
+      selection_anchor: None,
+      extra_carets: vec![],
+      file_path: None,
+      line_ending: LineEnding::default(),
+      is_dirty: false,
+      is_loading: false,
+      change_count: 0,
+      saved_lines: None,
+      folds: vec![],
+      indent_style: IndentStyle::default(),
+    }
+  }
+}
+
+impl Display for EditorBuffer {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "EditorBuffer {{ file_path: {:?}, lines: {}, caret: {}, is_dirty: {} }}",
+      self.file_path,
+      self.lines.len(),
+      self.caret,
+      self.is_dirty
+    )
+  }
+}
+
+impl EditorBuffer {
+  /// The line ending of the buffer is CRLF if the first line of `content` ends w/ one.
+  pub fn from_content(content: &str) -> Self {
+    let mut lines: LineRope = content.lines().map(String::from).collect();
+    if lines.is_empty() {
+      lines.push(String::new());
+    }
+    let line_ending = match content.split('\n').next() {
+      Some(first_line) if first_line.ends_with('\r') => LineEnding::CrLf,
+      _ => LineEnding::Lf,
+    };
+    Self {
+      lines,
+      line_ending,
+      ..Default::default()
+    }
+  }
+
+  pub fn get_as_string(&self) -> String { self.lines.join("\n") }
+
+  /// Content to write to a file, which ends w/ a new line (unless the buffer is empty). Lines end
+  /// w/ the buffer's line ending.
+  pub fn get_as_file_content(&self) -> String {
+    if self.lines.len() == 1 && self.lines[0].is_empty() {
+      String::new()
+    } else {
+      let line_ending = self.line_ending.as_str();
+      format!("{}{}", self.lines.join(line_ending), line_ending)
+    }
+  }
+
+  /// Name of the file (w/out its directory), if there is one.
+  pub fn get_file_name(&self) -> Option<&str> {
+    let file_path = self.file_path.as_deref()?;
+    std::path::Path::new(file_path)
+      .file_name()
+      .and_then(|it| it.to_str())
+      .or(Some(file_path))
+  }
+
+  pub fn line_count(&self) -> usize { self.lines.len() }
+
+  pub fn is_large(&self) -> bool { self.line_count() > LARGE_BUFFER_LINE_COUNT }
+
+  /// Number of grapheme clusters in the line at `row` (0 if there is no such line).
+  pub fn line_len(&self, row: usize) -> usize {
+    self
+      .lines
+      .get(row)
+      .map(|line| line.grapheme_count())
+      .unwrap_or(0)
+  }
+
+  /// Display column of the caret in its line.
+  pub fn caret_display_col(&self) -> usize {
+    self
+      .lines
+      .get(self.caret.row)
+      .map(|line| line.display_col_of_grapheme(self.caret.col))
+      .unwrap_or(0)
+  }
+
+  pub fn mark_changed(&mut self) {
+    self.is_dirty = true;
+    self.change_count += 1;
+  }
+
+  /// The buffer is dirty unless its lines are the same as the ones in its file. The lines of the
+  /// file share their chunks w/ the ones that weren't edited since (see [LineRope]), so this only
+  /// compares the lines of the chunks that were.
+  pub fn update_is_dirty(&mut self) {
+    self.is_dirty = self.saved_lines.as_ref() != Some(&self.lines);
+  }
+
+  pub fn insert_char(&mut self, ch: char) { self.insert_str(ch.encode_utf8(&mut [0; 4])); }
+
+  /// Insert `text` (which must not contain new lines) at the caret. The caret is moved past the
+  /// inserted text. If `text` combines w/ the grapheme cluster before the caret (eg: a combining
+  /// accent is typed after a letter) then the caret ends up after the combined cluster.
+  pub fn insert_str(&mut self, text: &str) {
+    let CaretPos { row, col } = self.caret;
+    let line = &mut self.lines[row];
+    let byte_index = line.byte_index_of_grapheme(col);
+    line.insert_str(byte_index, text);
+    self.caret.col = line[..byte_index + text.len()].grapheme_count();
+    self.mark_changed();
+  }
+
+  /// Insert `text` (which may contain new lines, eg: when it is pasted) at the caret. The caret is
+  /// moved past the inserted text.
+  pub fn insert_text(&mut self, text: &str) {
+    let text = text.replace("\r\n", "\n").replace('\r', "\n");
+    for (index, line) in text.split('\n').enumerate() {
+      if index > 0 {
+        self.insert_new_line();
+      }
+      if !line.is_empty() {
+        self.insert_str(line);
+      }
+    }
+  }
+
+  /// Replace `byte_range` of the line at `row` w/ `text` (which must not contain new lines), and
+  /// move the caret to the end of the replacement.
+  pub fn replace_in_line(&mut self, row: usize, byte_range: Range<usize>, text: &str) {
+    let line = &mut self.lines[row];
+    let start = byte_range.start;
+    line.replace_range(byte_range, text);
+    self.caret = CaretPos {
+      row,
+      col: line[..start + text.len()].grapheme_count(),
+    };
+    self.mark_changed();
+  }
+
+  /// Split the current line at the caret & move the caret to the start of the new line.
+  pub fn insert_new_line(&mut self) {
+    let CaretPos { row, col } = self.caret;
+    let line = &mut self.lines[row];
+    let byte_index = line.byte_index_of_grapheme(col);
+    let rest = line.split_off(byte_index);
+    self.lines.insert(row + 1, rest);
+    self.caret = CaretPos {
+      row: row + 1,
+      col: 0,
+    };
+    self.mark_changed();
+  }
+
+  /// Delete the grapheme cluster under the caret. At the end of a line, join the next line to it.
+  pub fn delete(&mut self) {
+    let CaretPos { row, col } = self.caret;
+    if col < self.line_len(row) {
+      let line = &mut self.lines[row];
+      let start = line.byte_index_of_grapheme(col);
+      let end = line.byte_index_of_grapheme(col + 1);
+      line.replace_range(start..end, "");
+      self.mark_changed();
+    } else if row + 1 < self.lines.len() {
+      let next_line = self.lines.remove(row + 1);
+      self.lines[row].push_str(&next_line);
+      self.mark_changed();
+    }
+  }
+
+  /// Delete the grapheme cluster before the caret. At the start of a line, join it to the previous
+  /// line.
+  pub fn backspace(&mut self) {
+    let CaretPos { row, col } = self.caret;
+    if col > 0 {
+      self.caret.col -= 1;
+      self.delete();
+    } else if row > 0 {
+      let prev_line_len = self.line_len(row - 1);
+      let line = self.lines.remove(row);
+      self.lines[row - 1].push_str(&line);
+      self.caret = CaretPos {
+        row: row - 1,
+        col: prev_line_len,
+      };
+      self.mark_changed();
+    }
+  }
+
+  pub fn move_caret(&mut self, direction: CaretDirection) {
+    self.caret = self.get_moved_caret_pos(self.caret, direction);
+  }
+
+  /// Position that a caret at `caret` ends up at when it is moved in `direction`. This doesn't
+  /// move any caret, so it can be used to find the end of a motion (eg: for vi operators).
+  pub fn get_moved_caret_pos(&self, caret: CaretPos, direction: CaretDirection) -> CaretPos {
+    let CaretPos { row, col } = caret;
+    let last_row = self.lines.len() - 1;
+    match direction {
+      CaretDirection::Left if col > 0 => CaretPos { row, col: col - 1 },
+      CaretDirection::Left if row > 0 => CaretPos {
+        row: row - 1,
+        col: self.line_len(row - 1),
+      },
+      CaretDirection::Right if col < self.line_len(row) => CaretPos { row, col: col + 1 },
+      CaretDirection::Right if row < last_row => CaretPos {
+        row: row + 1,
+        col: 0,
+      },
+      CaretDirection::Up => self.get_prev_unhidden_row(row).map_or(caret, |new_row| {
+        self.get_pos_in_row_keep_display_col(caret, new_row)
+      }),
+      CaretDirection::Down => self.get_next_unhidden_row(row).map_or(caret, |new_row| {
+        self.get_pos_in_row_keep_display_col(caret, new_row)
+      }),
+      CaretDirection::PageUp(page_height) => {
+        let new_row = self.get_unhidden_row(row.saturating_sub(page_height.max(1)));
+        self.get_pos_in_row_keep_display_col(caret, new_row)
+      }
+      CaretDirection::PageDown(page_height) => {
+        let new_row = self.get_unhidden_row((row + page_height.max(1)).min(last_row));
+        self.get_pos_in_row_keep_display_col(caret, new_row)
+      }
+      CaretDirection::LineStart => CaretPos { row, col: 0 },
+      CaretDirection::LineEnd => CaretPos {
+        row,
+        col: self.line_len(row),
+      },
+      CaretDirection::BufferStart => CaretPos::default(),
+      CaretDirection::BufferEnd => CaretPos {
+        row: last_row,
+        col: self.line_len(last_row),
+      },
+      CaretDirection::VisualLineUp(wrap_width) => {
+        self.get_pos_by_visual_line(caret, wrap_width, false)
+      }
+      CaretDirection::VisualLineDown(wrap_width) => {
+        self.get_pos_by_visual_line(caret, wrap_width, true)
+      }
+      CaretDirection::WordForward => self.get_next_word_start(caret),
+      CaretDirection::WordBackward => self.get_prev_word_start(caret),
+      CaretDirection::WordEnd => self.get_word_end(caret),
+      CaretDirection::WordRight => self.get_word_right(caret),
+      CaretDirection::WordLeft => self.get_word_left(caret),
+      _ => caret,
+    }
+  }
+
+  /// Lines may contain wide grapheme clusters, so the caret is kept at the same display column
+  /// (rather than the same grapheme cluster index) when it moves between lines.
+  fn get_pos_in_row_keep_display_col(&self, caret: CaretPos, new_row: usize) -> CaretPos {
+    let display_col = self.lines[caret.row].display_col_of_grapheme(caret.col);
+    CaretPos {
+      row: new_row,
+      col: self.lines[new_row].grapheme_index_at_display_col(display_col),
+    }
+  }
+
+  /// Move the caret to the previous or next visual line (which may be part of the same line) when
+  /// the lines are soft wrapped at `wrap_width`. The caret is kept at the same display column
+  /// relative to the start of the visual line.
+  fn get_pos_by_visual_line(&self, caret: CaretPos, wrap_width: usize, is_down: bool) -> CaretPos {
+    let row = caret.row;
+    let ranges = self.lines[row].wrap_to_display_cols(wrap_width);
+    let display_col = self.lines[row].display_col_of_grapheme(caret.col);
+    let index = wrapped_line_index_of_display_col(&ranges, display_col);
+    let offset = display_col - ranges[index].start;
+
+    let (new_row, new_ranges, new_index) = match is_down {
+      true if index + 1 < ranges.len() => (row, ranges, index + 1),
+      true => match self.get_next_unhidden_row(row) {
+        Some(next_row) => (
+          next_row,
+          self.lines[next_row].wrap_to_display_cols(wrap_width),
+          0,
+        ),
+        None => return caret,
+      },
+      false if index > 0 => (row, ranges, index - 1),
+      false => match self.get_prev_unhidden_row(row) {
+        Some(prev_row) => {
+          let new_ranges = self.lines[prev_row].wrap_to_display_cols(wrap_width);
+          let last_index = new_ranges.len() - 1;
+          (prev_row, new_ranges, last_index)
+        }
+        None => return caret,
+      },
+    };
+
+    // The end of a visual line that isn't the last one is the start of the next one, so stop on
+    // its last column instead.
+    let range = &new_ranges[new_index];
+    let max_col = match new_index + 1 == new_ranges.len() {
+      true => range.end,
+      false => range.end.saturating_sub(1).max(range.start),
+    };
+    CaretPos {
+      row: new_row,
+      col: self.lines[new_row].grapheme_index_at_display_col((range.start + offset).min(max_col)),
+    }
+  }
+}
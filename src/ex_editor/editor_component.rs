@@ -0,0 +1,1055 @@
+/*
+ *   Copyright (c) 2022 R3BL LLC
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use std::{ops::Range,
+          sync::Arc,
+          time::{Duration, Instant}};
+
+use async_trait::async_trait;
+use crossterm::{event::*, style::Color};
+use r3bl_rs_utils::*;
+
+use super::*;
+use crate::ColorSupport;
+
+/// Key presses that are handled closer together than this are the keys of a paste in the terminal
+/// (which types the pasted text), not typing.
+pub const PASTE_BURST_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Renders the [EditorBuffer] for `id` in [EditorState] inside of a [TWBox] & paints the caret when
+/// the box has focus. Each instance edits its own buffer, so the `id` must match the id of the box
+/// that it is rendered into.
+///
+/// The buffer can be taller & wider than the box. Only the part of it that is inside the viewport
+/// is painted, and the viewport is scrolled (using `scroll_offset`) so that the caret is always
+/// visible.
+///
+/// If there is a syntax for the buffer's file, then its lines are painted as [StyledSpan]s.
+///
+/// All the carets of the buffer (see [Caret]) & their selections are painted, so that multiple
+/// carets can be used to edit in several places at once.
+///
+/// W/ `is_soft_wrap`, lines that are wider than the box are wrapped (see
+/// [EditorLine::wrap_to_display_cols]) instead of scrolling the viewport horizontally. The buffer
+/// itself is not changed, the caret moves by visual line & is mapped back to its row & col.
+///
+/// W/ [KeyBindings::Vi] or [KeyBindings::Emacs], input events go through `vi_state` or
+/// `emacs_state` first (see [ViState] & [EmacsState]), & only the ones that they don't handle (eg:
+/// typing in vi's insert mode) are handled as described below.
+#[derive(Debug, Clone, Default)]
+pub struct EditorComponent {
+  pub id: String,
+  pub scroll_offset: ScrollOffset,
+  /// Height of the viewport from the last render, used for PageUp & PageDown.
+  pub page_height: usize,
+  /// Width of the viewport from the last render, used to move the caret by visual line.
+  pub page_width: usize,
+  pub is_soft_wrap: bool,
+  /// Origin of the box & the lines in the viewport from the last render, used to map mouse clicks
+  /// to caret positions.
+  pub box_origin_pos: Position,
+  pub visual_lines: Vec<VisualLine>,
+  pub syntax_highlighter: Option<Arc<SyntaxHighlighter>>,
+  pub highlight_cache: HighlightCache,
+  pub key_bindings: KeyBindings,
+  /// To quit the editor w/ vi's `:q` (or emacs' `C-x C-c`).
+  pub quit_signal: QuitSignal,
+  pub vi_state: ViState,
+  pub emacs_state: EmacsState,
+  /// Rows of the hex view from the last render, for a file that isn't UTF-8 text.
+  pub hex_viewport: HexViewport,
+  /// For the colors of the syntax highlighting & of the highlights that aren't in the stylesheet.
+  pub color_support: ColorSupport,
+  /// When the last key press was handled, to tell a paste from typing (see
+  /// [PASTE_BURST_INTERVAL]).
+  pub maybe_last_key_press: Option<Instant>,
+}
+
+impl EditorComponent {
+  pub fn new(
+    id: &str, syntax_highlighter: &Arc<SyntaxHighlighter>, key_bindings: KeyBindings,
+    quit_signal: &QuitSignal,
+  ) -> Self {
+    Self {
+      id: id.into(),
+      syntax_highlighter: Some(syntax_highlighter.clone()),
+      key_bindings,
+      quit_signal: quit_signal.clone(),
+      color_support: ColorSupport::detect(),
+      ..Default::default()
+    }
+  }
+
+  /// Whether `input_event` is a key press that is handled right after the last one, like the keys
+  /// that the terminal types for a paste (w/out bracketed paste, which crossterm 0.24 drops).
+  fn is_paste_burst(&mut self, input_event: &TWInputEvent) -> bool {
+    if !matches!(
+      input_event,
+      TWInputEvent::DisplayableKeypress(_) | TWInputEvent::NonDisplayableKeypress(_)
+    ) {
+      return false;
+    }
+    let now = Instant::now();
+    match self.maybe_last_key_press.replace(now) {
+      Some(last_key_press) => now.duration_since(last_key_press) < PASTE_BURST_INTERVAL,
+      None => false,
+    }
+  }
+
+  /// Position of the (primary) caret of `buffer` relative to the origin of the box, as of the last
+  /// render, `None` if it is scrolled out of the viewport.
+  pub fn get_caret_viewport_pos(&self, buffer: &EditorBuffer) -> Option<Position> {
+    let caret_line = buffer.lines.get(buffer.caret.row)?;
+    get_caret_viewport_pos(
+      &self.visual_lines,
+      self.page_width,
+      caret_line,
+      buffer.caret,
+    )
+  }
+
+  /// Mode that `buffer` is edited in, for the status line.
+  pub fn get_mode_name(&self, buffer: &EditorBuffer) -> String {
+    let mut mode = match &self.syntax_highlighter {
+      Some(syntax_highlighter) => syntax_highlighter
+        .get_mode_name(buffer.file_path.as_deref())
+        .to_string(),
+      None => "Plain Text".to_string(),
+    };
+    if self.is_soft_wrap {
+      mode.push_str(" (wrap)");
+    }
+    match self.key_bindings {
+      KeyBindings::Vi => mode = format!("{} │ {}", mode, self.vi_state.get_status()),
+      KeyBindings::Emacs => {
+        if let Some(prefix) = self.emacs_state.get_status() {
+          mode = format!("{} │ {}", mode, prefix);
+        }
+      }
+      KeyBindings::Default => {}
+    }
+    mode
+  }
+}
+
+#[async_trait]
+impl Component<EditorState, EditorAction> for EditorComponent {
+  /// Handle following input events (and consume them), all actions are fired w/ `self.id`:
+  /// - Displayable char      : fire `InsertChar(id, char)`
+  /// - Enter                 : fire `InsertNewLine(id)`
+  /// - Backspace, Delete     : fire `Backspace(id)`, `Delete(id)`
+  /// - Up, Down, Left, Right : fire `MoveCaret(id, direction)` (`VisualLineUp` & `VisualLineDown`
+  ///                           for Up & Down w/ soft wrap)
+  /// - Shift + any of the keys that move the caret, or mouse drag : fire `ExtendSelection(id,
+  ///                           direction)` or `ExtendSelectionTo(id, pos)`
+  /// - Ctrl+C, Ctrl+X        : copy the selected text to the terminal's clipboard (see
+  ///                           [copy_to_terminal_clipboard]) & fire `Copy(id)`, `Cut(id)`
+  /// - Ctrl+V                : fire `Paste(id, text)` w/ the text in [EditorState::clipboard]
+  /// - Paste in the terminal : fire `InsertText(id, text)` for each displayable char & Enter after
+  ///                           the first one (so that the pasted text isn't auto-indented or
+  ///                           auto-paired, see [PASTE_BURST_INTERVAL])
+  /// - PageUp, PageDown      : fire `MoveCaret(id, PageUp(page_height) | PageDown(page_height))`
+  /// - Home, End             : fire `MoveCaret(id, LineStart | LineEnd)`
+  /// - Ctrl+Home, Ctrl+End   : fire `MoveCaret(id, BufferStart | BufferEnd)`
+  /// - Ctrl+Z, Ctrl+Y        : fire `Undo(id)`, `Redo(id)`
+  /// - Click, Alt+Click      : fire `SetCaret(id, pos)`, `ToggleCaret(id, pos)`
+  /// - Alt+Shift+Up, Down    : fire `AddCaretAbove(id)`, `AddCaretBelow(id)`
+  /// - Ctrl+D                : fire `SelectNextOccurrence(id)`
+  /// - Esc                   : fire `ClearExtraCarets(id)`
+  /// - Ctrl+S                : save the buffer to its file (see [spawn_save_buffer])
+  /// - Alt+W                 : toggle soft wrap
+  /// - Alt+Z                 : fire `ToggleFold(id)`
+  /// - Alt+M, Alt+U          : fire `FoldAll(id)`, `UnfoldAll(id)`
+  ///
+  /// A file that isn't UTF-8 text is edited in the hex view instead (see [action_for_hex_input]),
+  /// w/o the vi & emacs key bindings.
+  async fn handle_event(
+    &mut self, input_event: &TWInputEvent, state: &EditorState,
+    shared_store: &SharedStore<EditorState, EditorAction>,
+  ) -> CommonResult<EventPropagation> {
+    throws_with_return!({
+      if let Some(hex_buffer) = state.get_hex_buffer(&self.id) {
+        return Ok(self.handle_hex_event(input_event, hex_buffer, shared_store));
+      }
+
+      let is_paste_burst = self.is_paste_burst(input_event);
+
+      // Vi & emacs key bindings are layers in front of the default ones.
+      let default_buffer = EditorBuffer::default();
+      let buffer = state.get_buffer(&self.id).unwrap_or(&default_buffer);
+      let outcome = match self.key_bindings {
+        KeyBindings::Default => KeyBindingOutcome::NotHandled,
+        KeyBindings::Vi => {
+          let maybe_clipboard = state.clipboard.as_deref();
+          self
+            .vi_state
+            .handle_event(input_event, &self.id, buffer, maybe_clipboard)
+        }
+        KeyBindings::Emacs => {
+          self
+            .emacs_state
+            .handle_event(input_event, &self.id, buffer, self.page_height)
+        }
+      };
+      match outcome {
+        KeyBindingOutcome::NotHandled => {}
+        KeyBindingOutcome::Dispatch(actions) => {
+          self.dispatch_key_binding_actions(actions, shared_store);
+          return Ok(EventPropagation::ConsumedRerender);
+        }
+        KeyBindingOutcome::Run(command) => {
+          self.run_editor_command(command, state, shared_store);
+          return Ok(EventPropagation::ConsumedRerender);
+        }
+      }
+
+      if let TWInputEvent::NonDisplayableKeypress(KeyEvent {
+        code: KeyCode::Char('s'),
+        modifiers: KeyModifiers::CONTROL,
+      }) = input_event
+      {
+        if let Some(buffer) = state.get_buffer(&self.id) {
+          spawn_save_buffer(shared_store, &self.id, buffer);
+        }
+        return Ok(EventPropagation::Consumed);
+      }
+
+      if let TWInputEvent::NonDisplayableKeypress(KeyEvent {
+        code: KeyCode::Char('w'),
+        modifiers: KeyModifiers::ALT,
+      }) = input_event
+      {
+        self.is_soft_wrap = !self.is_soft_wrap;
+        return Ok(EventPropagation::ConsumedRerender);
+      }
+
+      let maybe_action = match input_event {
+        // The keys of a paste are inserted as is.
+        TWInputEvent::DisplayableKeypress(typed_char) if is_paste_burst => Some(
+          EditorAction::InsertText(self.id.clone(), typed_char.to_string()),
+        ),
+        TWInputEvent::NonDisplayableKeypress(KeyEvent {
+          code: KeyCode::Enter,
+          modifiers: KeyModifiers::NONE,
+        }) if is_paste_burst => Some(EditorAction::InsertText(self.id.clone(), "\n".into())),
+        TWInputEvent::DisplayableKeypress(typed_char) => {
+          Some(EditorAction::InsertChar(self.id.clone(), *typed_char))
+        }
+        TWInputEvent::NonDisplayableKeypress(key_event) => self
+          .action_for_clipboard_key_event(key_event, state)
+          .or_else(|| self.action_for_key_event(key_event)),
+        TWInputEvent::Mouse(mouse_event) => self.action_for_mouse_event(mouse_event, state),
+        _ => None,
+      };
+
+      if let Some(action) = maybe_action {
+        debug_log_editor_action(
+          stringify!(EditorComponent::handle_event).into(),
+          action.clone(),
+        );
+        spawn_dispatch_action!(shared_store, action);
+        return Ok(EventPropagation::Consumed);
+      }
+
+      EventPropagation::Propagate
+    });
+  }
+
+  async fn render(
+    &mut self, has_focus: &HasFocus, current_box: &TWBox, state: &EditorState,
+    _shared_store: &SharedStore<EditorState, EditorAction>,
+  ) -> CommonResult<TWCommandQueue> {
+    throws_with_return!({
+      if let Some(hex_buffer) = state.get_hex_buffer(&self.id) {
+        // There are no text lines to click on.
+        self.visual_lines.clear();
+        let has_focus = has_focus.does_current_box_have_focus(current_box);
+        return Ok(render_hex_view(
+          &mut self.hex_viewport,
+          current_box,
+          hex_buffer,
+          has_focus,
+        ));
+      }
+
+      let default_buffer = EditorBuffer::default();
+      let buffer = state.get_buffer(&self.id).unwrap_or(&default_buffer);
+
+      // Setup intermediate vars.
+      let box_origin_pos = current_box.origin_pos; // Adjusted for style margin (if any).
+      let box_bounding_size = current_box.bounding_size; // Adjusted for style margin (if any).
+      let viewport_rows = box_bounding_size.rows as usize;
+      let viewport_cols = box_bounding_size.cols as usize;
+      let mut queue: TWCommandQueue = tw_command_queue!();
+
+      let caret = buffer.caret;
+
+      // Scroll the viewport so that the caret is visible.
+      self.page_height = viewport_rows;
+      self.page_width = viewport_cols;
+      let caret_line = buffer
+        .lines
+        .get(caret.row)
+        .map(String::as_str)
+        .unwrap_or_default();
+      let caret_display_col = caret_line.display_col_of_grapheme(caret.col);
+      let caret_width = caret_line
+        .grapheme_at(caret.col)
+        .map(|it| it.display_width())
+        .unwrap_or(1);
+      if !buffer.folds.is_empty() && caret.row < buffer.lines.len() {
+        let maybe_wrap_width = self.is_soft_wrap.then_some(viewport_cols);
+        self
+          .scroll_offset
+          .scroll_to_caret_folded(buffer, caret, viewport_rows, maybe_wrap_width);
+        match self.is_soft_wrap {
+          true => self.scroll_offset.col = 0,
+          false => {
+            self
+              .scroll_offset
+              .scroll_to_caret_col(caret_display_col, caret_width, viewport_cols)
+          }
+        }
+      } else if self.is_soft_wrap && caret.row < buffer.lines.len() {
+        self
+          .scroll_offset
+          .scroll_to_caret_wrapped(buffer, caret, viewport_rows, viewport_cols);
+      } else {
+        self.scroll_offset.scroll_to_caret(
+          caret.row,
+          caret_display_col,
+          caret_width,
+          viewport_rows,
+          viewport_cols,
+        );
+      }
+      let visual_lines = get_visual_lines(
+        buffer,
+        self.scroll_offset,
+        viewport_rows,
+        viewport_cols,
+        self.is_soft_wrap,
+      );
+      self.box_origin_pos = box_origin_pos;
+      self.visual_lines = visual_lines.clone();
+
+      // Paint each line of the buffer that is inside the viewport.
+      self.render_lines(&mut queue, current_box, buffer, &visual_lines);
+
+      // Paint the matches of the search query (if the search bar is open for this buffer).
+      if let Some(search) = state.search.as_ref().filter(|it| it.id == self.id) {
+        render_search_matches(
+          &mut queue,
+          current_box,
+          buffer,
+          &visual_lines,
+          search,
+          self.color_support,
+        );
+      }
+
+      // Paint the selections.
+      for (start, end) in buffer.get_carets().iter().filter_map(Caret::get_selection) {
+        render_selection(
+          &mut queue,
+          current_box,
+          buffer,
+          &visual_lines,
+          start,
+          end,
+          self.color_support,
+        );
+      }
+
+      // Paint the bracket next to the caret & the one that matches it.
+      if let Some((bracket_pos, match_pos)) = buffer.get_matching_bracket(caret) {
+        for pos in [bracket_pos, match_pos] {
+          render_bracket_match(
+            &mut queue,
+            current_box,
+            buffer,
+            &visual_lines,
+            pos,
+            self.color_support,
+          );
+        }
+      }
+
+      // Paint the carets of the peers that share the buffer, each one in its own color.
+      if let Some(collab) = state.collab.as_ref().filter(|it| it.id == self.id) {
+        for (peer_id, offset) in &collab.peer_carets {
+          let pos = buffer.get_pos_at_char_offset(*offset);
+          render_peer_caret(
+            &mut queue,
+            current_box,
+            buffer,
+            &visual_lines,
+            pos,
+            *peer_id,
+            self.color_support,
+          );
+        }
+      }
+
+      // Paint the carets.
+      if has_focus.does_current_box_have_focus(current_box) {
+        render_caret(&mut queue, current_box, &visual_lines, caret_line, caret);
+        for extra_caret in &buffer.extra_carets {
+          let line = buffer.lines[extra_caret.pos.row].as_str();
+          render_caret(
+            &mut queue,
+            current_box,
+            &visual_lines,
+            line,
+            extra_caret.pos,
+          );
+        }
+      }
+
+      call_if_true!(DEBUG, {
+        log_no_err! {
+          INFO,
+          "\
+📜 EditorComponent::render ->
+  - current_box: {:?},
+  - box_origin_pos: {:?},
+  - box_bounding_size: {:?},
+  - caret: {:?},
+  - scroll_offset: {:?},
+  - queue: {:?}",
+          current_box,
+          box_origin_pos,
+          box_bounding_size,
+          caret,
+          self.scroll_offset,
+          queue
+        };
+      });
+
+      // Return the command queue.
+      queue
+    });
+  }
+}
+
+impl EditorComponent {
+  /// Paint the `visual_lines` of the viewport, w/ syntax highlighting if there is a syntax for the
+  /// buffer's file, or as plain text otherwise.
+  fn render_lines(
+    &mut self, queue: &mut TWCommandQueue, current_box: &TWBox, buffer: &EditorBuffer,
+    visual_lines: &[VisualLine],
+  ) {
+    let viewport_cols = current_box.bounding_size.cols as usize;
+    let last_visible_row = match visual_lines.last() {
+      Some(visual_line) => visual_line.row,
+      None => return,
+    };
+
+    let maybe_highlighted_lines = match &self.syntax_highlighter {
+      Some(syntax_highlighter) => {
+        syntax_highlighter.highlight(buffer, last_visible_row, &mut self.highlight_cache)
+      }
+      None => None,
+    };
+
+    for (viewport_row, visual_line) in visual_lines.iter().enumerate() {
+      let line = buffer.lines[visual_line.row].as_str();
+      let width = (visual_line.end_col - visual_line.start_col).min(viewport_cols);
+      tw_command_queue! {
+        queue push
+        TWCommand::MoveCursorPositionRelTo(
+          current_box.origin_pos,
+          Position { col: 0, row: viewport_row as UnitType }
+        ),
+        TWCommand::ApplyColors(current_box.get_computed_style())
+      };
+      if visual_line.is_fold_placeholder() {
+        render_fold_placeholder(queue, current_box, line, visual_line.folded_line_count);
+        continue;
+      }
+      match maybe_highlighted_lines.and_then(|lines| lines.get(visual_line.row)) {
+        Some(spans) => {
+          render_styled_spans(
+            queue,
+            current_box,
+            spans,
+            visual_line.start_col,
+            width,
+            self.color_support,
+          );
+        }
+        None => {
+          tw_command_queue! {
+            queue push
+            TWCommand::PrintWithAttributes(
+              line.clip_to_display_cols(visual_line.start_col, width),
+              current_box.get_computed_style(),
+            )
+          };
+        }
+      }
+      tw_command_queue!(queue push TWCommand::ResetColor);
+    }
+  }
+
+  fn handle_hex_event(
+    &self, input_event: &TWInputEvent, hex_buffer: &HexBuffer,
+    shared_store: &SharedStore<EditorState, EditorAction>,
+  ) -> EventPropagation {
+    if let TWInputEvent::NonDisplayableKeypress(KeyEvent {
+      code: KeyCode::Char('s'),
+      modifiers: KeyModifiers::CONTROL,
+    }) = input_event
+    {
+      spawn_save_hex_buffer(shared_store, &self.id, hex_buffer);
+      return EventPropagation::Consumed;
+    }
+
+    match action_for_hex_input(input_event, &self.id, hex_buffer, &self.hex_viewport) {
+      Some(action) => {
+        debug_log_editor_action(
+          stringify!(EditorComponent::handle_hex_event).into(),
+          action.clone(),
+        );
+        spawn_dispatch_action!(shared_store, action);
+        EventPropagation::Consumed
+      }
+      None => EventPropagation::Propagate,
+    }
+  }
+
+  fn action_for_key_event(&self, key_event: &KeyEvent) -> Option<EditorAction> {
+    let id = self.id.clone();
+    let action = match (key_event.code, key_event.modifiers) {
+      (KeyCode::Enter, KeyModifiers::NONE) => EditorAction::InsertNewLine(id),
+      (KeyCode::Backspace, KeyModifiers::NONE) => EditorAction::Backspace(id),
+      (KeyCode::Delete, KeyModifiers::NONE) => EditorAction::Delete(id),
+      (KeyCode::Char('z'), KeyModifiers::CONTROL) => EditorAction::Undo(id),
+      (KeyCode::Char('y'), KeyModifiers::CONTROL) => EditorAction::Redo(id),
+      (KeyCode::Char('d'), KeyModifiers::CONTROL) => EditorAction::SelectNextOccurrence(id),
+      (KeyCode::Char('z'), KeyModifiers::ALT) => EditorAction::ToggleFold(id),
+      (KeyCode::Char('m'), KeyModifiers::ALT) => EditorAction::FoldAll(id),
+      (KeyCode::Char('u'), KeyModifiers::ALT) => EditorAction::UnfoldAll(id),
+      (KeyCode::Esc, KeyModifiers::NONE) => EditorAction::ClearExtraCarets(id),
+      (KeyCode::Up, modifiers) if modifiers == KeyModifiers::ALT | KeyModifiers::SHIFT => {
+        EditorAction::AddCaretAbove(id)
+      }
+      (KeyCode::Down, modifiers) if modifiers == KeyModifiers::ALT | KeyModifiers::SHIFT => {
+        EditorAction::AddCaretBelow(id)
+      }
+      (code, KeyModifiers::SHIFT) => {
+        EditorAction::ExtendSelection(id, self.caret_direction_for_key(code, KeyModifiers::NONE)?)
+      }
+      (code, modifiers) if modifiers == KeyModifiers::CONTROL | KeyModifiers::SHIFT => {
+        EditorAction::ExtendSelection(
+          id,
+          self.caret_direction_for_key(code, KeyModifiers::CONTROL)?,
+        )
+      }
+      (code, modifiers) => {
+        EditorAction::MoveCaret(id, self.caret_direction_for_key(code, modifiers)?)
+      }
+    };
+    Some(action)
+  }
+
+  /// Copying also sends the selected text to the terminal's clipboard, since that is a side effect
+  /// it is done here (& not in the reducer).
+  fn action_for_clipboard_key_event(
+    &self, key_event: &KeyEvent, state: &EditorState,
+  ) -> Option<EditorAction> {
+    let id = self.id.clone();
+    match (key_event.code, key_event.modifiers) {
+      (KeyCode::Char('c' | 'x'), KeyModifiers::CONTROL) => {
+        let text = state.get_buffer(&id)?.get_selected_text()?;
+        send_to_terminal_clipboard(&text);
+        match key_event.code {
+          KeyCode::Char('c') => Some(EditorAction::Copy(id)),
+          _ => Some(EditorAction::Cut(id)),
+        }
+      }
+      (KeyCode::Char('v'), KeyModifiers::CONTROL) => {
+        Some(EditorAction::Paste(id, state.clipboard.clone()?))
+      }
+      _ => None,
+    }
+  }
+
+  /// The actions for a key binding (eg: vi's `d3w`) are dispatched as a single
+  /// [EditorAction::Batch], so that they are applied (and rendered) at once. Text that they put in
+  /// the clipboard also goes to the terminal's clipboard.
+  fn dispatch_key_binding_actions(
+    &self, actions: Vec<EditorAction>, shared_store: &SharedStore<EditorState, EditorAction>,
+  ) {
+    for action in &actions {
+      if let EditorAction::SetClipboard(text) = action {
+        send_to_terminal_clipboard(text);
+      }
+    }
+    if actions.is_empty() {
+      return;
+    }
+
+    let action = EditorAction::Batch(actions);
+    debug_log_editor_action(
+      stringify!(EditorComponent::dispatch_key_binding_actions).into(),
+      action.clone(),
+    );
+    let mut _event_consumed = false;
+    spawn_and_consume_event!(_event_consumed, shared_store, action);
+  }
+
+  /// Like vi & emacs, quitting is refused w/ unsaved changes (in any buffer), & `:wq` only quits
+  /// once the buffer has been saved.
+  fn run_editor_command(
+    &self, command: EditorCommand, state: &EditorState,
+    shared_store: &SharedStore<EditorState, EditorAction>,
+  ) {
+    let default_buffer = EditorBuffer::default();
+    let buffer = state.get_buffer(&self.id).unwrap_or(&default_buffer);
+    let shared_store = shared_store.clone();
+    let no_write_message = match self.key_bindings {
+      KeyBindings::Emacs => "Modified buffers exist, C-x C-c again to quit anyway",
+      _ => "No write since last change (add ! to override)",
+    };
+
+    match command {
+      // Shows that the buffer can't be saved yet, if it is still loading.
+      EditorCommand::Write | EditorCommand::WriteQuit if buffer.is_loading => {
+        spawn_save_buffer(&shared_store, &self.id, buffer)
+      }
+      EditorCommand::Write => spawn_save_buffer(&shared_store, &self.id, buffer),
+      EditorCommand::Quit if state.has_dirty_buffers() => {
+        let mut _event_consumed = false;
+        spawn_and_consume_event!(
+          _event_consumed,
+          shared_store,
+          EditorAction::ShowMessage(no_write_message.into())
+        );
+      }
+      EditorCommand::Quit | EditorCommand::ForceQuit => self.quit_signal.request(),
+      EditorCommand::WriteQuit => {
+        let id = self.id.clone();
+        let maybe_file_path = buffer.file_path.clone();
+        let content = buffer.get_as_file_content();
+        let change_count = buffer.change_count;
+        let quit_signal = self.quit_signal.clone();
+        tokio::spawn(async move {
+          let action = save_buffer(id, maybe_file_path, content.into_bytes(), change_count).await;
+          let is_saved = matches!(action, EditorAction::FileSaved(..));
+          let mut store = shared_store.write().await;
+          store.dispatch_action(action).await;
+          if !is_saved {
+            return;
+          }
+          // The buffer is still dirty if it was edited while it was being saved.
+          match store.get_state().has_dirty_buffers() {
+            true => {
+              store
+                .dispatch_action(EditorAction::ShowMessage(no_write_message.into()))
+                .await
+            }
+            false => quit_signal.request(),
+          }
+        });
+      }
+    }
+  }
+
+  fn action_for_mouse_event(
+    &self, mouse_event: &MouseEvent, state: &EditorState,
+  ) -> Option<EditorAction> {
+    let buffer = state.get_buffer(&self.id)?;
+    let pos = self.caret_pos_at(buffer, mouse_event.column, mouse_event.row)?;
+    let id = self.id.clone();
+    match (mouse_event.kind, mouse_event.modifiers) {
+      (MouseEventKind::Down(MouseButton::Left), KeyModifiers::NONE) => {
+        Some(EditorAction::SetCaret(id, pos))
+      }
+      (MouseEventKind::Down(MouseButton::Left), KeyModifiers::ALT) => {
+        Some(EditorAction::ToggleCaret(id, pos))
+      }
+      (MouseEventKind::Drag(MouseButton::Left), _) => {
+        Some(EditorAction::ExtendSelectionTo(id, pos))
+      }
+      _ => None,
+    }
+  }
+
+  /// Map a position in the terminal window (eg: of a mouse click) to the position in the buffer
+  /// that is painted there, using the viewport from the last render. `None` if it is outside of
+  /// the box.
+  fn caret_pos_at(&self, buffer: &EditorBuffer, col: UnitType, row: UnitType) -> Option<CaretPos> {
+    let viewport_row = row.checked_sub(self.box_origin_pos.row)? as usize;
+    let viewport_col = col.checked_sub(self.box_origin_pos.col)? as usize;
+    if viewport_col >= self.page_width {
+      return None;
+    }
+    let visual_line = self.visual_lines.get(viewport_row)?;
+    // Clicking on the placeholder of a fold opens it (by moving the caret into it).
+    if visual_line.is_fold_placeholder() {
+      return Some(CaretPos {
+        row: visual_line.row + 1,
+        col: 0,
+      });
+    }
+    let mut display_col = visual_line.start_col + viewport_col;
+    if !visual_line.is_end_of_line {
+      display_col = display_col.min(visual_line.end_col.saturating_sub(1));
+    }
+    let line = buffer.lines.get(visual_line.row)?;
+    Some(CaretPos {
+      row: visual_line.row,
+      col: line.grapheme_index_at_display_col(display_col),
+    })
+  }
+
+  fn caret_direction_for_key(
+    &self, code: KeyCode, modifiers: KeyModifiers,
+  ) -> Option<CaretDirection> {
+    let direction = match (code, modifiers) {
+      (KeyCode::Up, KeyModifiers::NONE) if self.is_soft_wrap => {
+        CaretDirection::VisualLineUp(self.page_width)
+      }
+      (KeyCode::Down, KeyModifiers::NONE) if self.is_soft_wrap => {
+        CaretDirection::VisualLineDown(self.page_width)
+      }
+      (KeyCode::Up, KeyModifiers::NONE) => CaretDirection::Up,
+      (KeyCode::Down, KeyModifiers::NONE) => CaretDirection::Down,
+      (KeyCode::Left, KeyModifiers::NONE) => CaretDirection::Left,
+      (KeyCode::Right, KeyModifiers::NONE) => CaretDirection::Right,
+      (KeyCode::PageUp, KeyModifiers::NONE) => CaretDirection::PageUp(self.page_height),
+      (KeyCode::PageDown, KeyModifiers::NONE) => CaretDirection::PageDown(self.page_height),
+      (KeyCode::Home, KeyModifiers::NONE) => CaretDirection::LineStart,
+      (KeyCode::End, KeyModifiers::NONE) => CaretDirection::LineEnd,
+      (KeyCode::Home, KeyModifiers::CONTROL) => CaretDirection::BufferStart,
+      (KeyCode::End, KeyModifiers::CONTROL) => CaretDirection::BufferEnd,
+      _ => return None,
+    };
+    Some(direction)
+  }
+}
+
+fn send_to_terminal_clipboard(text: &str) {
+  if let Err(error) = copy_to_terminal_clipboard(text) {
+    log_no_err!(
+      ERROR,
+      "Could not copy to the terminal's clipboard: {}",
+      error
+    );
+  }
+}
+
+/// Paint the matches of `search` that are inside the viewport w/ a highlight color. Only the lines
+/// in the viewport are searched.
+fn render_search_matches(
+  queue: &mut TWCommandQueue, current_box: &TWBox, buffer: &EditorBuffer,
+  visual_lines: &[VisualLine], search: &EditorSearch, color_support: ColorSupport,
+) {
+  let regex = match search.build_regex() {
+    Ok(Some(regex)) => regex,
+    _ => return,
+  };
+  let mut style = style! {
+    id: search_match
+    color_fg: Color::Rgb { r: 0, g: 0, b: 0 }
+    color_bg: Color::Rgb { r: 255, g: 216, b: 0 }
+  };
+  color_support.degrade_highlight_style(&mut style);
+
+  let mut visible_rows: Vec<usize> = visual_lines.iter().map(|it| it.row).collect();
+  visible_rows.dedup();
+  for row in visible_rows {
+    let line = buffer.lines[row].as_str();
+    for search_match in find_matches_in_line(&regex, row, line) {
+      let start_col = line.display_col_of_grapheme(search_match.start);
+      let end_col = line.display_col_of_grapheme(search_match.end);
+      render_range(
+        queue,
+        current_box,
+        line,
+        visual_lines,
+        row,
+        start_col..end_col,
+        &style,
+      );
+    }
+  }
+}
+
+/// Paint the text between `start` & `end` w/ the selection color. The new line at the end of each
+/// selected line (except for the last one) is painted as a selected space.
+fn render_selection(
+  queue: &mut TWCommandQueue, current_box: &TWBox, buffer: &EditorBuffer,
+  visual_lines: &[VisualLine], start: CaretPos, end: CaretPos, color_support: ColorSupport,
+) {
+  let mut style = style! {
+    id: selection
+    color_fg: Color::Rgb { r: 255, g: 255, b: 255 }
+    color_bg: Color::Rgb { r: 79, g: 91, b: 102 }
+  };
+  color_support.degrade_highlight_style(&mut style);
+
+  let first_visible_row = visual_lines.first().map(|it| it.row).unwrap_or_default();
+  let last_visible_row = visual_lines.last().map(|it| it.row).unwrap_or_default();
+  for row in start.row.max(first_visible_row)..=end.row.min(last_visible_row) {
+    let line = buffer.lines[row].as_str();
+    let start_col = match row == start.row {
+      true => line.display_col_of_grapheme(start.col),
+      false => 0,
+    };
+    let end_col = match row == end.row {
+      true => line.display_col_of_grapheme(end.col),
+      false => line.display_width() + 1,
+    };
+    render_range(
+      queue,
+      current_box,
+      line,
+      visual_lines,
+      row,
+      start_col..end_col,
+      &style,
+    );
+  }
+}
+
+/// Paint the bracket at `pos` w/ the bracket match color.
+fn render_bracket_match(
+  queue: &mut TWCommandQueue, current_box: &TWBox, buffer: &EditorBuffer,
+  visual_lines: &[VisualLine], pos: CaretPos, color_support: ColorSupport,
+) {
+  let mut style = style! {
+    id: bracket_match
+    color_fg: Color::Rgb { r: 255, g: 255, b: 255 }
+    color_bg: Color::Rgb { r: 120, g: 120, b: 170 }
+  };
+  color_support.degrade_highlight_style(&mut style);
+  let line = buffer.lines[pos.row].as_str();
+  let start_col = line.display_col_of_grapheme(pos.col);
+  let end_col = line.display_col_of_grapheme(pos.col + 1);
+  render_range(
+    queue,
+    current_box,
+    line,
+    visual_lines,
+    pos.row,
+    start_col..end_col,
+    &style,
+  );
+}
+
+/// Paint the grapheme cluster at `pos` (or a space at the end of a line) w/ the color of the peer
+/// w/ `peer_id` (see [PEER_CARET_COLORS]).
+fn render_peer_caret(
+  queue: &mut TWCommandQueue, current_box: &TWBox, buffer: &EditorBuffer,
+  visual_lines: &[VisualLine], pos: CaretPos, peer_id: u32, color_support: ColorSupport,
+) {
+  let (r, g, b) = PEER_CARET_COLORS[peer_id as usize % PEER_CARET_COLORS.len()];
+  let mut style = style! {
+    id: peer_caret
+    color_fg: Color::Rgb { r: 0, g: 0, b: 0 }
+  };
+  style.color_bg = Some(Color::Rgb { r, g, b }.into());
+  color_support.degrade_highlight_style(&mut style);
+  let line = buffer.lines[pos.row].as_str();
+  let start_col = line.display_col_of_grapheme(pos.col);
+  let width = line
+    .grapheme_at(pos.col)
+    .map(|it| it.display_width())
+    .unwrap_or(1);
+  render_range(
+    queue,
+    current_box,
+    line,
+    visual_lines,
+    pos.row,
+    start_col..start_col + width,
+    &style,
+  );
+}
+
+/// Paint the display columns `cols` of `line` (at `row` in the buffer) w/ `style`, in each of the
+/// `visual_lines` that they are visible in. Columns past the end of the line are painted as
+/// spaces.
+fn render_range(
+  queue: &mut TWCommandQueue, current_box: &TWBox, line: &str, visual_lines: &[VisualLine],
+  row: usize, cols: Range<usize>, style: &Style,
+) {
+  let viewport_cols = current_box.bounding_size.cols as usize;
+  for (viewport_row, visual_line) in visual_lines.iter().enumerate() {
+    if visual_line.row != row || visual_line.is_fold_placeholder() {
+      continue;
+    }
+    let visible_end_col = match visual_line.is_end_of_line {
+      true => visual_line.start_col + viewport_cols,
+      false => visual_line
+        .end_col
+        .min(visual_line.start_col + viewport_cols),
+    };
+    let start_col = cols.start.max(visual_line.start_col);
+    let end_col = cols.end.min(visible_end_col);
+    if start_col >= end_col {
+      continue;
+    }
+
+    let mut text = line.clip_to_display_cols(start_col, end_col - start_col);
+    let padding = (end_col - start_col).saturating_sub(text.display_width());
+    text.push_str(&" ".repeat(padding));
+    tw_command_queue! {
+      queue push
+      TWCommand::MoveCursorPositionRelTo(
+        current_box.origin_pos,
+        Position {
+          col: (start_col - visual_line.start_col) as UnitType,
+          row: viewport_row as UnitType,
+        }
+      ),
+      TWCommand::PrintWithAttributes(text, Some(style.clone())),
+      TWCommand::ResetColor
+    };
+  }
+}
+
+/// Paint the placeholder of a fold after `line` (the line that was folded), indented like it, at
+/// the current position of the terminal cursor.
+fn render_fold_placeholder(
+  queue: &mut TWCommandQueue, current_box: &TWBox, line: &str, folded_line_count: usize,
+) {
+  let viewport_cols = current_box.bounding_size.cols as usize;
+  let indent = &line[..line.len() - line.trim_start().len()];
+  let text = format!(
+    "{}⋯ {} folded line{}",
+    indent,
+    folded_line_count,
+    if folded_line_count == 1 { "" } else { "s" }
+  );
+  let mut style = style! { id: fold_placeholder attrib: [dim] };
+  style.color_bg = current_box
+    .get_computed_style()
+    .and_then(|style| style.color_bg);
+  tw_command_queue! {
+    queue push
+    TWCommand::PrintWithAttributes(text.clip_to_display_cols(0, viewport_cols), Some(style))
+  };
+}
+
+/// Paint the grapheme cluster under the caret (or a space at the end of a line) in reverse video.
+/// `caret` is the logical insertion point (grapheme cluster index), it is converted to a display
+/// column and looked up in the `visual_lines` of the viewport here, so that wide grapheme clusters
+/// before it, the scroll offset, and soft wrap are accounted for.
+fn render_caret(
+  queue: &mut TWCommandQueue, current_box: &TWBox, visual_lines: &[VisualLine], caret_line: &str,
+  caret: CaretPos,
+) {
+  let viewport_cols = current_box.bounding_size.cols as usize;
+  let caret_display_pos =
+    match get_caret_viewport_pos(visual_lines, viewport_cols, caret_line, caret) {
+      Some(it) => it,
+      None => return,
+    };
+  let grapheme_under_caret = caret_line
+    .grapheme_at(caret.col)
+    .map(String::from)
+    .unwrap_or_else(|| " ".into());
+
+  tw_command_queue! {
+    queue push
+    TWCommand::MoveCursorPositionRelTo(
+      current_box.origin_pos,
+      caret_display_pos
+    ),
+    TWCommand::PrintWithAttributes(
+      grapheme_under_caret,
+      Some(style! { id: caret attrib: [reverse] })
+    ),
+    TWCommand::ResetColor
+  };
+}
+
+/// Position of `caret` relative to the origin of the viewport (which shows the `visual_lines` & is
+/// `viewport_cols` wide), `None` if it is outside of the viewport.
+fn get_caret_viewport_pos(
+  visual_lines: &[VisualLine], viewport_cols: usize, caret_line: &str, caret: CaretPos,
+) -> Option<Position> {
+  let display_col = caret_line.display_col_of_grapheme(caret.col);
+  let (viewport_row, visual_line) = visual_lines
+    .iter()
+    .enumerate()
+    .find(|(_, visual_line)| visual_line.contains(caret.row, display_col))?;
+  let mut viewport_col = display_col - visual_line.start_col;
+  if viewport_col >= viewport_cols {
+    // W/ soft wrap, whitespace can hang past the edge of the box, paint the caret on the edge.
+    let is_hanging = visual_line.end_col - visual_line.start_col > viewport_cols;
+    match is_hanging && viewport_cols > 0 {
+      true => viewport_col = viewport_cols - 1,
+      false => return None,
+    }
+  }
+  Some(Position {
+    col: viewport_col as UnitType,
+    row: viewport_row as UnitType,
+  })
+}
+
+/// Paint the part of the `spans` of a line that is inside the viewport (from display column
+/// `scroll_col`, `viewport_cols` wide), starting at the current position of the terminal cursor.
+/// Each span is painted w/ its own foreground color on top of the box's background color.
+fn render_styled_spans(
+  queue: &mut TWCommandQueue, current_box: &TWBox, spans: &[StyledSpan], scroll_col: usize,
+  viewport_cols: usize, color_support: ColorSupport,
+) {
+  let box_color_bg = current_box
+    .get_computed_style()
+    .and_then(|style| style.color_bg);
+  let viewport_end_col = scroll_col + viewport_cols;
+
+  let mut span_start_col = 0;
+  for span in spans {
+    let span_end_col = span_start_col + span.text.display_width();
+    let visible_start_col = span_start_col.max(scroll_col);
+    let visible_end_col = span_end_col.min(viewport_end_col);
+
+    if visible_start_col < visible_end_col {
+      let (r, g, b) = span.style.fg_rgb;
+      let mut style = style! {
+        id: syntax
+        color_fg: Color::Rgb { r, g, b }
+      };
+      color_support.degrade_style(&mut style);
+      style.color_bg = box_color_bg.clone();
+      style.bold = span.style.bold;
+      style.underline = span.style.underline;
+
+      tw_command_queue! {
+        queue push
+        TWCommand::PrintWithAttributes(
+          span.text.clip_to_display_cols(
+            visible_start_col - span_start_col,
+            visible_end_col - visible_start_col
+          ),
+          Some(style)
+        )
+      };
+    }
+
+    if span_end_col >= viewport_end_col {
+      break;
+    }
+    span_start_col = span_end_col;
+  }
+}
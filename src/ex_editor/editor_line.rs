@@ -0,0 +1,281 @@
+/*
+ *   Copyright (c) 2022 R3BL LLC
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use std::ops::Range;
+
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+/// Grapheme cluster aware operations on a single line of text in an
+/// [EditorBuffer](crate::EditorBuffer).
+///
+/// Indices passed to & returned from these methods are grapheme cluster indices (not byte or
+/// `char` indices), and columns are display columns (a CJK character or most emoji take up 2).
+pub trait EditorLine {
+  /// Number of grapheme clusters in the line.
+  fn grapheme_count(&self) -> usize;
+
+  /// The grapheme cluster at `index`, if there is one.
+  fn grapheme_at(&self, index: usize) -> Option<&str>;
+
+  /// Byte index of the start of the grapheme cluster at `index`. Returns the length of the line
+  /// (in bytes) if `index` is past its end.
+  fn byte_index_of_grapheme(&self, index: usize) -> usize;
+
+  /// Number of display columns that the line takes up.
+  fn display_width(&self) -> usize;
+
+  /// Display column at which the grapheme cluster at `index` starts.
+  fn display_col_of_grapheme(&self, index: usize) -> usize;
+
+  /// Index of the grapheme cluster that is painted at `display_col`. If `display_col` falls in the
+  /// middle of a wide grapheme cluster, then that cluster is returned. If it is past the end of the
+  /// line then [EditorLine::grapheme_count] is returned.
+  fn grapheme_index_at_display_col(&self, display_col: usize) -> usize;
+
+  /// The part of the line that is visible in a viewport that starts at display column `start_col`
+  /// & is `width` display columns wide. Wide grapheme clusters that are cut in half by either edge
+  /// of the viewport are replaced w/ spaces, so the returned string is never wider than `width`
+  /// and the columns after it don't shift.
+  fn clip_to_display_cols(&self, start_col: usize, width: usize) -> String;
+
+  /// Split the line into visual lines (for soft wrap) that are at most `width` display columns
+  /// wide. Lines are broken after whitespace where possible, and between grapheme clusters
+  /// otherwise (for words that are wider than `width`). Whitespace at a break is allowed to hang
+  /// past `width`, so that visual lines don't start w/ it. Returns the range of display columns of
+  /// each visual line, an empty line has a single empty range.
+  fn wrap_to_display_cols(&self, width: usize) -> Vec<Range<usize>>;
+
+  /// Range of grapheme cluster indices of the word (letters, digits & `_`) that `index` is in, or
+  /// that ends right at `index`. `None` if there is no such word.
+  fn word_range_at(&self, index: usize) -> Option<Range<usize>>;
+}
+
+impl EditorLine for str {
+  fn grapheme_count(&self) -> usize { self.graphemes(true).count() }
+
+  fn grapheme_at(&self, index: usize) -> Option<&str> { self.graphemes(true).nth(index) }
+
+  fn byte_index_of_grapheme(&self, index: usize) -> usize {
+    self
+      .grapheme_indices(true)
+      .nth(index)
+      .map(|(byte_index, _)| byte_index)
+      .unwrap_or(self.len())
+  }
+
+  fn display_width(&self) -> usize { UnicodeWidthStr::width(self) }
+
+  fn display_col_of_grapheme(&self, index: usize) -> usize {
+    self
+      .graphemes(true)
+      .take(index)
+      .map(UnicodeWidthStr::width)
+      .sum()
+  }
+
+  fn grapheme_index_at_display_col(&self, display_col: usize) -> usize {
+    let mut col = 0;
+    for (index, grapheme) in self.graphemes(true).enumerate() {
+      let width = UnicodeWidthStr::width(grapheme);
+      if display_col < col + width.max(1) {
+        return index;
+      }
+      col += width;
+    }
+    self.grapheme_count()
+  }
+
+  fn clip_to_display_cols(&self, start_col: usize, width: usize) -> String {
+    let end_col = start_col + width;
+    let mut clipped = String::new();
+    let mut col = 0;
+    for grapheme in self.graphemes(true) {
+      if col >= end_col {
+        break;
+      }
+      let grapheme_width = UnicodeWidthStr::width(grapheme);
+      let grapheme_end_col = col + grapheme_width;
+      if col >= start_col && grapheme_end_col <= end_col {
+        clipped.push_str(grapheme);
+      } else if grapheme_end_col > start_col {
+        // Partially visible, pad the visible part of it.
+        let visible_cols = grapheme_end_col.min(end_col) - col.max(start_col);
+        clipped.push_str(&" ".repeat(visible_cols));
+      }
+      col = grapheme_end_col;
+    }
+    clipped
+  }
+
+  fn wrap_to_display_cols(&self, width: usize) -> Vec<Range<usize>> {
+    let width = width.max(1);
+    let mut ranges = vec![];
+    let mut start_col = 0;
+    let mut col = 0;
+    let mut maybe_break_col: Option<usize> = None;
+
+    for grapheme in self.graphemes(true) {
+      let grapheme_width = UnicodeWidthStr::width(grapheme);
+      let is_whitespace = grapheme.chars().all(char::is_whitespace);
+
+      if col > start_col && col + grapheme_width - start_col > width {
+        if is_whitespace {
+          // Hang the whitespace & break after it.
+          col += grapheme_width;
+          ranges.push(start_col..col);
+          start_col = col;
+          maybe_break_col = None;
+          continue;
+        }
+        // Break after the last whitespace, or right here if there isn't any.
+        let break_col = match maybe_break_col {
+          Some(break_col) if break_col > start_col => break_col,
+          _ => col,
+        };
+        ranges.push(start_col..break_col);
+        start_col = break_col;
+        maybe_break_col = None;
+        // The rest of the word may still not have room for this grapheme cluster.
+        if col > start_col && col + grapheme_width - start_col > width {
+          ranges.push(start_col..col);
+          start_col = col;
+        }
+      }
+
+      col += grapheme_width;
+      if is_whitespace {
+        maybe_break_col = Some(col);
+      }
+    }
+
+    ranges.push(start_col..col);
+    ranges
+  }
+
+  fn word_range_at(&self, index: usize) -> Option<Range<usize>> {
+    let mut start = 0;
+    let mut maybe_found = None;
+    for segment in self.split_word_bounds() {
+      let end = start + segment.grapheme_count();
+      let is_word = segment.chars().any(|it| it.is_alphanumeric() || it == '_');
+      if is_word && start <= index && index <= end {
+        maybe_found = Some(start..end);
+        if index < end {
+          break;
+        }
+      }
+      start = end;
+    }
+    maybe_found
+  }
+}
+
+/// Index of the visual line (in `ranges` returned by [EditorLine::wrap_to_display_cols]) that
+/// `display_col` is painted in. A display column at the end of the line is in the last visual line.
+pub fn wrapped_line_index_of_display_col(ranges: &[Range<usize>], display_col: usize) -> usize {
+  ranges
+    .iter()
+    .position(|range| display_col < range.end)
+    .unwrap_or_else(|| ranges.len().saturating_sub(1))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// A family emoji: 3 emoji joined by zero width joiners, painted as one 2 column wide emoji.
+  const FAMILY: &str = "👨\u{200d}👩\u{200d}👧";
+  /// `e` followed by a combining acute accent.
+  const E_ACUTE: &str = "e\u{301}";
+
+  #[test]
+  fn test_zwj_emoji_is_one_grapheme_cluster() {
+    let line = format!("a{}b", FAMILY);
+    assert_eq!(line.grapheme_count(), 3);
+    assert_eq!(line.grapheme_at(1), Some(FAMILY));
+    assert_eq!(line.byte_index_of_grapheme(2), 1 + FAMILY.len());
+    assert_eq!(line.display_width(), 4);
+    assert_eq!(line.display_col_of_grapheme(2), 3);
+    assert_eq!(line.grapheme_index_at_display_col(2), 1);
+    assert_eq!(line.grapheme_index_at_display_col(3), 2);
+  }
+
+  #[test]
+  fn test_cjk_is_wide() {
+    let line = "a世界b";
+    assert_eq!(line.grapheme_count(), 4);
+    assert_eq!(line.display_width(), 6);
+    assert_eq!(line.display_col_of_grapheme(2), 3);
+    assert_eq!(line.display_col_of_grapheme(3), 5);
+    // The 2nd column of a wide char is in it.
+    assert_eq!(line.grapheme_index_at_display_col(2), 1);
+    assert_eq!(line.grapheme_index_at_display_col(6), 4);
+  }
+
+  #[test]
+  fn test_combining_mark_is_part_of_grapheme_cluster() {
+    let line = format!("caf{}!", E_ACUTE);
+    assert_eq!(line.grapheme_count(), 5);
+    assert_eq!(line.grapheme_at(3), Some(E_ACUTE));
+    assert_eq!(line.byte_index_of_grapheme(4), 3 + E_ACUTE.len());
+    assert_eq!(line.display_width(), 5);
+    assert_eq!(line.grapheme_index_at_display_col(4), 4);
+  }
+
+  #[test]
+  fn test_byte_index_past_end() {
+    assert_eq!("ab".byte_index_of_grapheme(5), 2);
+    assert_eq!("ab".grapheme_at(2), None);
+  }
+
+  #[test]
+  fn test_clip_pads_cut_wide_chars() {
+    let line = "a世界b";
+    assert_eq!(line.clip_to_display_cols(0, 6), "a世界b");
+    assert_eq!(line.clip_to_display_cols(2, 3), " 界");
+    assert_eq!(line.clip_to_display_cols(0, 2), "a ");
+    assert_eq!(format!("{}x", FAMILY).clip_to_display_cols(1, 2), " x");
+  }
+
+  #[test]
+  fn test_wrap_breaks_after_whitespace() {
+    // The space after `cd` hangs past the width.
+    assert_eq!("ab cd ef".wrap_to_display_cols(5), vec![0..6, 6..8]);
+    assert_eq!("ab cd ef".wrap_to_display_cols(4), vec![0..3, 3..6, 6..8]);
+    assert_eq!("abcdefg".wrap_to_display_cols(3), vec![0..3, 3..6, 6..7]);
+    assert_eq!("".wrap_to_display_cols(3), vec![0..0]);
+  }
+
+  #[test]
+  fn test_wrap_doesnt_split_wide_chars() {
+    assert_eq!("世界世".wrap_to_display_cols(3), vec![0..2, 2..4, 4..6]);
+    let line = format!("{}{}", FAMILY, FAMILY);
+    assert_eq!(line.wrap_to_display_cols(3), vec![0..2, 2..4]);
+  }
+
+  #[test]
+  fn test_word_range_at() {
+    let line = format!("foo caf{} _x1", E_ACUTE);
+    assert_eq!(line.word_range_at(1), Some(0..3));
+    // Right after the end of a word.
+    assert_eq!(line.word_range_at(3), Some(0..3));
+    assert_eq!(line.word_range_at(5), Some(4..8));
+    assert_eq!(line.word_range_at(9), Some(9..12));
+    assert_eq!("a  b".word_range_at(2), None);
+  }
+}
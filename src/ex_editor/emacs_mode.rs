@@ -0,0 +1,213 @@
+/*
+ *   Copyright (c) 2022 R3BL LLC
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use crossterm::event::*;
+use r3bl_rs_utils::*;
+
+use super::*;
+
+/// Emacs style key bindings, a layer in front of the default key bindings of an
+/// [EditorComponent]. Keys that it doesn't handle (eg: typing, Enter, Backspace, arrows) are
+/// handled by the default key bindings.
+/// - C-f, C-b, C-n, C-p    : right, left, down, up
+/// - C-a, C-e              : start & end of the line
+/// - M-f, M-b              : forward & backward by word
+/// - C-v, M-v, M-<, M->    : page down & up, start & end of the buffer
+/// - C-d                   : delete
+/// - C-k, C-y, M-y         : kill to the end of the line, yank, yank the previous kill (see
+///   [KillRing])
+/// - C-/, C-_              : undo
+/// - C-s, C-r              : incremental search (see [action_for_emacs_search_key])
+/// - C-g                   : cancel (eg: a C-x prefix or extra carets)
+/// - C-x C-s, C-x C-c      : save, quit (C-x C-c again to quit w/ unsaved changes)
+/// - C-x u, C-x h          : undo, select the whole buffer
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EmacsState {
+  /// C-x was typed, the rest of the chord is expected.
+  pub is_ctrl_x_pending: bool,
+  /// C-x C-c was typed while there were unsaved changes, typing it again quits.
+  pub is_quit_requested: bool,
+}
+
+impl EmacsState {
+  /// Pending prefix, for the status line.
+  pub fn get_status(&self) -> Option<&str> {
+    match self.is_ctrl_x_pending {
+      true => Some("C-x-"),
+      false => None,
+    }
+  }
+
+  pub fn handle_event(
+    &mut self, input_event: &TWInputEvent, id: &str, buffer: &EditorBuffer, page_height: usize,
+  ) -> KeyBindingOutcome {
+    let is_quit_requested = std::mem::take(&mut self.is_quit_requested);
+    if std::mem::take(&mut self.is_ctrl_x_pending) {
+      return self.handle_ctrl_x_chord(input_event, id, buffer, is_quit_requested);
+    }
+
+    let key_event = match input_event {
+      TWInputEvent::NonDisplayableKeypress(key_event) => key_event,
+      _ => return KeyBindingOutcome::NotHandled,
+    };
+    let id = id.to_string();
+    let move_caret = |direction| vec![EditorAction::MoveCaret(id.clone(), direction)];
+    let actions = match (key_event.code, key_event.modifiers) {
+      (KeyCode::Char('f'), KeyModifiers::CONTROL) => move_caret(CaretDirection::Right),
+      (KeyCode::Char('b'), KeyModifiers::CONTROL) => move_caret(CaretDirection::Left),
+      (KeyCode::Char('n'), KeyModifiers::CONTROL) => move_caret(CaretDirection::Down),
+      (KeyCode::Char('p'), KeyModifiers::CONTROL) => move_caret(CaretDirection::Up),
+      (KeyCode::Char('a'), KeyModifiers::CONTROL) => move_caret(CaretDirection::LineStart),
+      (KeyCode::Char('e'), KeyModifiers::CONTROL) => move_caret(CaretDirection::LineEnd),
+      (KeyCode::Char('v'), KeyModifiers::CONTROL) => {
+        move_caret(CaretDirection::PageDown(page_height))
+      }
+      (KeyCode::Char('f'), KeyModifiers::ALT) => move_caret(CaretDirection::WordRight),
+      (KeyCode::Char('b'), KeyModifiers::ALT) => move_caret(CaretDirection::WordLeft),
+      (KeyCode::Char('v'), KeyModifiers::ALT) => move_caret(CaretDirection::PageUp(page_height)),
+      (KeyCode::Char('<'), modifiers) if modifiers.contains(KeyModifiers::ALT) => {
+        move_caret(CaretDirection::BufferStart)
+      }
+      (KeyCode::Char('>'), modifiers) if modifiers.contains(KeyModifiers::ALT) => {
+        move_caret(CaretDirection::BufferEnd)
+      }
+      (KeyCode::Char('d'), KeyModifiers::CONTROL) => vec![EditorAction::Delete(id)],
+      (KeyCode::Char('k'), KeyModifiers::CONTROL) => vec![EditorAction::Kill(id)],
+      (KeyCode::Char('y'), KeyModifiers::CONTROL) => vec![EditorAction::Yank(id)],
+      (KeyCode::Char('y'), KeyModifiers::ALT) => vec![EditorAction::YankPop(id)],
+      (KeyCode::Char('/' | '_'), KeyModifiers::CONTROL) => vec![EditorAction::Undo(id)],
+      (KeyCode::Char('s' | 'r'), KeyModifiers::CONTROL) => vec![EditorAction::StartSearch(id)],
+      (KeyCode::Char('g'), KeyModifiers::CONTROL) => vec![EditorAction::ClearExtraCarets(id)],
+      (KeyCode::Char('x'), KeyModifiers::CONTROL) => {
+        self.is_ctrl_x_pending = true;
+        self.is_quit_requested = is_quit_requested;
+        vec![]
+      }
+      _ => return KeyBindingOutcome::NotHandled,
+    };
+    KeyBindingOutcome::Dispatch(actions)
+  }
+
+  fn handle_ctrl_x_chord(
+    &mut self, input_event: &TWInputEvent, id: &str, buffer: &EditorBuffer, is_quit_requested: bool,
+  ) -> KeyBindingOutcome {
+    let id = id.to_string();
+    let (code, modifiers) = match input_event {
+      TWInputEvent::DisplayableKeypress(typed_char) => {
+        (KeyCode::Char(*typed_char), KeyModifiers::NONE)
+      }
+      TWInputEvent::NonDisplayableKeypress(key_event) => (key_event.code, key_event.modifiers),
+      _ => return KeyBindingOutcome::Dispatch(vec![]),
+    };
+    let actions = match (code, modifiers) {
+      (KeyCode::Char('s'), KeyModifiers::CONTROL) => {
+        return KeyBindingOutcome::Run(EditorCommand::Write)
+      }
+      (KeyCode::Char('c'), KeyModifiers::CONTROL) if is_quit_requested => {
+        return KeyBindingOutcome::Run(EditorCommand::ForceQuit)
+      }
+      (KeyCode::Char('c'), KeyModifiers::CONTROL) => {
+        self.is_quit_requested = true;
+        return KeyBindingOutcome::Run(EditorCommand::Quit);
+      }
+      (KeyCode::Char('u'), KeyModifiers::NONE) => vec![EditorAction::Undo(id)],
+      (KeyCode::Char('h'), KeyModifiers::NONE) => {
+        let last_row = buffer.line_count() - 1;
+        vec![
+          EditorAction::SetCaret(id.clone(), CaretPos::default()),
+          EditorAction::ExtendSelectionTo(
+            id,
+            CaretPos {
+              row: last_row,
+              col: buffer.line_len(last_row),
+            },
+          ),
+        ]
+      }
+      (KeyCode::Char('g'), KeyModifiers::CONTROL) => vec![],
+      _ => vec![EditorAction::ShowMessage(format!(
+        "C-x {} is undefined",
+        describe_key(code, modifiers)
+      ))],
+    };
+    KeyBindingOutcome::Dispatch(actions)
+  }
+}
+
+/// Emacs key bindings for the search bar, which is incremental (it searches as the query is typed).
+/// Keys that aren't handled here are handled by the default key bindings of the search bar.
+/// - C-s, C-r              : next & previous match
+/// - Enter, C-g            : close the search bar, at the current match or where the search started
+///   (in the replacement field Enter replaces the current match, M-Enter replaces all)
+/// - C-f, C-b, C-a, C-e    : move the cursor in the active field (also M-f & M-b)
+/// - C-d, C-k, C-y         : delete, kill to the end of the field, yank
+pub fn action_for_emacs_search_key(
+  key_event: &KeyEvent, search: &EditorSearch,
+) -> Option<EditorAction> {
+  let is_replacement = search.active_field == SearchField::Replacement;
+  let action = match (key_event.code, key_event.modifiers) {
+    (KeyCode::Char('s'), KeyModifiers::CONTROL) => EditorAction::SearchNext,
+    (KeyCode::Char('r'), KeyModifiers::CONTROL) => EditorAction::SearchPrev,
+    (KeyCode::Enter, KeyModifiers::NONE) if is_replacement => EditorAction::ReplaceOne,
+    (KeyCode::Enter, KeyModifiers::ALT) if is_replacement => EditorAction::ReplaceAll,
+    (KeyCode::Enter, KeyModifiers::NONE) => EditorAction::EndSearch,
+    (KeyCode::Char('g'), KeyModifiers::CONTROL) => EditorAction::CancelSearch,
+    (KeyCode::Char('f'), KeyModifiers::CONTROL) => {
+      EditorAction::SearchMoveCursor(CaretDirection::Right)
+    }
+    (KeyCode::Char('b'), KeyModifiers::CONTROL) => {
+      EditorAction::SearchMoveCursor(CaretDirection::Left)
+    }
+    (KeyCode::Char('a'), KeyModifiers::CONTROL) => {
+      EditorAction::SearchMoveCursor(CaretDirection::LineStart)
+    }
+    (KeyCode::Char('e'), KeyModifiers::CONTROL) => {
+      EditorAction::SearchMoveCursor(CaretDirection::LineEnd)
+    }
+    (KeyCode::Char('f'), KeyModifiers::ALT) => {
+      EditorAction::SearchMoveCursor(CaretDirection::WordRight)
+    }
+    (KeyCode::Char('b'), KeyModifiers::ALT) => {
+      EditorAction::SearchMoveCursor(CaretDirection::WordLeft)
+    }
+    (KeyCode::Char('d'), KeyModifiers::CONTROL)
+      if search.cursor < search.get_active_field().grapheme_count() =>
+    {
+      return Some(EditorAction::Batch(vec![
+        EditorAction::SearchMoveCursor(CaretDirection::Right),
+        EditorAction::SearchBackspace,
+      ]))
+    }
+    (KeyCode::Char('d'), KeyModifiers::CONTROL) => EditorAction::Noop,
+    (KeyCode::Char('k'), KeyModifiers::CONTROL) => EditorAction::SearchKill,
+    (KeyCode::Char('y'), KeyModifiers::CONTROL) => EditorAction::SearchYank,
+    _ => return None,
+  };
+  Some(action)
+}
+
+fn describe_key(code: KeyCode, modifiers: KeyModifiers) -> String {
+  let key = match code {
+    KeyCode::Char(typed_char) => typed_char.to_string(),
+    code => format!("{:?}", code),
+  };
+  match modifiers {
+    KeyModifiers::CONTROL => format!("C-{}", key),
+    KeyModifiers::ALT => format!("M-{}", key),
+    _ => key,
+  }
+}
@@ -0,0 +1,308 @@
+/*
+ *   Copyright (c) 2022 R3BL LLC
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use std::io::{self, ErrorKind};
+
+use r3bl_rs_utils::*;
+use tokio::{fs::File,
+            io::{AsyncRead, AsyncReadExt}};
+
+use super::*;
+
+/// Files that are larger than this (in bytes) are loaded lazily, see [PendingLoad].
+const LAZY_LOAD_MIN_FILE_LEN: u64 = 4 * 1024 * 1024;
+
+/// Number of bytes that are read at a time from a file that is loaded lazily.
+const LOAD_CHUNK_LEN: u64 = 1024 * 1024;
+
+/// The rest of a large file, after its first chunk has been loaded into a buffer by [load_buffer].
+/// It is loaded in the background by [spawn_load_rest], so that the editor shows the start of the
+/// file right away.
+#[derive(Debug)]
+pub struct PendingLoad {
+  pub file_path: String,
+  pub file: File,
+  /// Bytes of the line that was cut off at the end of the last chunk that was read.
+  pub partial_line: Vec<u8>,
+}
+
+/// A file loaded by [load_file], as text or (if it isn't valid UTF-8) as bytes for the hex view.
+#[derive(Debug)]
+pub enum LoadedFile {
+  Text(Box<EditorBuffer>, Option<PendingLoad>),
+  Binary(HexBuffer),
+}
+
+/// Load the file at `file_path` into a new [EditorBuffer]. If the file can't be loaded, then an
+/// empty buffer and a message describing what went wrong are returned, instead of an error. A
+/// missing file is not an error, it is a new file (`file_path` is set, so that it can be saved
+/// later). A file that exists but can't be read must not be saved over w/ the empty buffer, so
+/// `file_path` isn't set then.
+///
+/// Only the first chunk of a large file is loaded, the buffer is marked as loading & the
+/// [PendingLoad] for the rest of the file is returned. A file that isn't valid UTF-8 is loaded
+/// into a [HexBuffer] instead.
+///
+/// The [IndentStyle] of the buffer comes from the config files of the file's project.
+pub async fn load_file(file_path: &str) -> (LoadedFile, Option<String>) {
+  match read_first_chunk(file_path).await {
+    Ok((content, maybe_pending_load)) => {
+      let mut buffer = EditorBuffer::from_content(&content);
+      buffer.file_path = Some(file_path.into());
+      buffer.is_loading = maybe_pending_load.is_some();
+      buffer.saved_lines = Some(buffer.lines.clone());
+      buffer.indent_style = IndentStyle::load(file_path).await;
+      (LoadedFile::Text(Box::new(buffer), maybe_pending_load), None)
+    }
+    Err(error) if error.kind() == ErrorKind::InvalidData => load_binary_file(file_path).await,
+    Err(error) => {
+      let is_new_file = error.kind() == ErrorKind::NotFound;
+      let mut buffer = EditorBuffer {
+        file_path: is_new_file.then(|| file_path.into()),
+        indent_style: IndentStyle::load(file_path).await,
+        ..Default::default()
+      };
+      if is_new_file {
+        buffer.saved_lines = Some(buffer.lines.clone());
+      }
+      let message = match is_new_file {
+        true => format!("New file {}", file_path),
+        false => describe_io_error("open", file_path, &error),
+      };
+      (LoadedFile::Text(Box::new(buffer), None), Some(message))
+    }
+  }
+}
+
+/// Load the file at `file_path` into a new [HexBuffer], w/ a message saying so. If that fails too,
+/// then it is handled like [load_file] does for a text file.
+async fn load_binary_file(file_path: &str) -> (LoadedFile, Option<String>) {
+  match load_hex_buffer(file_path).await {
+    Ok(hex_buffer) => (
+      LoadedFile::Binary(hex_buffer),
+      Some(get_binary_file_message(file_path)),
+    ),
+    Err(error) => {
+      let buffer = EditorBuffer {
+        file_path: (error.kind() == ErrorKind::NotFound).then(|| file_path.into()),
+        ..Default::default()
+      };
+      let message = describe_io_error("open", file_path, &error);
+      (LoadedFile::Text(Box::new(buffer), None), Some(message))
+    }
+  }
+}
+
+pub async fn load_hex_buffer(file_path: &str) -> io::Result<HexBuffer> {
+  let mut hex_buffer = HexBuffer::from(tokio::fs::read(file_path).await?);
+  hex_buffer.file_path = Some(file_path.into());
+  Ok(hex_buffer)
+}
+
+fn get_binary_file_message(file_path: &str) -> String {
+  format!("{} isn't UTF-8 text, opened in the hex view", file_path)
+}
+
+/// Load the rest of a large file into the buffer for `id` in a separate task, one chunk at a time
+/// (each one is dispatched w/ [EditorAction::AppendLines]). When it is done, dispatch
+/// [EditorAction::LoadFinished]. If a chunk isn't valid UTF-8, then the whole file is reloaded into
+/// the hex view w/ [EditorAction::SetHexBuffer].
+pub fn spawn_load_rest(
+  shared_store: &SharedStore<EditorState, EditorAction>, id: &str, mut pending_load: PendingLoad,
+) {
+  let shared_store = shared_store.clone();
+  let id = id.to_string();
+
+  tokio::spawn(async move {
+    loop {
+      let action = match pending_load.read_next_lines().await {
+        Ok(Some(lines)) => EditorAction::AppendLines(id.clone(), lines),
+        Ok(None) => EditorAction::LoadFinished(id.clone(), None),
+        Err(error) if error.kind() == ErrorKind::InvalidData => {
+          let file_path = pending_load.file_path.clone();
+          let actions = match load_hex_buffer(&file_path).await {
+            Ok(hex_buffer) => vec![
+              EditorAction::SetHexBuffer(id.clone(), hex_buffer),
+              EditorAction::ShowMessage(get_binary_file_message(&file_path)),
+            ],
+            Err(error) => {
+              let message = describe_io_error("load", &file_path, &error);
+              vec![EditorAction::LoadFinished(id.clone(), Some(message))]
+            }
+          };
+          let mut store = shared_store.write().await;
+          for action in actions {
+            store.dispatch_action(action).await;
+          }
+          break;
+        }
+        Err(error) => {
+          let message = describe_io_error("load", &pending_load.file_path, &error);
+          EditorAction::LoadFinished(id.clone(), Some(message))
+        }
+      };
+      let is_finished = matches!(action, EditorAction::LoadFinished(..));
+      shared_store.write().await.dispatch_action(action).await;
+      if is_finished {
+        break;
+      }
+    }
+  });
+}
+
+impl PendingLoad {
+  /// The complete lines of the next chunk of the file, or `None` at the end of the file.
+  async fn read_next_lines(&mut self) -> io::Result<Option<Vec<String>>> {
+    let mut bytes = std::mem::take(&mut self.partial_line);
+    let is_at_end = read_chunk(&mut self.file, &mut bytes).await? == 0;
+    if is_at_end && bytes.is_empty() {
+      return Ok(None);
+    }
+    // At the end of the file, the partial line is the last line (that doesn't end w/ a new line).
+    let content = match is_at_end {
+      true => decode(bytes)?,
+      false => {
+        let (content, partial_line) = split_at_last_new_line(bytes)?;
+        self.partial_line = partial_line;
+        content
+      }
+    };
+    Ok(Some(content.lines().map(String::from).collect()))
+  }
+}
+
+/// The whole content of the file, or only its first lines if it is large (w/ the [PendingLoad] for
+/// the rest of it).
+async fn read_first_chunk(file_path: &str) -> io::Result<(String, Option<PendingLoad>)> {
+  let mut file = File::open(file_path).await?;
+  let mut bytes = vec![];
+  if file.metadata().await?.len() < LAZY_LOAD_MIN_FILE_LEN {
+    file.read_to_end(&mut bytes).await?;
+    return Ok((decode(bytes)?, None));
+  }
+
+  // Read until there is at least one complete line (or the whole file has been read).
+  while !bytes.contains(&b'\n') {
+    if read_chunk(&mut file, &mut bytes).await? == 0 {
+      return Ok((decode(bytes)?, None));
+    }
+  }
+  let (content, partial_line) = split_at_last_new_line(bytes)?;
+  let pending_load = PendingLoad {
+    file_path: file_path.into(),
+    file,
+    partial_line,
+  };
+  Ok((content, Some(pending_load)))
+}
+
+/// Append up to [LOAD_CHUNK_LEN] bytes from `reader` to `bytes`, & return how many were read.
+async fn read_chunk(
+  reader: &mut (impl AsyncRead + Unpin), bytes: &mut Vec<u8>,
+) -> io::Result<usize> {
+  reader.take(LOAD_CHUNK_LEN).read_to_end(bytes).await
+}
+
+/// Decode the lines of `bytes` up to (& including) the last new line, & return the bytes after it.
+/// A new line byte is never part of a multi byte UTF-8 sequence, so no character is cut in two.
+fn split_at_last_new_line(mut bytes: Vec<u8>) -> io::Result<(String, Vec<u8>)> {
+  let end = bytes
+    .iter()
+    .rposition(|it| *it == b'\n')
+    .map_or(0, |index| index + 1);
+  let partial_line = bytes.split_off(end);
+  Ok((decode(bytes)?, partial_line))
+}
+
+fn decode(bytes: Vec<u8>) -> io::Result<String> {
+  String::from_utf8(bytes).map_err(|error| io::Error::new(ErrorKind::InvalidData, error))
+}
+
+/// Write the buffer for `id` to its file in a separate task, so that the main event loop isn't
+/// blocked. When it is done, dispatch [EditorAction::FileSaved] or an [EditorAction::ShowMessage]
+/// w/ the error.
+pub fn spawn_save_buffer(
+  shared_store: &SharedStore<EditorState, EditorAction>, id: &str, buffer: &EditorBuffer,
+) {
+  let shared_store = shared_store.clone();
+  let id = id.to_string();
+  let maybe_file_path = buffer.file_path.clone();
+  let change_count = buffer.change_count;
+  // A buffer that is still loading only has the start of its file.
+  let maybe_content = (!buffer.is_loading).then(|| buffer.get_as_file_content());
+  let loading_message = get_loading_message(buffer);
+
+  tokio::spawn(async move {
+    let action = match maybe_content {
+      Some(content) => save_buffer(id, maybe_file_path, content.into_bytes(), change_count).await,
+      None => EditorAction::ShowMessage(loading_message),
+    };
+    debug_log_editor_action(stringify!(spawn_save_buffer).into(), action.clone());
+    shared_store.write().await.dispatch_action(action).await;
+  });
+}
+
+/// Same as [spawn_save_buffer], for a buffer in the hex view.
+pub fn spawn_save_hex_buffer(
+  shared_store: &SharedStore<EditorState, EditorAction>, id: &str, hex_buffer: &HexBuffer,
+) {
+  let shared_store = shared_store.clone();
+  let id = id.to_string();
+  let maybe_file_path = hex_buffer.file_path.clone();
+  let content = hex_buffer.get_bytes();
+  let change_count = hex_buffer.change_count;
+
+  tokio::spawn(async move {
+    let action = save_buffer(id, maybe_file_path, content, change_count).await;
+    debug_log_editor_action(stringify!(spawn_save_hex_buffer).into(), action.clone());
+    shared_store.write().await.dispatch_action(action).await;
+  });
+}
+
+/// Write `content` (the content of the buffer for `id` at `change_count`) to its file. Returns the
+/// action to dispatch when it is done, [EditorAction::FileSaved] or an [EditorAction::ShowMessage]
+/// w/ the error.
+pub async fn save_buffer(
+  id: String, maybe_file_path: Option<String>, content: Vec<u8>, change_count: u64,
+) -> EditorAction {
+  match maybe_file_path {
+    None => {
+      EditorAction::ShowMessage("No file name, run `r3bl-cmdr edit <file>` to set one".into())
+    }
+    Some(file_path) => match tokio::fs::write(&file_path, content).await {
+      Ok(_) => EditorAction::FileSaved(id, file_path, change_count),
+      Err(error) => EditorAction::ShowMessage(describe_io_error("save", &file_path, &error)),
+    },
+  }
+}
+
+/// Message for when a buffer can't be edited or saved, because its file is still being loaded.
+pub fn get_loading_message(buffer: &EditorBuffer) -> String {
+  format!(
+    "{} is still loading",
+    buffer.get_file_name().unwrap_or("The file")
+  )
+}
+
+fn describe_io_error(verb: &str, file_path: &str, error: &io::Error) -> String {
+  match error.kind() {
+    ErrorKind::NotFound => format!("Can't {} {}: no such file or directory", verb, file_path),
+    ErrorKind::PermissionDenied => format!("Can't {} {}: permission denied", verb, file_path),
+    ErrorKind::InvalidData => format!("Can't {} {}: not a UTF-8 text file", verb, file_path),
+    _ => format!("Can't {} {}: {}", verb, file_path, error),
+  }
+}
@@ -0,0 +1,362 @@
+/*
+ *   Copyright (c) 2022 R3BL LLC
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+use std::{collections::HashSet, ops::Range};
+
+use super::*;
+
+/// How the regions of a buffer that can be folded are found, picked by the extension of its file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FoldKind {
+  /// A line & the lines after it that are indented more (or are blank).
+  Indent,
+  /// A heading & the lines up to the next heading of the same or a higher level.
+  MarkdownHeading,
+  /// A line that opens a `{` block & the lines up to the one that closes it (which stays visible).
+  Braces,
+}
+
+/// An item of the outline of a buffer (see [EditorBuffer::get_outline]). `level` starts at 0.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutlineItem {
+  pub row: usize,
+  pub level: usize,
+  pub text: String,
+}
+
+/// Keywords that start the Rust items that are listed in the outline.
+const RUST_OUTLINE_KEYWORDS: [&str; 6] = ["fn", "struct", "enum", "trait", "impl", "mod"];
+
+/// Code folding. [EditorBuffer::folds] are the ranges of rows that are hidden, each one right
+/// after the line that was folded (which is shown w/ a placeholder line after it, see
+/// [get_visual_lines]). Folds may be nested, the caret skips over the hidden rows (see
+/// [EditorBuffer::get_moved_caret_pos]), & a fold is opened when a caret ends up in it.
+impl EditorBuffer {
+  pub fn get_fold_kind(&self) -> FoldKind {
+    match self.file_path.as_deref().and_then(|it| it.rsplit_once('.')) {
+      Some((_, "md" | "markdown")) => FoldKind::MarkdownHeading,
+      Some((_, "rs")) => FoldKind::Braces,
+      _ => FoldKind::Indent,
+    }
+  }
+
+  /// Fold the innermost region that contains the caret (or starts at its line), or open the fold
+  /// that starts at the caret's line. Returns a message for the user if there is nothing to fold.
+  pub fn toggle_fold(&mut self) -> Option<String> {
+    let row = self.caret.row;
+    if self.folds.iter().any(|it| it.start == row + 1) {
+      self.folds.retain(|it| it.start != row + 1);
+      return None;
+    }
+    match self.get_enclosing_fold_range(row) {
+      Some(hidden) => {
+        self.caret = CaretPos {
+          row: hidden.start - 1,
+          col: 0,
+        };
+        self.add_fold(hidden);
+        None
+      }
+      None => Some("Nothing to fold here".into()),
+    }
+  }
+
+  /// Fold every region in the buffer, including the nested ones.
+  pub fn fold_all(&mut self) {
+    let kind = self.get_fold_kind();
+    let fenced_rows = self.get_fenced_rows(kind);
+    for row in 0..self.line_count() {
+      if fenced_rows.contains(&row) {
+        continue;
+      }
+      if let Some(hidden) = self.get_fold_range(kind, row) {
+        self.add_fold(hidden);
+      }
+    }
+    // Like vi's `zM`, the caret moves to the line that hides it.
+    let row = self.get_unhidden_row(self.caret.row);
+    if row != self.caret.row {
+      self.caret = CaretPos { row, col: 0 };
+      self.selection_anchor = None;
+    }
+    self.unfold_at_carets();
+  }
+
+  pub fn unfold_all(&mut self) { self.folds.clear(); }
+
+  /// Open the folds that hide a caret.
+  pub fn unfold_at_carets(&mut self) {
+    let caret_rows: Vec<usize> = self.get_carets().iter().map(|it| it.pos.row).collect();
+    self
+      .folds
+      .retain(|hidden| !caret_rows.iter().any(|row| hidden.contains(row)));
+  }
+
+  /// Move the folds from `first_row` by `delta` rows, after an edit that added that many lines
+  /// before `first_row` (or removed them from `first_row`). Folds that hid removed lines are
+  /// dropped, & a fold that hides the line before `first_row` also hides the lines that were added
+  /// after it (eg: by a peer, see [EditorBuffer::apply_operation]).
+  pub fn shift_folds(&mut self, first_row: usize, delta: isize) {
+    let removed_rows = first_row..first_row + delta.unsigned_abs();
+    let line_count = self.line_count();
+    self.folds.retain_mut(|hidden| {
+      let header_row = hidden.start - 1;
+      if delta < 0 && header_row < removed_rows.end && removed_rows.start < hidden.end {
+        return false;
+      }
+      if hidden.start >= first_row {
+        hidden.start = (hidden.start as isize + delta) as usize;
+        hidden.end = (hidden.end as isize + delta) as usize;
+      } else if delta > 0 && first_row <= hidden.end {
+        hidden.end += delta as usize;
+      }
+      hidden.start > 0 && hidden.start < hidden.end && hidden.end <= line_count
+    });
+  }
+
+  pub fn is_row_hidden(&self, row: usize) -> bool { self.folds.iter().any(|it| it.contains(&row)) }
+
+  /// The outermost fold that starts after the line at `row`, which is shown w/ a placeholder.
+  pub fn get_fold_at(&self, row: usize) -> Option<&Range<usize>> {
+    self
+      .folds
+      .iter()
+      .filter(|it| it.start == row + 1)
+      .max_by_key(|it| it.end)
+  }
+
+  /// `row` if it isn't hidden, or the line that was folded to hide it.
+  pub fn get_unhidden_row(&self, mut row: usize) -> usize {
+    while let Some(hidden) = self.folds.iter().find(|it| it.contains(&row)) {
+      row = hidden.start - 1;
+    }
+    row
+  }
+
+  /// The first row after `row` that isn't hidden.
+  pub fn get_next_unhidden_row(&self, row: usize) -> Option<usize> {
+    let mut next_row = row + 1;
+    while let Some(hidden) = self.folds.iter().find(|it| it.contains(&next_row)) {
+      next_row = hidden.end;
+    }
+    (next_row < self.line_count()).then_some(next_row)
+  }
+
+  /// The last row before `row` that isn't hidden.
+  pub fn get_prev_unhidden_row(&self, row: usize) -> Option<usize> {
+    Some(self.get_unhidden_row(row.checked_sub(1)?))
+  }
+
+  /// Markdown headings, or Rust items (w/ their nesting level in `{}` blocks). Other files have
+  /// no outline.
+  pub fn get_outline(&self) -> Vec<OutlineItem> {
+    match self.get_fold_kind() {
+      FoldKind::MarkdownHeading => {
+        let fenced_rows = self.get_fenced_rows(FoldKind::MarkdownHeading);
+        self
+          .lines
+          .iter()
+          .enumerate()
+          .filter(|(row, _)| !fenced_rows.contains(row))
+          .filter_map(|(row, line)| {
+            let level = get_heading_level(line)?;
+            Some(OutlineItem {
+              row,
+              level: level - 1,
+              text: line.trim_start_matches('#').trim().to_string(),
+            })
+          })
+          .collect()
+      }
+      FoldKind::Braces => {
+        let mut depth = 0;
+        let mut items = vec![];
+        for (row, line) in self.lines.iter().enumerate() {
+          if is_rust_outline_item(line) {
+            let text = line.trim().trim_end_matches('{').trim_end().to_string();
+            items.push(OutlineItem {
+              row,
+              level: depth as usize,
+              text,
+            });
+          }
+          depth = get_brace_depths(line, depth).1.max(0);
+        }
+        items
+      }
+      FoldKind::Indent => vec![],
+    }
+  }
+
+  /// Folds are kept sorted, so that nested ones come after the ones that contain them.
+  fn add_fold(&mut self, hidden: Range<usize>) {
+    if !self.folds.contains(&hidden) {
+      self.folds.push(hidden);
+      self
+        .folds
+        .sort_by_key(|it| (it.start, std::cmp::Reverse(it.end)));
+    }
+  }
+
+  /// The region that starts at `row`, or the innermost one that contains it.
+  fn get_enclosing_fold_range(&self, row: usize) -> Option<Range<usize>> {
+    let kind = self.get_fold_kind();
+    let fenced_rows = self.get_fenced_rows(kind);
+    (0..=row)
+      .rev()
+      .filter(|it| !fenced_rows.contains(it))
+      .filter_map(|header_row| self.get_fold_range(kind, header_row))
+      .find(|hidden| hidden.start <= row + 1 && row < hidden.end)
+  }
+
+  /// The rows that a fold of the line at `row` hides, if a region starts there.
+  fn get_fold_range(&self, kind: FoldKind, row: usize) -> Option<Range<usize>> {
+    let line = self.lines.get(row)?;
+    let end = match kind {
+      FoldKind::Indent => {
+        if line.trim().is_empty() {
+          return None;
+        }
+        let indent = get_indent(line);
+        let end = (row + 1..self.line_count())
+          .find(|it| {
+            let next_line = &self.lines[*it];
+            !next_line.trim().is_empty() && get_indent(next_line) <= indent
+          })
+          .unwrap_or(self.line_count());
+        self.skip_blank_lines_back(row, end)
+      }
+      FoldKind::MarkdownHeading => {
+        let level = get_heading_level(line)?;
+        let mut is_in_fence = false;
+        let end = (row + 1..self.line_count())
+          .find(|it| {
+            let next_line = &self.lines[*it];
+            if is_fence(next_line) {
+              is_in_fence = !is_in_fence;
+            }
+            !is_in_fence && matches!(get_heading_level(next_line), Some(it) if it <= level)
+          })
+          .unwrap_or(self.line_count());
+        self.skip_blank_lines_back(row, end)
+      }
+      FoldKind::Braces => {
+        // Closing braces before the opening one (eg: `} else {`) close the previous block.
+        let (min_depth, end_depth) = get_brace_depths(line, 0);
+        let mut depth = end_depth - min_depth;
+        if depth == 0 {
+          return None;
+        }
+        (row + 1..self.line_count()).find(|it| {
+          let (min_depth, end_depth) = get_brace_depths(&self.lines[*it], depth);
+          depth = end_depth;
+          min_depth <= 0
+        })?
+      }
+    };
+    (end > row + 1).then_some(row + 1..end)
+  }
+
+  /// `end`, w/out the blank lines right before it (the ones that separate regions stay visible).
+  fn skip_blank_lines_back(&self, row: usize, mut end: usize) -> usize {
+    while end > row + 1 && self.lines[end - 1].trim().is_empty() {
+      end -= 1;
+    }
+    end
+  }
+
+  /// Rows inside of Markdown code fences, where a `#` doesn't start a heading.
+  fn get_fenced_rows(&self, kind: FoldKind) -> HashSet<usize> {
+    let mut fenced_rows = HashSet::new();
+    if kind != FoldKind::MarkdownHeading {
+      return fenced_rows;
+    }
+    let mut is_in_fence = false;
+    for (row, line) in self.lines.iter().enumerate() {
+      let is_fence_line = is_fence(line);
+      if is_fence_line {
+        is_in_fence = !is_in_fence;
+      }
+      if is_in_fence || is_fence_line {
+        fenced_rows.insert(row);
+      }
+    }
+    fenced_rows
+  }
+}
+
+/// Leading whitespace of `line`, in bytes.
+fn get_indent(line: &str) -> usize { line.len() - line.trim_start().len() }
+
+fn is_fence(line: &str) -> bool {
+  let line = line.trim_start();
+  line.starts_with("```") || line.starts_with("~~~")
+}
+
+/// Level of a Markdown heading (eg: 2 for `## Usage`).
+fn get_heading_level(line: &str) -> Option<usize> {
+  let level = line.len() - line.trim_start_matches('#').len();
+  let rest = &line[level..];
+  ((1..=6).contains(&level) && (rest.is_empty() || rest.starts_with(' '))).then_some(level)
+}
+
+/// Lowest & final depth of `{}` blocks in `line`, starting at `depth`. Braces in string & char
+/// literals, & after `//`, don't count.
+fn get_brace_depths(line: &str, depth: isize) -> (isize, isize) {
+  let chars: Vec<char> = line.chars().collect();
+  let mut depth = depth;
+  let mut min_depth = depth;
+  let mut index = 0;
+  while index < chars.len() {
+    match chars[index] {
+      '/' if chars.get(index + 1) == Some(&'/') => break,
+      '"' => {
+        index += 1;
+        while index < chars.len() && chars[index] != '"' {
+          index += if chars[index] == '\\' { 2 } else { 1 };
+        }
+      }
+      // A char literal (& not a lifetime, like `'a`).
+      '\'' if chars.get(index + 2) == Some(&'\'') => index += 2,
+      '\'' if chars.get(index + 1) == Some(&'\\') => {
+        while index + 1 < chars.len() && chars[index + 1] != '\'' {
+          index += 1;
+        }
+        index += 1;
+      }
+      '{' => depth += 1,
+      '}' => {
+        depth -= 1;
+        min_depth = min_depth.min(depth);
+      }
+      _ => {}
+    }
+    index += 1;
+  }
+  (min_depth, depth)
+}
+
+/// Whether `line` starts a Rust item that is listed in the outline (eg: `pub(crate) async fn`).
+fn is_rust_outline_item(line: &str) -> bool {
+  let mut words = line
+    .trim_start()
+    .split(|it: char| it.is_whitespace() || it == '<' || it == '(' || it == '{')
+    .filter(|it| !it.is_empty())
+    .skip_while(|it| {
+      it.starts_with("pub") || matches!(*it, "async" | "const" | "unsafe" | "extern" | "default")
+    });
+  matches!(words.next(), Some(word) if RUST_OUTLINE_KEYWORDS.contains(&word))
+}
@@ -0,0 +1,88 @@
+/*
+ *   Copyright (c) 2022 R3BL LLC
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use r3bl_rs_utils::*;
+
+use super::*;
+
+/// How the line numbers in the gutter are shown.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LineNumberMode {
+  #[default]
+  Absolute,
+  /// The caret's line shows its line number, & the other lines their distance from it.
+  Relative,
+}
+
+impl LineNumberMode {
+  pub fn toggle(self) -> Self {
+    match self {
+      LineNumberMode::Absolute => LineNumberMode::Relative,
+      LineNumberMode::Relative => LineNumberMode::Absolute,
+    }
+  }
+}
+
+/// Width of the gutter (in display columns) for a buffer w/ `line_count` lines, which fits the
+/// largest line number & a space after it.
+pub fn get_gutter_width(line_count: usize) -> usize { line_count.max(1).to_string().len() + 1 }
+
+/// Paint the line numbers for the `visual_lines` of an [EditorComponent] (from its last render) in
+/// `gutter_box`, right aligned. The number of the caret's line is bold. Visual lines that continue
+/// a soft wrapped line don't get a number. Every row of the box is painted, so that its background
+/// color fills it.
+pub fn render_gutter(
+  gutter_box: &TWBox, visual_lines: &[VisualLine], caret_row: usize, mode: LineNumberMode,
+) -> TWCommandQueue {
+  let width = gutter_box.bounding_size.cols as usize;
+  let mut queue = tw_command_queue!();
+
+  let mut maybe_prev_row = None;
+  for viewport_row in 0..gutter_box.bounding_size.rows as usize {
+    let maybe_visual_line = visual_lines.get(viewport_row);
+    let line_number = match maybe_visual_line {
+      Some(visual_line) if maybe_prev_row != Some(visual_line.row) => {
+        let row = visual_line.row;
+        match mode {
+          LineNumberMode::Relative if row != caret_row => row.abs_diff(caret_row).to_string(),
+          _ => (row + 1).to_string(),
+        }
+      }
+      _ => String::new(),
+    };
+    maybe_prev_row = maybe_visual_line.map(|it| it.row);
+
+    let mut maybe_style = gutter_box.get_computed_style();
+    if let Some(style) = maybe_style.as_mut() {
+      style.bold = maybe_visual_line.map(|it| it.row) == Some(caret_row);
+    }
+    let text = format!("{:>1$} ", line_number, width.saturating_sub(1));
+
+    tw_command_queue! {
+      queue push
+      TWCommand::MoveCursorPositionRelTo(
+        gutter_box.origin_pos,
+        Position { col: 0, row: viewport_row as UnitType }
+      ),
+      TWCommand::ApplyColors(maybe_style.clone()),
+      TWCommand::PrintWithAttributes(text.clip_to_display_cols(0, width), maybe_style),
+      TWCommand::ResetColor
+    };
+  }
+
+  queue
+}
@@ -0,0 +1,255 @@
+/*
+ *   Copyright (c) 2022 R3BL LLC
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use std::{fmt::{Display, Formatter},
+          sync::Arc};
+
+/// Number of bytes in each chunk of a [HexBuffer].
+const CHUNK_LEN: usize = 64 * 1024;
+
+/// Which pane of the hex view typed characters go to.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HexPane {
+  /// Hex digits overwrite the nibble under the cursor.
+  #[default]
+  Hex,
+  /// ASCII characters overwrite the byte under the cursor.
+  Ascii,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HexPromptKind {
+  GoToOffset,
+  Search,
+}
+
+/// Input field of the hex view, shown in the status line while it is open.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HexPrompt {
+  pub kind: HexPromptKind,
+  pub input: String,
+}
+
+impl Display for HexPrompt {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    let label = match self.kind {
+      HexPromptKind::GoToOffset => "Go to offset (eg: 4096 or 0x1000)",
+      HexPromptKind::Search => "Find bytes (eg: de ad be ef or \"text\")",
+    };
+    write!(f, "{}: {}▏", label, self.input)
+  }
+}
+
+/// Bytes of a file that isn't valid UTF-8, edited in the hex view (see [render_hex_view]). Like
+/// [LineRope], the bytes are stored in chunks that are shared by the clones of the buffer. Bytes
+/// are only overwritten (never inserted or removed), so the size of the file doesn't change.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HexBuffer {
+  chunks: Vec<Arc<Vec<u8>>>,
+  len: usize,
+  /// Offset of the byte under the cursor.
+  pub cursor: usize,
+  /// In the hex pane, the cursor is on the low nibble of the byte.
+  pub is_low_nibble: bool,
+  pub pane: HexPane,
+  pub maybe_prompt: Option<HexPrompt>,
+  /// Bytes that were searched for last, to find the next match.
+  pub maybe_pattern: Option<Vec<u8>>,
+  pub file_path: Option<String>,
+  pub is_dirty: bool,
+  /// Incremented by each write, like [EditorBuffer::change_count].
+  pub change_count: u64,
+}
+
+impl From<Vec<u8>> for HexBuffer {
+  fn from(bytes: Vec<u8>) -> Self {
+    Self {
+      chunks: bytes
+        .chunks(CHUNK_LEN)
+        .map(|chunk| Arc::new(chunk.to_vec()))
+        .collect(),
+      len: bytes.len(),
+      ..Default::default()
+    }
+  }
+}
+
+impl HexBuffer {
+  pub fn len(&self) -> usize { self.len }
+
+  pub fn is_empty(&self) -> bool { self.len == 0 }
+
+  pub fn get(&self, offset: usize) -> Option<u8> {
+    let chunk = self.chunks.get(offset / CHUNK_LEN)?;
+    chunk.get(offset % CHUNK_LEN).copied()
+  }
+
+  pub fn get_bytes(&self) -> Vec<u8> {
+    self
+      .chunks
+      .iter()
+      .flat_map(|chunk| chunk.iter().copied())
+      .collect()
+  }
+
+  pub fn get_file_name(&self) -> Option<&str> {
+    let file_path = self.file_path.as_deref()?;
+    std::path::Path::new(file_path)
+      .file_name()
+      .and_then(|it| it.to_str())
+      .or(Some(file_path))
+  }
+
+  /// Only the chunk of the byte at `offset` is copied, if it is shared w/ another buffer.
+  fn set(&mut self, offset: usize, byte: u8) {
+    if let Some(chunk) = self.chunks.get_mut(offset / CHUNK_LEN) {
+      Arc::make_mut(chunk)[offset % CHUNK_LEN] = byte;
+      self.is_dirty = true;
+      self.change_count += 1;
+    }
+  }
+
+  /// Move the cursor to `offset` (clamped to the last byte), at the high nibble.
+  pub fn set_cursor(&mut self, offset: usize) {
+    self.cursor = offset.min(self.len.saturating_sub(1));
+    self.is_low_nibble = false;
+  }
+
+  /// Overwrite the nibble under the cursor w/ `digit` (0 to 15), the cursor moves to the next
+  /// nibble.
+  pub fn write_nibble(&mut self, digit: u8) {
+    let byte = match self.get(self.cursor) {
+      Some(byte) => byte,
+      None => return,
+    };
+    match self.is_low_nibble {
+      false => {
+        self.set(self.cursor, (digit << 4) | (byte & 0x0f));
+        self.is_low_nibble = true;
+      }
+      true => {
+        self.set(self.cursor, (byte & 0xf0) | digit);
+        self.set_cursor(self.cursor + 1);
+      }
+    }
+  }
+
+  /// Overwrite the byte under the cursor, the cursor moves to the next byte.
+  pub fn write_byte(&mut self, byte: u8) {
+    if self.cursor < self.len {
+      self.set(self.cursor, byte);
+      self.set_cursor(self.cursor + 1);
+    }
+  }
+
+  /// First offset after `offset` (wrapping around to the start of the buffer) where `pattern`
+  /// starts.
+  pub fn find(&self, pattern: &[u8], offset: usize) -> Option<usize> {
+    if pattern.is_empty() || pattern.len() > self.len {
+      return None;
+    }
+    let last_start = self.len - pattern.len();
+    let is_match = |start: usize| {
+      pattern
+        .iter()
+        .enumerate()
+        .all(|(index, byte)| self.get(start + index) == Some(*byte))
+    };
+    (offset + 1..=last_start)
+      .chain(0..=offset.min(last_start))
+      .find(|start| is_match(*start))
+  }
+
+  /// Move the cursor to the next match of the last pattern that was searched for. Returns a
+  /// message for the user if there is none.
+  pub fn find_next(&mut self) -> Option<String> {
+    let pattern = match &self.maybe_pattern {
+      Some(pattern) => pattern,
+      None => return Some("Nothing to find, Ctrl+F to search for bytes".into()),
+    };
+    match self.find(pattern, self.cursor) {
+      Some(offset) => {
+        self.set_cursor(offset);
+        None
+      }
+      None => Some("Pattern not found".into()),
+    }
+  }
+
+  /// Run the command of the prompt (if it is open) & close it. Returns a message for the user if
+  /// the input is invalid, or nothing was found.
+  pub fn submit_prompt(&mut self) -> Option<String> {
+    let prompt = self.maybe_prompt.take()?;
+    match prompt.kind {
+      HexPromptKind::GoToOffset => match parse_offset(&prompt.input) {
+        Some(offset) if offset < self.len => {
+          self.set_cursor(offset);
+          None
+        }
+        Some(_) => Some(format!(
+          "Offset is past the end of the file ({} bytes)",
+          self.len
+        )),
+        None => Some(format!("Invalid offset: {}", prompt.input)),
+      },
+      HexPromptKind::Search => match parse_byte_pattern(&prompt.input) {
+        Some(pattern) => {
+          self.maybe_pattern = Some(pattern);
+          self.find_next()
+        }
+        None => Some(format!("Invalid byte pattern: {}", prompt.input)),
+      },
+    }
+  }
+}
+
+/// Decimal offset, or hex w/ a `0x` prefix.
+pub fn parse_offset(input: &str) -> Option<usize> {
+  let input = input.trim();
+  match input
+    .strip_prefix("0x")
+    .or_else(|| input.strip_prefix("0X"))
+  {
+    Some(hex_digits) => usize::from_str_radix(hex_digits, 16).ok(),
+    None => input.parse().ok(),
+  }
+}
+
+/// Hex digits (pairs may be separated by whitespace, eg: `de ad be ef`), or text in double quotes
+/// (eg: `"PNG"`) for its UTF-8 bytes.
+pub fn parse_byte_pattern(input: &str) -> Option<Vec<u8>> {
+  let input = input.trim();
+  if let Some(text) = input.strip_prefix('"') {
+    let text = text.strip_suffix('"').unwrap_or(text);
+    return (!text.is_empty()).then(|| text.as_bytes().to_vec());
+  }
+
+  let digits: Vec<u8> = input
+    .chars()
+    .filter(|it| !it.is_whitespace())
+    .map(|it| it.to_digit(16).map(|digit| digit as u8))
+    .collect::<Option<_>>()?;
+  if digits.is_empty() || digits.len() % 2 == 1 {
+    return None;
+  }
+  Some(
+    digits
+      .chunks(2)
+      .map(|pair| (pair[0] << 4) | pair[1])
+      .collect(),
+  )
+}
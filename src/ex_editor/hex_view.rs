@@ -0,0 +1,255 @@
+/*
+ *   Copyright (c) 2022 R3BL LLC
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use crossterm::event::*;
+use r3bl_rs_utils::*;
+
+use super::*;
+
+/// Rows of the hex view from its last render, used to move the cursor by rows & pages.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct HexViewport {
+  pub first_row: usize,
+  pub rows: usize,
+  pub bytes_per_row: usize,
+}
+
+impl HexViewport {
+  /// Scroll so that the row of `cursor` is visible.
+  fn scroll_to_cursor(&mut self, cursor: usize) {
+    let cursor_row = cursor / self.bytes_per_row;
+    if cursor_row < self.first_row {
+      self.first_row = cursor_row;
+    } else if cursor_row >= self.first_row + self.rows {
+      self.first_row = cursor_row + 1 - self.rows.max(1);
+    }
+  }
+}
+
+/// Paint `hex_buffer` in `current_box`, one row per 16 bytes (or 8 if the box is narrow): the
+/// offset of the first byte of the row, the bytes in hex (in groups of 8) & as ASCII (`.` for the
+/// ones that aren't printable). The cursor is painted in reverse video in the active pane, & the
+/// same byte is underlined in the other one.
+pub fn render_hex_view(
+  viewport: &mut HexViewport, current_box: &TWBox, hex_buffer: &HexBuffer, has_focus: bool,
+) -> TWCommandQueue {
+  let offset_width = get_offset_width(hex_buffer.len());
+  let box_cols = current_box.bounding_size.cols as usize;
+  viewport.rows = current_box.bounding_size.rows as usize;
+  viewport.bytes_per_row = match get_ascii_col(offset_width, 16) + 16 < box_cols {
+    true => 16,
+    false => 8,
+  };
+  viewport.scroll_to_cursor(hex_buffer.cursor);
+
+  let mut queue = tw_command_queue!();
+  for viewport_row in 0..viewport.rows {
+    let start = (viewport.first_row + viewport_row) * viewport.bytes_per_row;
+    if start >= hex_buffer.len() && !(start == 0 && hex_buffer.is_empty()) {
+      break;
+    }
+    let row = get_row_text(hex_buffer, start, viewport.bytes_per_row, offset_width);
+    tw_command_queue! {
+      queue push
+      TWCommand::MoveCursorPositionRelTo(
+        current_box.origin_pos,
+        Position { col: 0, row: viewport_row as UnitType }
+      ),
+      TWCommand::ApplyColors(current_box.get_computed_style()),
+      TWCommand::PrintWithAttributes(
+        row.clip_to_display_cols(0, box_cols),
+        current_box.get_computed_style()
+      ),
+      TWCommand::ResetColor
+    };
+  }
+
+  if has_focus && !hex_buffer.is_empty() {
+    render_hex_cursor(&mut queue, current_box, viewport, hex_buffer, offset_width);
+  }
+  queue
+}
+
+/// Action for a key press in the hex view. Hex digits (in the hex pane) or ASCII characters (in
+/// the ASCII pane) overwrite the byte under the cursor, & the keys are:
+/// - Arrows, Home, End     : move the cursor by byte, row, or to the start / end of the row
+/// - PageUp, PageDown      : move the cursor by page
+/// - Ctrl+Home, Ctrl+End   : move the cursor to the start / end of the file
+/// - Shift+Tab             : fire `HexTogglePane(id)`
+/// - Ctrl+G, Ctrl+F        : fire `HexOpenPrompt(id, GoToOffset | Search)`
+/// - F3                    : fire `HexFindNext(id)`
+///
+/// While the prompt is open, typed characters go to it, Enter runs it & Esc closes it.
+pub fn action_for_hex_input(
+  input_event: &TWInputEvent, id: &str, hex_buffer: &HexBuffer, viewport: &HexViewport,
+) -> Option<EditorAction> {
+  let id = id.to_string();
+  if hex_buffer.maybe_prompt.is_some() {
+    return match input_event {
+      TWInputEvent::DisplayableKeypress(typed_char) => {
+        Some(EditorAction::HexPromptInsertChar(id, *typed_char))
+      }
+      TWInputEvent::NonDisplayableKeypress(key_event) => match key_event.code {
+        KeyCode::Backspace => Some(EditorAction::HexPromptBackspace(id)),
+        KeyCode::Enter => Some(EditorAction::HexPromptSubmit(id)),
+        KeyCode::Esc => Some(EditorAction::HexPromptCancel(id)),
+        _ => None,
+      },
+      _ => None,
+    };
+  }
+
+  let key_event = match input_event {
+    TWInputEvent::DisplayableKeypress(typed_char) => {
+      return match hex_buffer.pane {
+        HexPane::Hex => typed_char
+          .to_digit(16)
+          .map(|digit| EditorAction::HexWriteNibble(id, digit as u8)),
+        HexPane::Ascii => typed_char
+          .is_ascii()
+          .then_some(EditorAction::HexWriteByte(id, *typed_char as u8)),
+      };
+    }
+    TWInputEvent::NonDisplayableKeypress(key_event) => key_event,
+    _ => return None,
+  };
+
+  let cursor = hex_buffer.cursor;
+  let bytes_per_row = viewport.bytes_per_row.max(1);
+  let page_len = bytes_per_row * viewport.rows.max(1);
+  let row_start = cursor - cursor % bytes_per_row;
+  let offset = match (key_event.code, key_event.modifiers) {
+    (KeyCode::BackTab, _) => return Some(EditorAction::HexTogglePane(id)),
+    (KeyCode::Char('g'), KeyModifiers::CONTROL) => {
+      return Some(EditorAction::HexOpenPrompt(id, HexPromptKind::GoToOffset));
+    }
+    (KeyCode::Char('f'), KeyModifiers::CONTROL) => {
+      return Some(EditorAction::HexOpenPrompt(id, HexPromptKind::Search));
+    }
+    (KeyCode::F(3), _) => return Some(EditorAction::HexFindNext(id)),
+    (KeyCode::Left, KeyModifiers::NONE) => cursor.saturating_sub(1),
+    (KeyCode::Right, KeyModifiers::NONE) => cursor + 1,
+    (KeyCode::Up, KeyModifiers::NONE) => cursor.checked_sub(bytes_per_row)?,
+    (KeyCode::Down, KeyModifiers::NONE) if cursor + bytes_per_row < hex_buffer.len() => {
+      cursor + bytes_per_row
+    }
+    (KeyCode::Home, KeyModifiers::NONE) => row_start,
+    (KeyCode::End, KeyModifiers::NONE) => row_start + bytes_per_row - 1,
+    (KeyCode::PageUp, KeyModifiers::NONE) => cursor.saturating_sub(page_len),
+    (KeyCode::PageDown, KeyModifiers::NONE) => cursor + page_len,
+    (KeyCode::Home, KeyModifiers::CONTROL) => 0,
+    (KeyCode::End, KeyModifiers::CONTROL) => usize::MAX,
+    _ => return None,
+  };
+  Some(EditorAction::HexSetCursor(id, offset))
+}
+
+/// Number of hex digits in the offset column, enough for the offset of the last byte.
+fn get_offset_width(len: usize) -> usize { format!("{:x}", len.saturating_sub(1)).len().max(8) }
+
+/// Display column of the hex digits of the byte at `index` in its row.
+fn get_hex_col(offset_width: usize, index: usize) -> usize {
+  offset_width + 2 + index * 3 + index / 8
+}
+
+/// Display column of the ASCII pane, after the hex digits of the `bytes_per_row` bytes of a row.
+fn get_ascii_col(offset_width: usize, bytes_per_row: usize) -> usize {
+  get_hex_col(offset_width, bytes_per_row) + 1
+}
+
+fn get_row_text(
+  hex_buffer: &HexBuffer, start: usize, bytes_per_row: usize, offset_width: usize,
+) -> String {
+  let mut hex = String::new();
+  let mut ascii = String::new();
+  for index in 0..bytes_per_row {
+    if index > 0 && index % 8 == 0 {
+      hex.push(' ');
+    }
+    match hex_buffer.get(start + index) {
+      Some(byte) => {
+        hex.push_str(&format!("{:02x} ", byte));
+        ascii.push(get_ascii_char(byte));
+      }
+      None => hex.push_str("   "),
+    }
+  }
+  format!(
+    "{:0width$x}  {}│{}│",
+    start,
+    hex,
+    ascii,
+    width = offset_width
+  )
+}
+
+fn get_ascii_char(byte: u8) -> char {
+  match byte {
+    0x20..=0x7e => byte as char,
+    _ => '.',
+  }
+}
+
+fn render_hex_cursor(
+  queue: &mut TWCommandQueue, current_box: &TWBox, viewport: &HexViewport, hex_buffer: &HexBuffer,
+  offset_width: usize,
+) {
+  let cursor = hex_buffer.cursor;
+  let byte = hex_buffer.get(cursor).unwrap_or_default();
+  let index = cursor % viewport.bytes_per_row;
+  let row = (cursor / viewport.bytes_per_row - viewport.first_row) as UnitType;
+  let hex_digits = format!("{:02x}", byte);
+  let hex_col = get_hex_col(offset_width, index);
+  let ascii_col = get_ascii_col(offset_width, viewport.bytes_per_row) + index;
+
+  let cursor_style = Some(style! { id: cursor attrib: [reverse] });
+  let other_pane_style = Some(style! { id: cursor_other_pane attrib: [underline] });
+  let (hex_cell, ascii_cell) = match hex_buffer.pane {
+    HexPane::Hex => {
+      let nibble_index = hex_buffer.is_low_nibble as usize;
+      let nibble = hex_digits[nibble_index..nibble_index + 1].to_string();
+      (
+        (hex_col + nibble_index, nibble, cursor_style.clone()),
+        (
+          ascii_col,
+          get_ascii_char(byte).to_string(),
+          other_pane_style,
+        ),
+      )
+    }
+    HexPane::Ascii => (
+      (hex_col, hex_digits, other_pane_style),
+      (ascii_col, get_ascii_char(byte).to_string(), cursor_style),
+    ),
+  };
+
+  let box_cols = current_box.bounding_size.cols as usize;
+  for (col, text, style) in [hex_cell, ascii_cell] {
+    if col >= box_cols {
+      continue;
+    }
+    tw_command_queue! {
+      queue push
+      TWCommand::MoveCursorPositionRelTo(
+        current_box.origin_pos,
+        Position { col: col as UnitType, row }
+      ),
+      TWCommand::PrintWithAttributes(text, style),
+      TWCommand::ResetColor
+    };
+  }
+}
@@ -0,0 +1,144 @@
+/*
+ *   Copyright (c) 2022 R3BL LLC
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use std::collections::VecDeque;
+
+use super::*;
+
+/// Maximum number of undo steps that are kept for each buffer, the oldest ones are dropped first.
+pub const MAX_UNDO_STEPS: usize = 100;
+
+/// Kind of edit that is being recorded in [EditorHistory]. Consecutive [EditKind::Typing] edits
+/// are grouped into a single undo step.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EditKind {
+  Typing,
+  Other,
+}
+
+/// Content & carets of an [EditorBuffer] at some point in time. The lines share their chunks w/
+/// the buffer (see [LineRope]), so a snapshot of a large file is cheap.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BufferSnapshot {
+  pub lines: LineRope,
+  pub carets: Vec<Caret>,
+}
+
+impl From<&EditorBuffer> for BufferSnapshot {
+  fn from(buffer: &EditorBuffer) -> Self {
+    Self {
+      lines: buffer.lines.clone(),
+      carets: buffer.get_carets(),
+    }
+  }
+}
+
+impl BufferSnapshot {
+  fn restore_into(self, buffer: &mut EditorBuffer) {
+    buffer.lines = self.lines;
+    buffer.set_carets(self.carets);
+    buffer.mark_changed();
+    buffer.update_is_dirty();
+  }
+
+  /// Apply `op` (an edit of a peer, which applies to `buffer`) to the snapshot, as if it had been
+  /// made before the edits of this instance since the snapshot. `None` if it doesn't apply.
+  fn transform(&mut self, buffer: &EditorBuffer, op: &TextOperation) -> Option<()> {
+    // The edits since the snapshot are undone by `undo_op`, `op` is moved to after it.
+    let undo_op = TextOperation::from_line_ropes(&buffer.lines, &self.lines);
+    let (_, op) = TextOperation::transform(&undo_op, op)?;
+    let mut snapshot_buffer = EditorBuffer {
+      lines: self.lines.clone(),
+      ..Default::default()
+    };
+    snapshot_buffer.set_carets(self.carets.clone());
+    snapshot_buffer.apply_operation(&op)?;
+    self.lines = snapshot_buffer.lines.clone();
+    self.carets = snapshot_buffer.get_carets();
+    Some(())
+  }
+}
+
+/// Bounded undo & redo history of snapshots for one [EditorBuffer]. The undo stack holds the
+/// snapshot from *before* each undo step.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EditorHistory {
+  pub undo_stack: VecDeque<BufferSnapshot>,
+  pub redo_stack: Vec<BufferSnapshot>,
+  /// Kind of the last edit that was recorded, `None` if the group was broken since then (eg: the
+  /// caret was moved).
+  pub last_edit_kind: Option<EditKind>,
+}
+
+impl EditorHistory {
+  /// Apply `edit` to `buffer` & record an undo step for it, if it changed the content of the
+  /// buffer. If `kind` can be grouped w/ the last edit, then no new undo step is created.
+  pub fn apply_edit(
+    &mut self, buffer: &mut EditorBuffer, kind: EditKind, edit: impl FnOnce(&mut EditorBuffer),
+  ) {
+    let is_grouped = kind == EditKind::Typing && self.last_edit_kind == Some(EditKind::Typing);
+    let maybe_snapshot = (!is_grouped).then(|| BufferSnapshot::from(&*buffer));
+    let change_count = buffer.change_count;
+
+    edit(buffer);
+
+    if buffer.change_count == change_count {
+      return;
+    }
+    if let Some(snapshot) = maybe_snapshot {
+      self.undo_stack.push_back(snapshot);
+      if self.undo_stack.len() > MAX_UNDO_STEPS {
+        self.undo_stack.pop_front();
+      }
+    }
+    self.redo_stack.clear();
+    self.last_edit_kind = Some(kind);
+  }
+
+  /// Keep the edit of a peer (`op`, which was applied to `old_buffer`) in the snapshots, so that
+  /// undo & redo only take back the edits of this instance. The history is cleared if a snapshot
+  /// can't be transformed.
+  pub fn transform(&mut self, old_buffer: &EditorBuffer, op: &TextOperation) {
+    let is_transformed = self
+      .undo_stack
+      .iter_mut()
+      .chain(self.redo_stack.iter_mut())
+      .all(|snapshot| snapshot.transform(old_buffer, op).is_some());
+    if !is_transformed {
+      *self = EditorHistory::default();
+    }
+  }
+
+  /// Stop grouping edits into the current undo step.
+  pub fn break_group(&mut self) { self.last_edit_kind = None; }
+
+  pub fn undo(&mut self, buffer: &mut EditorBuffer) {
+    if let Some(snapshot) = self.undo_stack.pop_back() {
+      self.redo_stack.push(BufferSnapshot::from(&*buffer));
+      snapshot.restore_into(buffer);
+    }
+    self.break_group();
+  }
+
+  pub fn redo(&mut self, buffer: &mut EditorBuffer) {
+    if let Some(snapshot) = self.redo_stack.pop() {
+      self.undo_stack.push_back(BufferSnapshot::from(&*buffer));
+      snapshot.restore_into(buffer);
+    }
+    self.break_group();
+  }
+}
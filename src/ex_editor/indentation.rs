@@ -0,0 +1,267 @@
+/*
+ *   Copyright (c) 2022 R3BL LLC
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use super::*;
+
+/// Files that set the indentation of the files in their directory (& below it).
+const EDITORCONFIG_FILE_NAME: &str = ".editorconfig";
+const RUSTFMT_FILE_NAMES: [&str; 2] = ["rustfmt.toml", ".rustfmt.toml"];
+
+/// Opening brackets that add a level of indentation to the next line (w/ their closing ones).
+const INDENT_BRACKETS: [(char, char); 3] = [('{', '}'), ('(', ')'), ('[', ']')];
+
+/// How the lines of a buffer are indented, see [IndentStyle::load].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IndentStyle {
+  pub width: usize,
+  pub is_hard_tabs: bool,
+}
+
+impl Default for IndentStyle {
+  fn default() -> Self {
+    Self {
+      width: 4,
+      is_hard_tabs: false,
+    }
+  }
+}
+
+impl IndentStyle {
+  /// One level of indentation.
+  pub fn get_unit(&self) -> String {
+    match self.is_hard_tabs {
+      true => "\t".into(),
+      false => " ".repeat(self.width),
+    }
+  }
+
+  /// The indentation for the file at `file_path`, from the nearest `rustfmt.toml` (for Rust
+  /// files) or from the `.editorconfig` files in its directory & the ones above it. The default
+  /// is used if there are none, or if they can't be read.
+  pub async fn load(file_path: &str) -> IndentStyle {
+    let path = match std::env::current_dir() {
+      Ok(current_dir) => current_dir.join(file_path),
+      Err(_) => PathBuf::from(file_path),
+    };
+    if path.extension().and_then(|it| it.to_str()) == Some("rs") {
+      if let Some(indent_style) = load_from_rustfmt_config(&path).await {
+        return indent_style;
+      }
+    }
+    load_from_editorconfig(&path).await.unwrap_or_default()
+  }
+}
+
+/// The part of a `rustfmt.toml` file that sets the indentation (the rest of it is ignored).
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct RustfmtConfig {
+  tab_spaces: Option<usize>,
+  hard_tabs: Option<bool>,
+}
+
+async fn load_from_rustfmt_config(path: &Path) -> Option<IndentStyle> {
+  for dir in path.ancestors().skip(1) {
+    for file_name in RUSTFMT_FILE_NAMES {
+      let content = match read_config_file(&dir.join(file_name)).await {
+        Some(content) => content,
+        None => continue,
+      };
+      let config: RustfmtConfig = toml::from_str(&content).ok()?;
+      let default = IndentStyle::default();
+      return Some(IndentStyle {
+        width: config.tab_spaces.unwrap_or(default.width).max(1),
+        is_hard_tabs: config.hard_tabs.unwrap_or(default.is_hard_tabs),
+      });
+    }
+  }
+  None
+}
+
+/// `.editorconfig` files are read from the file's directory up, until one of them is marked w/
+/// `root = true`. Properties in the files that are closer to the file win, & so do the ones in the
+/// later sections of a file.
+async fn load_from_editorconfig(path: &Path) -> Option<IndentStyle> {
+  let mut maybe_indent_style: Option<String> = None;
+  let mut maybe_indent_size: Option<String> = None;
+  let mut maybe_tab_width: Option<String> = None;
+
+  for dir in path.ancestors().skip(1) {
+    let content = match read_config_file(&dir.join(EDITORCONFIG_FILE_NAME)).await {
+      Some(content) => content,
+      None => continue,
+    };
+    let relative_path = path
+      .strip_prefix(dir)
+      .ok()?
+      .to_string_lossy()
+      .replace('\\', "/");
+    let properties = get_editorconfig_properties(&content, &relative_path);
+    let mut is_root = false;
+    for (key, value) in properties {
+      match key.as_str() {
+        "root" => is_root = value == "true",
+        "indent_style" => maybe_indent_style = maybe_indent_style.or(Some(value)),
+        "indent_size" => maybe_indent_size = maybe_indent_size.or(Some(value)),
+        "tab_width" => maybe_tab_width = maybe_tab_width.or(Some(value)),
+        _ => {}
+      }
+    }
+    if is_root {
+      break;
+    }
+  }
+
+  if maybe_indent_style.is_none() && maybe_indent_size.is_none() {
+    return None;
+  }
+  let is_hard_tabs = maybe_indent_style.as_deref() == Some("tab");
+  let maybe_tab_width = maybe_tab_width.and_then(|it| it.parse().ok());
+  let width = match maybe_indent_size.as_deref() {
+    Some("tab") | None => maybe_tab_width,
+    Some(indent_size) => indent_size.parse().ok(),
+  };
+  Some(IndentStyle {
+    width: width.unwrap_or(IndentStyle::default().width).max(1),
+    is_hard_tabs,
+  })
+}
+
+/// The properties (lowercase keys & values) in `content` that apply to the file at
+/// `relative_path`, w/ the ones in later sections first. `root` comes from the preamble.
+fn get_editorconfig_properties(content: &str, relative_path: &str) -> Vec<(String, String)> {
+  let mut properties = vec![];
+  let mut is_in_matching_section = true; // The preamble (before the first section).
+  let mut is_in_preamble = true;
+  for line in content.lines().map(str::trim) {
+    if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+      continue;
+    }
+    if let Some(pattern) = line.strip_prefix('[').and_then(|it| it.strip_suffix(']')) {
+      is_in_preamble = false;
+      is_in_matching_section = is_editorconfig_match(pattern, relative_path);
+      continue;
+    }
+    if let Some((key, value)) = line.split_once('=') {
+      let key = key.trim().to_lowercase();
+      if is_in_matching_section && (key != "root" || is_in_preamble) {
+        properties.push((key, value.trim().to_lowercase()));
+      }
+    }
+  }
+  properties.reverse();
+  properties
+}
+
+/// Section names are globs, w/ `*`, `**`, `?` & `{a,b}`. Globs w/out a `/` match the file name in
+/// any directory.
+fn is_editorconfig_match(pattern: &str, relative_path: &str) -> bool {
+  let (pattern, text) = match pattern.contains('/') {
+    true => (pattern.trim_start_matches('/'), relative_path),
+    false => (
+      pattern,
+      relative_path.rsplit('/').next().unwrap_or(relative_path),
+    ),
+  };
+  expand_braces(pattern)
+    .iter()
+    .any(|pattern| is_glob_match(pattern.as_bytes(), text.as_bytes()))
+}
+
+fn expand_braces(pattern: &str) -> Vec<String> {
+  let (start, end) = match (pattern.find('{'), pattern.find('}')) {
+    (Some(start), Some(end)) if start < end => (start, end),
+    _ => return vec![pattern.into()],
+  };
+  pattern[start + 1..end]
+    .split(',')
+    .flat_map(|alternative| {
+      expand_braces(&format!(
+        "{}{}{}",
+        &pattern[..start],
+        alternative,
+        &pattern[end + 1..]
+      ))
+    })
+    .collect()
+}
+
+/// `*` doesn't match `/`, `**` does.
+fn is_glob_match(pattern: &[u8], text: &[u8]) -> bool {
+  match pattern {
+    [] => text.is_empty(),
+    [b'*', b'*', rest @ ..] => (0..=text.len()).any(|index| is_glob_match(rest, &text[index..])),
+    [b'*', rest @ ..] => (0..=text.len())
+      .take_while(|index| *index == 0 || text[index - 1] != b'/')
+      .any(|index| is_glob_match(rest, &text[index..])),
+    [b'?', rest @ ..] => !text.is_empty() && is_glob_match(rest, &text[1..]),
+    [byte, rest @ ..] => text.first() == Some(byte) && is_glob_match(rest, &text[1..]),
+  }
+}
+
+/// Config files that are missing (or can't be read) are skipped.
+async fn read_config_file(path: &Path) -> Option<String> {
+  tokio::fs::read_to_string(path).await.ok()
+}
+
+/// Auto-indent.
+impl EditorBuffer {
+  /// Split the current line at the caret (like [EditorBuffer::insert_new_line]), & indent the new
+  /// line like the current one. After an opening bracket, the new line is indented one more level,
+  /// & if the caret was right before the closing bracket, then it is moved to a line of its own.
+  pub fn insert_new_line_indented(&mut self) {
+    let CaretPos { row, col } = self.caret;
+    let line = &self.lines[row];
+    let byte_index = line.byte_index_of_grapheme(col);
+    let indent = get_leading_whitespace(line).to_string();
+    let (before, after) = line.split_at(byte_index);
+    let maybe_closer = INDENT_BRACKETS
+      .iter()
+      .find(|(opener, _)| before.trim_end().ends_with(*opener))
+      .map(|(_, closer)| *closer);
+    let is_closer_after = maybe_closer.is_some_and(|closer| after.trim_start().starts_with(closer));
+    let is_before_blank = before.trim().is_empty();
+
+    self.insert_new_line();
+
+    // The whitespace around the split is replaced by the indentation.
+    let rest = self.lines[row + 1].trim_start().to_string();
+    if is_before_blank {
+      self.lines[row].clear();
+    } else {
+      let trimmed_len = self.lines[row].trim_end().len();
+      self.lines[row].truncate(trimmed_len);
+    }
+    let new_indent = match maybe_closer {
+      Some(_) => format!("{}{}", indent, self.indent_style.get_unit()),
+      None => indent.clone(),
+    };
+    self.caret.col = new_indent.grapheme_count();
+    if is_closer_after {
+      self.lines[row + 1] = new_indent;
+      self.lines.insert(row + 2, format!("{}{}", indent, rest));
+    } else {
+      self.lines[row + 1] = format!("{}{}", new_indent, rest);
+    }
+  }
+}
+
+pub fn get_leading_whitespace(line: &str) -> &str { &line[..line.len() - line.trim_start().len()] }
@@ -0,0 +1,87 @@
+/*
+ *   Copyright (c) 2022 R3BL LLC
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use std::{fmt::{Display, Formatter},
+          str::FromStr};
+
+use serde::Deserialize;
+
+use super::*;
+
+/// Key bindings of the editor, picked in the config file (see [EditorConfig]) or w/
+/// `r3bl-cmdr edit --keys <name>`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyBindings {
+  /// Arrow keys, Ctrl+Z, Ctrl+C, etc (see [EditorComponent]).
+  #[default]
+  Default,
+  /// Modal vi style key bindings, in front of the default ones (see [ViState]).
+  Vi,
+  /// Emacs style key bindings, in front of the default ones (see [EmacsState]).
+  Emacs,
+}
+
+impl FromStr for KeyBindings {
+  type Err = String;
+
+  fn from_str(name: &str) -> Result<Self, Self::Err> {
+    match name {
+      "default" => Ok(KeyBindings::Default),
+      "vi" => Ok(KeyBindings::Vi),
+      "emacs" => Ok(KeyBindings::Emacs),
+      _ => Err(format!(
+        "Unknown key bindings: {}, use one of: default, vi, emacs",
+        name
+      )),
+    }
+  }
+}
+
+impl Display for KeyBindings {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    let name = match self {
+      KeyBindings::Default => "default",
+      KeyBindings::Vi => "vi",
+      KeyBindings::Emacs => "emacs",
+    };
+    write!(f, "{}", name)
+  }
+}
+
+/// What the editor component should do w/ an input event, after a key binding layer (like
+/// [ViState] or [EmacsState]) has seen it.
+#[derive(Clone, Debug)]
+pub enum KeyBindingOutcome {
+  /// The event is consumed, dispatch the actions (as a single [EditorAction::Batch]) & re-render.
+  Dispatch(Vec<EditorAction>),
+  /// Handle the event w/ the default key bindings (eg: typing in vi's insert mode).
+  NotHandled,
+  Run(EditorCommand),
+}
+
+/// Commands that the editor component has to run itself, since they write files or quit (which
+/// the reducer can't do).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EditorCommand {
+  Write,
+  /// Quit, unless there are unsaved changes.
+  Quit,
+  ForceQuit,
+  /// Save the buffer, & quit once it has been saved.
+  WriteQuit,
+}
@@ -0,0 +1,94 @@
+/*
+ *   Copyright (c) 2022 R3BL LLC
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use std::collections::VecDeque;
+
+use super::*;
+
+/// Max number of entries in the [KillRing], the oldest ones are dropped.
+const KILL_RING_MAX_LEN: usize = 60;
+
+/// Emacs style kill ring, shared by all the buffers. Killed (& copied or cut) text is pushed onto
+/// it, `C-y` yanks the newest entry, & `M-y` right after a yank replaces the yanked text w/ the
+/// entry before it (going around the ring).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct KillRing {
+  /// Newest entry at the back.
+  pub entries: VecDeque<String>,
+  /// Consecutive kills (eg: `C-k C-k`) are appended to the same entry.
+  pub is_appending: bool,
+  pub maybe_last_yank: Option<LastYank>,
+}
+
+/// Where the last yank inserted which entry of the [KillRing], so that `M-y` can replace it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LastYank {
+  pub id: String,
+  pub index: usize,
+  pub start: CaretPos,
+  pub end: CaretPos,
+}
+
+impl KillRing {
+  pub fn push(&mut self, text: String) {
+    match self.entries.back_mut() {
+      Some(newest) if self.is_appending => newest.push_str(&text),
+      _ => {
+        self.entries.push_back(text);
+        if self.entries.len() > KILL_RING_MAX_LEN {
+          self.entries.pop_front();
+        }
+      }
+    }
+  }
+
+  /// Yank the newest entry at the caret of `buffer`.
+  pub fn yank(&mut self, id: &str, buffer: &mut EditorBuffer) {
+    let index = match self.entries.len() {
+      0 => return,
+      len => len - 1,
+    };
+    self.insert_entry(id, buffer, index);
+  }
+
+  /// Replace the text that the last yank inserted w/ the entry before it. Returns false if the
+  /// last action (on this buffer) wasn't a yank.
+  pub fn yank_pop(&mut self, id: &str, buffer: &mut EditorBuffer) -> bool {
+    let last_yank = match self.maybe_last_yank.take() {
+      Some(last_yank) if last_yank.id == id => last_yank,
+      _ => return false,
+    };
+    buffer.delete_range(last_yank.start, last_yank.end);
+    let index = match last_yank.index {
+      0 => self.entries.len() - 1,
+      index => index - 1,
+    };
+    self.insert_entry(id, buffer, index);
+    true
+  }
+
+  fn insert_entry(&mut self, id: &str, buffer: &mut EditorBuffer, index: usize) {
+    let start = buffer.caret;
+    buffer.insert_text(&self.entries[index]);
+    self.maybe_last_yank = Some(LastYank {
+      id: id.into(),
+      index,
+      start,
+      end: buffer.caret,
+    });
+  }
+}
@@ -0,0 +1,187 @@
+/*
+ *   Copyright (c) 2022 R3BL LLC
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use std::{io::stdout, sync::Arc};
+
+use crossterm::{cursor, event::*, execute, terminal};
+use r3bl_rs_utils::*;
+use tokio::sync::{Notify, RwLock};
+
+use super::*;
+
+/// Run the editor. Each file in `file_paths` is opened in its own column (from left to right), any
+/// paths beyond the number of columns are ignored. `maybe_key_bindings` overrides the key bindings
+/// from the config file (see [EditorConfig]). The first column is shared w/ other instances if
+/// there is a `maybe_collab_role` (see [CollabRole]).
+pub async fn run_app(
+  file_paths: Vec<String>, maybe_key_bindings: Option<KeyBindings>,
+  maybe_collab_role: Option<CollabRole>,
+) -> CommonResult<()> {
+  throws!({
+    if DEBUG {
+      try_to_set_log_level(log::LevelFilter::Trace)?;
+    } else {
+      try_to_set_log_level(log::LevelFilter::Off)?;
+    }
+
+    // Load the config. Errors are shown in the UI (like errors loading the files).
+    let (config, maybe_config_message) = EditorConfig::load().await;
+    let key_bindings = maybe_key_bindings.unwrap_or(config.key_bindings);
+
+    // Create store.
+    let (mut store, pending_loads) = create_store(file_paths).await;
+    if let Some(message) = maybe_config_message {
+      store
+        .dispatch_action(EditorAction::ShowMessage(message))
+        .await;
+    }
+
+    // Share the first column w/ other instances. Errors are shown in the UI.
+    let mut collab_session = CollabSession::default();
+    if let Some(role) = maybe_collab_role {
+      let action = match CollabSession::connect(&role).await {
+        Ok(session) => {
+          collab_session = session;
+          EditorAction::StartCollab(EDITOR_IDS[0].into(), role)
+        }
+        Err(error) => {
+          let (CollabRole::Host(address) | CollabRole::Guest(address)) = role;
+          EditorAction::ShowMessage(format!("Can't connect to {}: {}", address, error))
+        }
+      };
+      store.dispatch_action(action).await;
+    }
+
+    // Write the unsaved changes to swap files periodically, & if the app panics.
+    let autosaver = Autosaver::default();
+    let autosave_task = autosaver.spawn();
+    set_panic_hook(autosaver.clone());
+
+    // Create an App (renders & responds to user input).
+    let quit_signal = QuitSignal::default();
+    let shared_app = Arc::new(RwLock::new(AppEditor {
+      key_bindings,
+      pending_loads,
+      autosaver: autosaver.clone(),
+      quit_signal: quit_signal.clone(),
+      collab_session,
+      ..Default::default()
+    }));
+
+    // Exit if these keys are pressed.
+    let exit_keys: Vec<KeyEvent> = vec![KeyEvent {
+      code: KeyCode::Char('q'),
+      modifiers: KeyModifiers::CONTROL,
+    }];
+
+    // Create a window. When vi's `:q` (or emacs' `C-x C-c`) quits, the loop is dropped between two
+    // of its events (it is only polled by this task), & the terminal is restored here instead.
+    tokio::select! {
+      result = TerminalWindow::main_event_loop(store, shared_app, exit_keys) => result?,
+      _ = quit_signal.wait() => restore_terminal(),
+    }
+
+    // Exited normally, so the swap files aren't needed anymore.
+    autosave_task.abort();
+    autosaver.remove_swap_files();
+    drop(std::panic::take_hook());
+  });
+}
+
+/// Quits the editor from outside of the main event loop, for vi's `:q` (the loop only exits when
+/// one of its exit keys is pressed). [run_app] waits for it alongside the loop, so the editor exits
+/// the same way (w/ its swap files removed) as w/ the exit key.
+#[derive(Clone, Debug, Default)]
+pub struct QuitSignal {
+  notify: Arc<Notify>,
+}
+
+impl QuitSignal {
+  /// Quit right away if [run_app] is waiting, or as soon as it does.
+  pub fn request(&self) { self.notify.notify_one(); }
+
+  async fn wait(&self) { self.notify.notified().await; }
+}
+
+fn restore_terminal() {
+  let _ = execute!(
+    stdout(),
+    DisableMouseCapture,
+    terminal::LeaveAlternateScreen,
+    cursor::Show
+  );
+  let _ = terminal::disable_raw_mode();
+}
+
+/// A panic anywhere (eg: in the main event loop, or in a spawned task) writes the swap files of the
+/// dirty buffers & restores the terminal before the panic message is printed, then exits.
+fn set_panic_hook(autosaver: Autosaver) {
+  let default_hook = std::panic::take_hook();
+  std::panic::set_hook(Box::new(move |panic_info| {
+    let file_paths = autosaver.write_swap_files();
+    restore_terminal();
+    default_hook(panic_info);
+    if !file_paths.is_empty() {
+      eprintln!(
+        "Unsaved changes were written to swap files, run `r3bl-cmdr edit {}` to recover them",
+        file_paths.join(" ")
+      );
+    }
+    std::process::exit(101);
+  }));
+}
+
+/// The store w/ the loaded files, & the rest of the large files that are loaded lazily (keyed by
+/// buffer id).
+async fn create_store(
+  file_paths: Vec<String>,
+) -> (Store<EditorState, EditorAction>, Vec<(String, PendingLoad)>) {
+  let mut store: Store<EditorState, EditorAction> = Store::default();
+  store.add_reducer(EditorReducer::new()).await;
+  let mut pending_loads = vec![];
+
+  // Load the files. Errors are shown in the UI (they don't prevent the editor from starting).
+  for (id, file_path) in EDITOR_IDS.iter().zip(file_paths.iter()) {
+    let (loaded_file, maybe_message) = match recover_swap_file(file_path) {
+      Some(mut buffer) => {
+        buffer.indent_style = IndentStyle::load(file_path).await;
+        (
+          LoadedFile::Text(Box::new(buffer), None),
+          Some(format!("Recovered {}", file_path)),
+        )
+      }
+      None => load_file(file_path).await,
+    };
+    let action = match loaded_file {
+      LoadedFile::Text(buffer, maybe_pending_load) => {
+        if let Some(pending_load) = maybe_pending_load {
+          pending_loads.push((id.to_string(), pending_load));
+        }
+        EditorAction::SetBuffer(id.to_string(), *buffer)
+      }
+      LoadedFile::Binary(hex_buffer) => EditorAction::SetHexBuffer(id.to_string(), hex_buffer),
+    };
+    store.dispatch_action(action).await;
+    if let Some(message) = maybe_message {
+      store
+        .dispatch_action(EditorAction::ShowMessage(message))
+        .await;
+    }
+  }
+
+  (store, pending_loads)
+}
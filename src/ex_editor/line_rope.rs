@@ -0,0 +1,613 @@
+/*
+ *   Copyright (c) 2022 R3BL LLC
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use std::{fmt::{Debug, Formatter},
+          ops::{Deref, Index, IndexMut, Range},
+          sync::{atomic::{AtomicUsize, Ordering},
+                 Arc}};
+
+/// Number of lines in each chunk of a [LineRope] when it is built. Chunks that grow to twice this
+/// size are split in two, & the ones that shrink to less than half of it are merged w/ the next one
+/// (see [LineRope::merge_small_chunk]).
+const CHUNK_LEN: usize = 1024;
+
+/// [Chunk::char_len] of a chunk whose chars haven't been counted since it was last changed.
+const UNKNOWN_CHAR_LEN: usize = usize::MAX;
+
+/// Lines of an [EditorBuffer](super::EditorBuffer), stored in chunks that are shared by the clones
+/// of the rope (copy on write). Cloning the rope (eg: when the reducer clones the state, or for an
+/// undo snapshot) only copies a pointer per chunk, & an edit only copies the chunk it changes, so
+/// the cost of an edit doesn't depend on the size of the file.
+#[derive(Clone, Default)]
+pub struct LineRope {
+  chunks: Vec<Arc<Chunk>>,
+  /// Row of the first line of each chunk, to find the chunk of a row w/ a binary search.
+  chunk_starts: Vec<usize>,
+  len: usize,
+}
+
+impl LineRope {
+  pub fn len(&self) -> usize { self.len }
+
+  pub fn is_empty(&self) -> bool { self.len == 0 }
+
+  pub fn get(&self, row: usize) -> Option<&String> {
+    if row >= self.len {
+      return None;
+    }
+    let (chunk_index, offset) = self.locate(row);
+    Some(&self.chunks[chunk_index][offset])
+  }
+
+  /// Only the chunk of the line at `row` is copied, if it is shared w/ another rope.
+  pub fn get_mut(&mut self, row: usize) -> Option<&mut String> {
+    if row >= self.len {
+      return None;
+    }
+    let (chunk_index, offset) = self.locate(row);
+    Some(&mut Arc::make_mut(&mut self.chunks[chunk_index]).get_lines_mut()[offset])
+  }
+
+  pub fn push(&mut self, line: String) {
+    match self.chunks.last_mut() {
+      Some(chunk) if chunk.len() < CHUNK_LEN => Arc::make_mut(chunk).get_lines_mut().push(line),
+      _ => {
+        self.chunk_starts.push(self.len);
+        self.chunks.push(Arc::new(Chunk::from(vec![line])));
+      }
+    }
+    self.len += 1;
+  }
+
+  /// Insert `line` at `row`, which can be the number of lines (to push it).
+  pub fn insert(&mut self, row: usize, line: String) {
+    assert!(
+      row <= self.len,
+      "row {} is out of range for {} lines",
+      row,
+      self.len
+    );
+    if row == self.len {
+      self.push(line);
+      return;
+    }
+    let (chunk_index, offset) = self.locate(row);
+    let chunk = Arc::make_mut(&mut self.chunks[chunk_index]).get_lines_mut();
+    chunk.insert(offset, line);
+    if chunk.len() >= CHUNK_LEN * 2 {
+      let rest = chunk.split_off(CHUNK_LEN);
+      self
+        .chunks
+        .insert(chunk_index + 1, Arc::new(Chunk::from(rest)));
+    }
+    self.len += 1;
+    self.update_chunk_starts();
+  }
+
+  pub fn remove(&mut self, row: usize) -> String {
+    assert!(
+      row < self.len,
+      "row {} is out of range for {} lines",
+      row,
+      self.len
+    );
+    let (chunk_index, offset) = self.locate(row);
+    let chunk = Arc::make_mut(&mut self.chunks[chunk_index]).get_lines_mut();
+    let line = chunk.remove(offset);
+    self.merge_small_chunk(chunk_index);
+    self.len -= 1;
+    self.update_chunk_starts();
+    line
+  }
+
+  /// Remove the lines in `range`. Chunks that are entirely inside of it are dropped w/out being
+  /// copied.
+  pub fn remove_range(&mut self, range: Range<usize>) {
+    assert!(
+      range.start <= range.end && range.end <= self.len,
+      "range {:?} is out of range for {} lines",
+      range,
+      self.len
+    );
+    let mut chunks = Vec::with_capacity(self.chunks.len());
+    let mut cut_chunk_indices = vec![];
+    for (mut chunk, start) in self.chunks.drain(..).zip(self.chunk_starts.iter().copied()) {
+      let end = start + chunk.len();
+      if end <= range.start || start >= range.end {
+        chunks.push(chunk);
+      } else if start < range.start || end > range.end {
+        let local_range = range.start.max(start) - start..range.end.min(end) - start;
+        Arc::make_mut(&mut chunk).get_lines_mut().drain(local_range);
+        cut_chunk_indices.push(chunks.len());
+        chunks.push(chunk);
+      }
+    }
+    self.chunks = chunks;
+    // From the last one, so that merging it doesn't move the other one.
+    for chunk_index in cut_chunk_indices.into_iter().rev() {
+      self.merge_small_chunk(chunk_index);
+    }
+    self.len -= range.len();
+    self.update_chunk_starts();
+  }
+
+  pub fn iter(&self) -> impl DoubleEndedIterator<Item = &String> + '_ {
+    self.chunks.iter().flat_map(|chunk| chunk.iter())
+  }
+
+  /// Lines in `range` (clamped to the number of lines), w/out going through the ones before it.
+  pub fn iter_range(&self, range: Range<usize>) -> impl Iterator<Item = &String> + '_ {
+    let (chunk_index, offset) = match range.start < self.len {
+      true => self.locate(range.start),
+      false => (self.chunks.len(), 0),
+    };
+    self.chunks[chunk_index..]
+      .iter()
+      .flat_map(|chunk| chunk.iter())
+      .skip(offset)
+      .take(range.end.min(self.len).saturating_sub(range.start))
+  }
+
+  pub fn join(&self, separator: &str) -> String {
+    let mut joined = String::new();
+    for (row, line) in self.iter().enumerate() {
+      if row > 0 {
+        joined.push_str(separator);
+      }
+      joined.push_str(line);
+    }
+    joined
+  }
+
+  /// Number of chars in the lines joined w/ new lines. The chars of the chunks that didn't change
+  /// since they were last counted aren't counted again.
+  pub fn get_char_len(&self) -> usize {
+    let len: usize = self
+      .chunks
+      .iter()
+      .map(|it| it.get_char_len() + it.len())
+      .sum();
+    len.saturating_sub(1)
+  }
+
+  /// Char offset of the start of the line at `row` (clamped to the number of lines) in the lines
+  /// joined w/ new lines. Only the lines before it in its chunk are counted (see
+  /// [LineRope::get_char_len]).
+  pub fn get_char_offset(&self, row: usize) -> usize {
+    let row = row.min(self.len);
+    let mut offset = 0;
+    for (chunk, start) in self.chunks.iter().zip(self.chunk_starts.iter().copied()) {
+      if start + chunk.len() <= row {
+        offset += chunk.get_char_len() + chunk.len();
+        continue;
+      }
+      offset += chunk[..row - start]
+        .iter()
+        .map(|it| it.chars().count() + 1)
+        .sum::<usize>();
+      break;
+    }
+    offset
+  }
+
+  /// Row of the line that the char at `offset` (in the lines joined w/ new lines) is in, & the
+  /// index of the char in that line. Offsets past the end are clamped to the end of the last line.
+  pub fn locate_char_offset(&self, mut offset: usize) -> (usize, usize) {
+    let last_chunk_index = self.chunks.len().saturating_sub(1);
+    for (chunk_index, chunk) in self.chunks.iter().enumerate() {
+      let chunk_char_len = chunk.get_char_len() + chunk.len();
+      if offset >= chunk_char_len && chunk_index < last_chunk_index {
+        offset -= chunk_char_len;
+        continue;
+      }
+      let start = self.chunk_starts[chunk_index];
+      for (index, line) in chunk.iter().enumerate() {
+        let line_char_len = line.chars().count();
+        if offset <= line_char_len || start + index + 1 == self.len {
+          return (start + index, offset.min(line_char_len));
+        }
+        offset -= line_char_len + 1;
+      }
+    }
+    (0, 0)
+  }
+
+  /// The rows of the lines that differ between `old` & `new`, as `(start, old_end, new_end)` (the
+  /// lines before `start` & after `old_end` in `old` are the same as the ones before `start` &
+  /// after `new_end` in `new`), `None` if they have the same lines. Chunks that are shared by both
+  /// ropes (eg: the ones that an edit didn't change) are skipped w/out comparing their lines.
+  pub fn get_changed_rows(old: &LineRope, new: &LineRope) -> Option<(usize, usize, usize)> {
+    let is_shared = |(lhs, rhs): &(&Arc<Chunk>, &Arc<Chunk>)| Arc::ptr_eq(lhs, rhs);
+    let min_len = old.len.min(new.len);
+
+    let shared_prefix_chunk_count = old
+      .chunks
+      .iter()
+      .zip(&new.chunks)
+      .take_while(is_shared)
+      .count();
+    let mut prefix_len: usize = old.chunks[..shared_prefix_chunk_count]
+      .iter()
+      .map(|it| it.len())
+      .sum();
+    prefix_len += old.chunks[shared_prefix_chunk_count..]
+      .iter()
+      .flat_map(|it| it.iter())
+      .zip(
+        new.chunks[shared_prefix_chunk_count..]
+          .iter()
+          .flat_map(|it| it.iter()),
+      )
+      .take_while(|(lhs, rhs)| lhs == rhs)
+      .count();
+    if prefix_len == old.len && prefix_len == new.len {
+      return None;
+    }
+
+    let max_suffix_len = min_len - prefix_len;
+    let mut suffix_len = 0;
+    let mut shared_suffix_chunk_count = 0;
+    for (chunk, _) in old
+      .chunks
+      .iter()
+      .rev()
+      .zip(new.chunks.iter().rev())
+      .take_while(is_shared)
+    {
+      if suffix_len + chunk.len() > max_suffix_len {
+        break;
+      }
+      suffix_len += chunk.len();
+      shared_suffix_chunk_count += 1;
+    }
+    suffix_len += old
+      .iter_rev_before_chunks(shared_suffix_chunk_count)
+      .zip(new.iter_rev_before_chunks(shared_suffix_chunk_count))
+      .take(max_suffix_len - suffix_len)
+      .take_while(|(lhs, rhs)| lhs == rhs)
+      .count();
+
+    Some((prefix_len, old.len - suffix_len, new.len - suffix_len))
+  }
+
+  /// Lines from the end to the start, w/out the last `chunk_count` chunks.
+  fn iter_rev_before_chunks(&self, chunk_count: usize) -> impl Iterator<Item = &String> + '_ {
+    self.chunks[..self.chunks.len() - chunk_count]
+      .iter()
+      .rev()
+      .flat_map(|it| it.iter().rev())
+  }
+
+  /// Drop the chunk at `chunk_index` if it is empty, or merge it w/ the chunk after it (or before
+  /// it, for the last one) if it has less than half of [CHUNK_LEN] lines, so that removing lines
+  /// doesn't leave lots of small chunks behind. The chunk starts must be updated after this.
+  fn merge_small_chunk(&mut self, chunk_index: usize) {
+    let chunk_len = self.chunks[chunk_index].len();
+    if chunk_len == 0 {
+      self.chunks.remove(chunk_index);
+      return;
+    }
+    if chunk_len >= CHUNK_LEN / 2 || self.chunks.len() < 2 {
+      return;
+    }
+    let first_index = match chunk_index + 1 < self.chunks.len() {
+      true => chunk_index,
+      false => chunk_index - 1,
+    };
+    if self.chunks[first_index].len() + self.chunks[first_index + 1].len() >= CHUNK_LEN * 2 {
+      return;
+    }
+    let next_chunk = self.chunks.remove(first_index + 1);
+    Arc::make_mut(&mut self.chunks[first_index])
+      .get_lines_mut()
+      .extend(next_chunk.iter().cloned());
+  }
+
+  /// Index of the chunk that holds the line at `row` (which must be in range), & the index of the
+  /// line in that chunk.
+  fn locate(&self, row: usize) -> (usize, usize) {
+    let chunk_index = self.chunk_starts.partition_point(|start| *start <= row) - 1;
+    (chunk_index, row - self.chunk_starts[chunk_index])
+  }
+
+  fn update_chunk_starts(&mut self) {
+    self.chunk_starts.clear();
+    let mut start = 0;
+    for chunk in &self.chunks {
+      self.chunk_starts.push(start);
+      start += chunk.len();
+    }
+  }
+}
+
+/// Lines of a [LineRope], w/ their number of chars (w/out new lines) once they were counted.
+#[derive(Default)]
+struct Chunk {
+  lines: Vec<String>,
+  char_len: AtomicUsize,
+}
+
+impl Chunk {
+  /// Changing the lines clears the number of chars.
+  fn get_lines_mut(&mut self) -> &mut Vec<String> {
+    *self.char_len.get_mut() = UNKNOWN_CHAR_LEN;
+    &mut self.lines
+  }
+
+  fn get_char_len(&self) -> usize {
+    match self.char_len.load(Ordering::Relaxed) {
+      UNKNOWN_CHAR_LEN => {
+        let char_len = self.lines.iter().map(|it| it.chars().count()).sum();
+        self.char_len.store(char_len, Ordering::Relaxed);
+        char_len
+      }
+      char_len => char_len,
+    }
+  }
+}
+
+impl From<Vec<String>> for Chunk {
+  fn from(lines: Vec<String>) -> Self {
+    Self {
+      lines,
+      char_len: AtomicUsize::new(UNKNOWN_CHAR_LEN),
+    }
+  }
+}
+
+impl Clone for Chunk {
+  fn clone(&self) -> Self {
+    Self {
+      lines: self.lines.clone(),
+      char_len: AtomicUsize::new(self.char_len.load(Ordering::Relaxed)),
+    }
+  }
+}
+
+impl Deref for Chunk {
+  type Target = Vec<String>;
+
+  fn deref(&self) -> &Vec<String> { &self.lines }
+}
+
+impl From<Vec<String>> for LineRope {
+  fn from(lines: Vec<String>) -> Self { lines.into_iter().collect() }
+}
+
+impl FromIterator<String> for LineRope {
+  fn from_iter<T: IntoIterator<Item = String>>(iter: T) -> Self {
+    let mut rope = LineRope::default();
+    rope.extend(iter);
+    rope
+  }
+}
+
+impl Extend<String> for LineRope {
+  fn extend<T: IntoIterator<Item = String>>(&mut self, iter: T) {
+    for line in iter {
+      self.push(line);
+    }
+  }
+}
+
+impl Index<usize> for LineRope {
+  type Output = String;
+
+  fn index(&self, row: usize) -> &String {
+    match self.get(row) {
+      Some(line) => line,
+      None => panic!("row {} is out of range for {} lines", row, self.len),
+    }
+  }
+}
+
+impl IndexMut<usize> for LineRope {
+  fn index_mut(&mut self, row: usize) -> &mut String {
+    let len = self.len;
+    match self.get_mut(row) {
+      Some(line) => line,
+      None => panic!("row {} is out of range for {} lines", row, len),
+    }
+  }
+}
+
+/// Ropes that share all their chunks are equal w/out comparing the lines.
+impl PartialEq for LineRope {
+  fn eq(&self, other: &Self) -> bool {
+    let is_shared = self.chunks.len() == other.chunks.len()
+      && self
+        .chunks
+        .iter()
+        .zip(&other.chunks)
+        .all(|(lhs, rhs)| Arc::ptr_eq(lhs, rhs));
+    self.len == other.len && (is_shared || self.iter().eq(other.iter()))
+  }
+}
+
+impl Eq for LineRope {}
+
+impl Debug for LineRope {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    f.debug_list().entries(self.iter()).finish()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn make_lines(range: Range<usize>) -> Vec<String> {
+    range.map(|it| format!("line {}", it)).collect()
+  }
+
+  fn get_chunk_lens(rope: &LineRope) -> Vec<usize> {
+    rope.chunks.iter().map(|it| it.len()).collect()
+  }
+
+  /// `rope` has the same lines as `lines`, & its chunk starts are up to date.
+  fn assert_lines(rope: &LineRope, lines: &[String]) {
+    assert_eq!(rope.len(), lines.len());
+    assert!(rope.iter().eq(lines.iter()));
+    for (row, line) in lines.iter().enumerate() {
+      assert_eq!(&rope[row], line);
+    }
+    let mut start = 0;
+    for (chunk, chunk_start) in rope.chunks.iter().zip(&rope.chunk_starts) {
+      assert_eq!(*chunk_start, start);
+      start += chunk.len();
+    }
+    assert_eq!(rope.chunk_starts.len(), rope.chunks.len());
+  }
+
+  #[test]
+  fn test_build_in_chunks() {
+    let lines = make_lines(0..CHUNK_LEN * 2 + 1);
+    let rope = LineRope::from(lines.clone());
+    assert_eq!(get_chunk_lens(&rope), vec![CHUNK_LEN, CHUNK_LEN, 1]);
+    assert_lines(&rope, &lines);
+  }
+
+  #[test]
+  fn test_insert_at_chunk_boundary() {
+    let mut lines = make_lines(0..CHUNK_LEN * 2);
+    let mut rope = LineRope::from(lines.clone());
+    let row_count = lines.len() + 3;
+    for row in [CHUNK_LEN, CHUNK_LEN - 1, 0, row_count] {
+      rope.insert(row, "new".into());
+      lines.insert(row, "new".into());
+      assert_lines(&rope, &lines);
+    }
+    assert_eq!(get_chunk_lens(&rope), vec![CHUNK_LEN + 2, CHUNK_LEN + 1, 1]);
+  }
+
+  #[test]
+  fn test_insert_splits_full_chunk() {
+    let mut lines = make_lines(0..CHUNK_LEN * 2);
+    let mut rope = LineRope::from(lines.clone());
+    for _ in 0..CHUNK_LEN {
+      rope.insert(1, "new".into());
+      lines.insert(1, "new".into());
+    }
+    assert_eq!(get_chunk_lens(&rope), vec![CHUNK_LEN, CHUNK_LEN, CHUNK_LEN]);
+    assert_lines(&rope, &lines);
+  }
+
+  #[test]
+  fn test_remove_at_chunk_boundary() {
+    let mut lines = make_lines(0..CHUNK_LEN * 2);
+    let mut rope = LineRope::from(lines.clone());
+    let last_row = lines.len() - 4;
+    for row in [CHUNK_LEN, CHUNK_LEN - 1, 0, last_row] {
+      assert_eq!(rope.remove(row), lines.remove(row));
+      assert_lines(&rope, &lines);
+    }
+  }
+
+  #[test]
+  fn test_remove_range_across_chunks() {
+    let mut lines = make_lines(0..CHUNK_LEN * 3);
+    let mut rope = LineRope::from(lines.clone());
+    let range = CHUNK_LEN - 10..CHUNK_LEN * 2 + 10;
+    rope.remove_range(range.clone());
+    lines.drain(range);
+    assert_eq!(get_chunk_lens(&rope), vec![CHUNK_LEN - 10, CHUNK_LEN - 10]);
+    assert_lines(&rope, &lines);
+
+    rope.remove_range(0..rope.len());
+    assert!(rope.is_empty());
+    assert!(rope.chunks.is_empty());
+  }
+
+  #[test]
+  fn test_remove_merges_small_chunk() {
+    let mut lines = make_lines(0..CHUNK_LEN * 2);
+    let mut rope = LineRope::from(lines.clone());
+    let removed_count = CHUNK_LEN / 2 + 1;
+    for _ in 0..removed_count {
+      rope.remove(0);
+      lines.remove(0);
+    }
+    assert_eq!(get_chunk_lens(&rope), vec![CHUNK_LEN * 2 - removed_count]);
+    assert_lines(&rope, &lines);
+  }
+
+  #[test]
+  fn test_remove_range_merges_small_chunks() {
+    let mut lines = make_lines(0..CHUNK_LEN * 4);
+    let mut rope = LineRope::from(lines.clone());
+    // Leaves 10 lines of the 2nd chunk & 10 lines of the 3rd one, the 3rd one is merged into the
+    // 4th, & then the 2nd one into that.
+    let range = CHUNK_LEN + 10..CHUNK_LEN * 3 - 10;
+    rope.remove_range(range.clone());
+    lines.drain(range);
+    assert_eq!(get_chunk_lens(&rope), vec![CHUNK_LEN, CHUNK_LEN + 20]);
+    assert_lines(&rope, &lines);
+  }
+
+  #[test]
+  fn test_last_small_chunk_is_merged_into_the_one_before() {
+    let mut lines = make_lines(0..CHUNK_LEN + 10);
+    let mut rope = LineRope::from(lines.clone());
+    rope.remove(CHUNK_LEN);
+    lines.remove(CHUNK_LEN);
+    assert_eq!(get_chunk_lens(&rope), vec![CHUNK_LEN + 9]);
+    assert_lines(&rope, &lines);
+  }
+
+  #[test]
+  fn test_clone_doesnt_see_later_edits() {
+    let lines = make_lines(0..CHUNK_LEN * 3);
+    let mut rope = LineRope::from(lines.clone());
+    let clone = rope.clone();
+    rope[0].push('!');
+    rope.insert(CHUNK_LEN, "new".into());
+    rope.remove(CHUNK_LEN * 2);
+    rope.remove_range(10..20);
+    assert_lines(&clone, &lines);
+    assert_ne!(rope, clone);
+    // Only the chunks that were edited are copied.
+    assert!(!Arc::ptr_eq(&rope.chunks[0], &clone.chunks[0]));
+    assert!(Arc::ptr_eq(&rope.chunks[2], &clone.chunks[2]));
+  }
+
+  #[test]
+  fn test_get_changed_rows() {
+    let old = LineRope::from(make_lines(0..CHUNK_LEN * 3));
+    let mut new = old.clone();
+    assert_eq!(LineRope::get_changed_rows(&old, &new), None);
+    new[CHUNK_LEN + 5].push('!');
+    new.insert(CHUNK_LEN + 6, "new".into());
+    assert_eq!(
+      LineRope::get_changed_rows(&old, &new),
+      Some((CHUNK_LEN + 5, CHUNK_LEN + 6, CHUNK_LEN + 7))
+    );
+  }
+
+  #[test]
+  fn test_char_offsets_across_chunks() {
+    let lines: Vec<String> = (0..CHUNK_LEN + 2).map(|_| "é".to_string()).collect();
+    let rope = LineRope::from(lines);
+    // Each line is a char & a new line.
+    assert_eq!(rope.get_char_len(), (CHUNK_LEN + 2) * 2 - 1);
+    assert_eq!(rope.get_char_offset(CHUNK_LEN + 1), (CHUNK_LEN + 1) * 2);
+    assert_eq!(
+      rope.locate_char_offset((CHUNK_LEN + 1) * 2 + 1),
+      (CHUNK_LEN + 1, 1)
+    );
+    assert_eq!(rope.locate_char_offset(usize::MAX), (CHUNK_LEN + 1, 1));
+  }
+}
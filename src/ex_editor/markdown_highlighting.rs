@@ -0,0 +1,247 @@
+/*
+ *   Copyright (c) 2022 R3BL LLC
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use std::path::Path;
+
+use regex::Regex;
+use syntect::{highlighting::{HighlightState, Highlighter, Theme},
+              parsing::{ParseState, Scope, ScopeStack}};
+
+use super::*;
+
+/// Markdown files are highlighted line by line w/ these rules, instead of w/ a syntect syntax, so
+/// that fenced code blocks can be highlighted in the language that is named on the opening fence.
+/// Block level syntax (code fences, headings, block quotes, list items & checkboxes) is matched
+/// at the start of a line, & inline syntax (code, emphasis, links) in the rest of it.
+#[derive(Debug)]
+pub struct MarkdownRules {
+  pub code_fence: Regex,
+  pub heading: Regex,
+  pub block_quote: Regex,
+  pub list_item: Regex,
+  pub checkbox: Regex,
+  pub inline: Regex,
+  pub styles: MarkdownStyles,
+}
+
+/// Styles for Markdown syntax, they are looked up in the theme by scope (mostly the ones that
+/// Markdown grammars use), so that they match the other syntaxes.
+#[derive(Clone, Copy, Debug)]
+pub struct MarkdownStyles {
+  pub text: SpanStyle,
+  pub heading: SpanStyle,
+  pub bold: SpanStyle,
+  pub italic: SpanStyle,
+  pub code: SpanStyle,
+  pub link: SpanStyle,
+  pub block_quote: SpanStyle,
+  pub list_marker: SpanStyle,
+  pub checkbox_checked: SpanStyle,
+  pub checkbox_unchecked: SpanStyle,
+  pub code_fence: SpanStyle,
+  pub code_fence_language: SpanStyle,
+}
+
+/// Parser state at the end of a line of a Markdown file. When the line is inside of a fenced code
+/// block, this holds the fence & the state of the syntax for the code (if the language is known).
+#[derive(Clone, Debug, Default)]
+pub struct MarkdownState {
+  pub code_fence: Option<CodeFence>,
+}
+
+#[derive(Clone, Debug)]
+pub struct CodeFence {
+  /// The backticks or tildes that opened the block, it is closed by a line w/ at least as many.
+  pub marker: String,
+  pub maybe_code_state: Option<(ParseState, HighlightState)>,
+}
+
+impl CodeFence {
+  fn is_closed_by(&self, line: &str) -> bool {
+    let line = line.trim();
+    let marker_char = self.marker.chars().next().unwrap_or('`');
+    line.len() >= self.marker.len() && line.chars().all(|it| it == marker_char)
+  }
+}
+
+impl MarkdownRules {
+  pub fn new(theme: &Theme) -> Self {
+    let highlighter = Highlighter::new(theme);
+    let style_for_scope = |scope: &str| -> SpanStyle {
+      let scopes: Vec<Scope> = Scope::new(scope).into_iter().collect();
+      highlighter.style_for_stack(&scopes).into()
+    };
+    let heading = SpanStyle {
+      bold: true,
+      ..style_for_scope("entity.name.section")
+    };
+    let bold = SpanStyle {
+      bold: true,
+      ..style_for_scope("markup.bold")
+    };
+    let link = SpanStyle {
+      underline: true,
+      ..style_for_scope("string.other.link")
+    };
+
+    Self {
+      code_fence: Regex::new(r"^ {0,3}(`{3,}|~{3,})\s*([^`\s]*)").unwrap(),
+      heading: Regex::new(r"^ {0,3}#{1,6}(\s|$)").unwrap(),
+      block_quote: Regex::new(r"^ {0,3}>\s?").unwrap(),
+      list_item: Regex::new(r"^\s*([-*+]|\d{1,9}[.)])(\s+|$)").unwrap(),
+      checkbox: Regex::new(r"^\[[ xX]\](\s|$)").unwrap(),
+      inline: Regex::new(concat!(
+        r"(?P<code>`[^`]+`)",
+        r"|(?P<bold>\*\*[^*]+\*\*|__[^_]+__)",
+        r"|(?P<italic>\*[^*\s][^*]*\*|\b_[^_\s][^_]*_\b)",
+        r"|(?P<link>!?\[[^\]]*\]\([^)]*\)|<https?://[^>\s]+>)",
+      ))
+      .unwrap(),
+      styles: MarkdownStyles {
+        text: style_for_scope("text"),
+        heading,
+        bold,
+        italic: style_for_scope("markup.italic"),
+        code: style_for_scope("markup.raw.inline"),
+        link,
+        block_quote: style_for_scope("markup.quote"),
+        list_marker: style_for_scope("markup.list"),
+        checkbox_checked: style_for_scope("markup.inserted"),
+        checkbox_unchecked: style_for_scope("keyword"),
+        code_fence: style_for_scope("comment"),
+        code_fence_language: style_for_scope("constant.other.language-name"),
+      },
+    }
+  }
+}
+
+/// Markdown files are the ones w/ a `.md` or `.markdown` extension.
+pub fn is_markdown_file(file_path: Option<&str>) -> bool {
+  let maybe_extension = file_path
+    .and_then(|it| Path::new(it).extension())
+    .and_then(|it| it.to_str());
+  matches!(maybe_extension, Some("md" | "markdown"))
+}
+
+impl SyntaxHighlighter {
+  /// Styled spans for a line of a Markdown file, `state` is the parser state at the end of the
+  /// previous line & it is updated for the next one.
+  pub fn highlight_markdown_line(
+    &self, line: &str, state: &mut MarkdownState, highlighter: &Highlighter,
+  ) -> Vec<StyledSpan> {
+    let rules = &self.markdown_rules;
+    let styles = &rules.styles;
+
+    // Inside of a fenced code block.
+    if let Some(code_fence) = &mut state.code_fence {
+      if code_fence.is_closed_by(line) {
+        state.code_fence = None;
+        return to_spans(&[(line, styles.code_fence)]);
+      }
+      return match &mut code_fence.maybe_code_state {
+        Some((parse_state, highlight_state)) => {
+          self.highlight_line(line, parse_state, highlight_state, highlighter)
+        }
+        None => to_spans(&[(line, styles.code)]),
+      };
+    }
+
+    // Opening fence of a code block, w/ an optional language.
+    if let Some(captures) = rules.code_fence.captures(line) {
+      let language = captures.get(2).map(|it| it.as_str()).unwrap_or_default();
+      let maybe_code_state = self.find_syntax_by_token(language).map(|syntax| {
+        (
+          ParseState::new(syntax),
+          HighlightState::new(highlighter, ScopeStack::new()),
+        )
+      });
+      state.code_fence = Some(CodeFence {
+        marker: captures[1].to_string(),
+        maybe_code_state,
+      });
+      let (start, end) = captures
+        .get(2)
+        .map(|it| (it.start(), it.end()))
+        .unwrap_or((line.len(), line.len()));
+      return to_spans(&[
+        (&line[..start], styles.code_fence),
+        (&line[start..end], styles.code_fence_language),
+        (&line[end..], styles.code_fence),
+      ]);
+    }
+
+    if rules.heading.is_match(line) {
+      return to_spans(&[(line, styles.heading)]);
+    }
+
+    // Block quote & list item markers (& checkboxes) at the start of the line.
+    let mut parts: Vec<(&str, SpanStyle)> = vec![];
+    let mut rest = line;
+    if let Some(found) = rules.block_quote.find(rest) {
+      parts.push((found.as_str(), styles.block_quote));
+      rest = &rest[found.end()..];
+    }
+    if let Some(found) = rules.list_item.find(rest) {
+      parts.push((found.as_str(), styles.list_marker));
+      rest = &rest[found.end()..];
+      if let Some(found) = rules.checkbox.find(rest) {
+        let style = match found.as_str().starts_with("[ ]") {
+          true => styles.checkbox_unchecked,
+          false => styles.checkbox_checked,
+        };
+        parts.push((found.as_str(), style));
+        rest = &rest[found.end()..];
+      }
+    }
+
+    // Inline syntax in the rest of the line.
+    let mut text_start = 0;
+    for captures in rules.inline.captures_iter(rest) {
+      let found = match captures.get(0) {
+        Some(it) => it,
+        None => continue,
+      };
+      let style = if captures.name("code").is_some() {
+        styles.code
+      } else if captures.name("bold").is_some() {
+        styles.bold
+      } else if captures.name("italic").is_some() {
+        styles.italic
+      } else {
+        styles.link
+      };
+      parts.push((&rest[text_start..found.start()], styles.text));
+      parts.push((found.as_str(), style));
+      text_start = found.end();
+    }
+    parts.push((&rest[text_start..], styles.text));
+
+    to_spans(&parts)
+  }
+}
+
+/// Empty parts are dropped, so a line that is empty has no spans (like w/ the other syntaxes).
+fn to_spans(parts: &[(&str, SpanStyle)]) -> Vec<StyledSpan> {
+  parts
+    .iter()
+    .filter(|(text, _)| !text.is_empty())
+    .map(|(text, style)| StyledSpan {
+      text: text.to_string(),
+      style: *style,
+    })
+    .collect()
+}
@@ -14,3 +14,75 @@
  *   See the License for the specific language governing permissions and
  *   limitations under the License.
  */
+
+// Attach sources.
+pub mod action;
+pub mod app_editor;
+pub mod brackets;
+pub mod carets;
+pub mod clipboard;
+pub mod collab;
+pub mod config;
+pub mod debug;
+pub mod editor_buffer;
+pub mod editor_component;
+pub mod editor_line;
+pub mod emacs_mode;
+pub mod file_io;
+pub mod folding;
+pub mod gutter;
+pub mod hex_buffer;
+pub mod hex_view;
+pub mod history;
+pub mod indentation;
+pub mod key_bindings;
+pub mod kill_ring;
+pub mod launcher;
+pub mod line_rope;
+pub mod markdown_highlighting;
+pub mod outline;
+pub mod reducer;
+pub mod search;
+pub mod state;
+pub mod status_line;
+pub mod swap_file;
+pub mod syntax_highlighting;
+pub mod text_operation;
+pub mod vi_mode;
+pub mod viewport;
+pub mod word_motions;
+
+// Re-export.
+pub use action::*;
+pub use app_editor::*;
+pub use carets::*;
+pub use clipboard::*;
+pub use collab::*;
+pub use config::*;
+pub use debug::*;
+pub use editor_buffer::*;
+pub use editor_component::*;
+pub use editor_line::*;
+pub use emacs_mode::*;
+pub use file_io::*;
+pub use folding::*;
+pub use gutter::*;
+pub use hex_buffer::*;
+pub use hex_view::*;
+pub use history::*;
+pub use indentation::*;
+pub use key_bindings::*;
+pub use kill_ring::*;
+pub use launcher::*;
+pub use line_rope::*;
+pub use markdown_highlighting::*;
+pub use outline::*;
+pub use reducer::*;
+pub use search::*;
+pub use state::*;
+pub use status_line::*;
+pub use swap_file::*;
+pub use syntax_highlighting::*;
+pub use text_operation::*;
+pub use vi_mode::*;
+pub use viewport::*;
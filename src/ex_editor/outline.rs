@@ -0,0 +1,148 @@
+/*
+ *   Copyright (c) 2022 R3BL LLC
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+use async_trait::async_trait;
+use crossterm::event::*;
+use r3bl_rs_utils::*;
+
+use super::*;
+
+/// Side panel w/ the outline (see [EditorBuffer::get_outline]) of the buffer for `editor_id`.
+/// `items` are refreshed on render when the buffer changes. The panel only moves the selection,
+/// [AppEditor] jumps to the selected item (since that moves the focus back to the editor).
+#[derive(Clone, Debug, Default)]
+pub struct OutlineComponent {
+  pub editor_id: String,
+  pub items: Vec<OutlineItem>,
+  /// Change count & line count of the buffer that `items` were made from.
+  pub items_version: Option<(u64, usize)>,
+  pub selected: usize,
+  pub first_row: usize,
+  /// Height of the panel from the last render, used for PageUp & PageDown.
+  pub page_height: usize,
+}
+
+impl OutlineComponent {
+  /// Show the outline of the buffer for `editor_id`, w/ the item that contains the caret
+  /// selected.
+  pub fn open(&mut self, editor_id: &str, buffer: &EditorBuffer) {
+    self.editor_id = editor_id.into();
+    self.items_version = None;
+    self.refresh_items(buffer);
+    self.selected = self
+      .items
+      .iter()
+      .rposition(|it| it.row <= buffer.caret.row)
+      .unwrap_or(0);
+  }
+
+  pub fn get_selected_item(&self) -> Option<&OutlineItem> { self.items.get(self.selected) }
+
+  fn refresh_items(&mut self, buffer: &EditorBuffer) {
+    let version = (buffer.change_count, buffer.line_count());
+    if self.items_version != Some(version) {
+      self.items = buffer.get_outline();
+      self.items_version = Some(version);
+      self.selected = self.selected.min(self.items.len().saturating_sub(1));
+    }
+  }
+}
+
+#[async_trait]
+impl Component<EditorState, EditorAction> for OutlineComponent {
+  /// Handle following input events (and consume them):
+  /// - Up, Down              : select the previous / next item
+  /// - PageUp, PageDown      : select the item a page up / down
+  /// - Home, End             : select the first / last item
+  async fn handle_event(
+    &mut self, input_event: &TWInputEvent, _state: &EditorState,
+    _shared_store: &SharedStore<EditorState, EditorAction>,
+  ) -> CommonResult<EventPropagation> {
+    throws_with_return!({
+      let last_index = self.items.len().saturating_sub(1);
+      let page_height = self.page_height.max(1);
+      let selected = match input_event {
+        TWInputEvent::NonDisplayableKeypress(KeyEvent {
+          code,
+          modifiers: KeyModifiers::NONE,
+        }) => match code {
+          KeyCode::Up => self.selected.saturating_sub(1),
+          KeyCode::Down => (self.selected + 1).min(last_index),
+          KeyCode::PageUp => self.selected.saturating_sub(page_height),
+          KeyCode::PageDown => (self.selected + page_height).min(last_index),
+          KeyCode::Home => 0,
+          KeyCode::End => last_index,
+          _ => return Ok(EventPropagation::Propagate),
+        },
+        _ => return Ok(EventPropagation::Propagate),
+      };
+      self.selected = selected;
+      EventPropagation::ConsumedRerender
+    });
+  }
+
+  async fn render(
+    &mut self, has_focus: &HasFocus, current_box: &TWBox, state: &EditorState,
+    _shared_store: &SharedStore<EditorState, EditorAction>,
+  ) -> CommonResult<TWCommandQueue> {
+    throws_with_return!({
+      let default_buffer = EditorBuffer::default();
+      let buffer = state.get_buffer(&self.editor_id).unwrap_or(&default_buffer);
+      self.refresh_items(buffer);
+
+      let viewport_rows = current_box.bounding_size.rows as usize;
+      let viewport_cols = current_box.bounding_size.cols as usize;
+      self.page_height = viewport_rows;
+      if self.selected < self.first_row {
+        self.first_row = self.selected;
+      } else if viewport_rows > 0 && self.selected >= self.first_row + viewport_rows {
+        self.first_row = self.selected + 1 - viewport_rows;
+      }
+
+      let mut queue: TWCommandQueue = tw_command_queue!();
+      for viewport_row in 0..viewport_rows {
+        let index = self.first_row + viewport_row;
+        let text = match self.items.get(index) {
+          Some(item) => format!(" {}{}", "  ".repeat(item.level), item.text),
+          None if index == 0 => " No outline for this file".to_string(),
+          None => String::new(),
+        };
+        let mut text = text.clip_to_display_cols(0, viewport_cols);
+        let padding = viewport_cols.saturating_sub(text.display_width());
+        text.push_str(&" ".repeat(padding));
+
+        let mut maybe_style = current_box.get_computed_style();
+        if let Some(style) = maybe_style.as_mut() {
+          let is_selected = index == self.selected && !self.items.is_empty();
+          style.reverse = is_selected && has_focus.does_current_box_have_focus(current_box);
+          style.bold = is_selected;
+        }
+        tw_command_queue! {
+          queue push
+          TWCommand::MoveCursorPositionRelTo(
+            current_box.origin_pos,
+            Position { col: 0, row: viewport_row as UnitType }
+          ),
+          TWCommand::ApplyColors(maybe_style.clone()),
+          TWCommand::PrintWithAttributes(text, maybe_style),
+          TWCommand::ResetColor
+        };
+      }
+
+      queue
+    });
+  }
+}
@@ -0,0 +1,624 @@
+/*
+ *   Copyright (c) 2022 R3BL LLC
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use r3bl_rs_utils::redux::AsyncReducer;
+use similar::{capture_diff_slices, Algorithm};
+
+use super::*;
+
+/// Reducer.
+#[derive(Default)]
+pub struct EditorReducer;
+
+#[async_trait]
+impl AsyncReducer<EditorState, EditorAction> for EditorReducer {
+  async fn run(&self, action: &EditorAction, state: &EditorState) -> EditorState {
+    let mut new_state = state.clone();
+    reduce_mut(&mut new_state, action);
+    update_folds(state, &mut new_state, action);
+    update_collab(state, &mut new_state, action);
+    new_state
+  }
+}
+
+fn reduce_mut(state: &mut EditorState, action: &EditorAction) {
+  // Buffers that are still loading can't be edited.
+  if let Some(buffer) = get_edited_buffer_id(state, action).and_then(|id| state.get_buffer(id)) {
+    if buffer.is_loading {
+      state.message = Some(get_loading_message(buffer));
+      return;
+    }
+  }
+
+  // Only consecutive kills are appended to the same entry of the kill ring, & `M-y` only works
+  // right after a yank (the actions in a batch are checked one by one).
+  if !matches!(action, EditorAction::Kill(_) | EditorAction::Batch(_)) {
+    state.kill_ring.is_appending = false;
+  }
+  if !matches!(
+    action,
+    EditorAction::Yank(_) | EditorAction::YankPop(_) | EditorAction::Batch(_)
+  ) {
+    state.kill_ring.maybe_last_yank = None;
+  }
+
+  match action {
+    EditorAction::InsertChar(id, ch) => apply_edit_at_carets(
+      state,
+      id,
+      EditKind::Typing,
+      EditSelection::Replace,
+      |buffer| buffer.insert_char_paired(*ch),
+    ),
+    EditorAction::InsertNewLine(id) => apply_edit_at_carets(
+      state,
+      id,
+      EditKind::Other,
+      EditSelection::Replace,
+      EditorBuffer::insert_new_line_indented,
+    ),
+    EditorAction::Delete(id) => apply_edit_at_carets(
+      state,
+      id,
+      EditKind::Other,
+      EditSelection::DeleteOnly,
+      EditorBuffer::delete,
+    ),
+    EditorAction::Backspace(id) => apply_edit_at_carets(
+      state,
+      id,
+      EditKind::Other,
+      EditSelection::DeleteOnly,
+      EditorBuffer::backspace_paired,
+    ),
+    EditorAction::MoveCaret(id, direction) => {
+      get_history_mut(&mut state.histories, id).break_group();
+      get_buffer_mut(&mut state.buffers, id).move_carets(*direction);
+    }
+    EditorAction::ExtendSelection(id, direction) => {
+      get_history_mut(&mut state.histories, id).break_group();
+      get_buffer_mut(&mut state.buffers, id).move_carets_selecting(*direction);
+    }
+    EditorAction::ExtendSelectionTo(id, pos) => {
+      get_history_mut(&mut state.histories, id).break_group();
+      get_buffer_mut(&mut state.buffers, id).select_to(*pos);
+    }
+    EditorAction::Copy(id) => {
+      if let Some(text) = get_buffer_mut(&mut state.buffers, id).get_selected_text() {
+        state.kill_ring.push(text.clone());
+        state.clipboard = Some(text);
+      }
+    }
+    EditorAction::Cut(id) => {
+      if let Some(text) = get_buffer_mut(&mut state.buffers, id).get_selected_text() {
+        state.kill_ring.push(text.clone());
+        state.clipboard = Some(text);
+        apply_edit_at_carets(
+          state,
+          id,
+          EditKind::Other,
+          EditSelection::DeleteOnly,
+          |_| {},
+        );
+      }
+    }
+    EditorAction::Paste(id, text) => apply_edit_at_carets(
+      state,
+      id,
+      EditKind::Other,
+      EditSelection::Replace,
+      |buffer| buffer.insert_text(text),
+    ),
+    EditorAction::InsertText(id, text) => apply_edit_at_carets(
+      state,
+      id,
+      EditKind::Typing,
+      EditSelection::Replace,
+      |buffer| buffer.insert_text(text),
+    ),
+    EditorAction::Kill(id) => {
+      let buffer = get_buffer_mut(&mut state.buffers, id);
+      buffer.clear_extra_carets();
+      let caret = buffer.caret;
+      // Kill to the end of the line, or the new line at the end of it.
+      let end = match buffer.line_len(caret.row) {
+        line_len if caret.col < line_len => CaretPos {
+          row: caret.row,
+          col: line_len,
+        },
+        _ if caret.row + 1 < buffer.line_count() => CaretPos {
+          row: caret.row + 1,
+          col: 0,
+        },
+        _ => return,
+      };
+      state.kill_ring.push(buffer.get_text_in_range(caret, end));
+      state.kill_ring.is_appending = true;
+      apply_edit(state, id, EditKind::Other, |buffer| {
+        buffer.delete_range(caret, end)
+      });
+    }
+    EditorAction::Yank(id) => {
+      let buffer = get_buffer_mut(&mut state.buffers, id);
+      buffer.clear_extra_carets();
+      let kill_ring = &mut state.kill_ring;
+      get_history_mut(&mut state.histories, id)
+        .apply_edit(buffer, EditKind::Other, |buffer| kill_ring.yank(id, buffer));
+    }
+    EditorAction::YankPop(id) => {
+      let buffer = get_buffer_mut(&mut state.buffers, id);
+      let kill_ring = &mut state.kill_ring;
+      let mut is_yank_popped = false;
+      get_history_mut(&mut state.histories, id).apply_edit(buffer, EditKind::Other, |buffer| {
+        is_yank_popped = kill_ring.yank_pop(id, buffer)
+      });
+      if !is_yank_popped {
+        state.message = Some("Previous command was not a yank".into());
+        return;
+      }
+    }
+    EditorAction::SetClipboard(text) => state.clipboard = Some(text.clone()),
+    EditorAction::SetCaret(id, pos) => {
+      get_history_mut(&mut state.histories, id).break_group();
+      let buffer = get_buffer_mut(&mut state.buffers, id);
+      let pos = buffer.clamp_caret_pos(*pos);
+      buffer.set_carets(vec![pos.into()]);
+    }
+    EditorAction::ToggleCaret(id, pos) => {
+      get_history_mut(&mut state.histories, id).break_group();
+      get_buffer_mut(&mut state.buffers, id).toggle_caret(*pos);
+    }
+    EditorAction::AddCaretAbove(id) => {
+      get_history_mut(&mut state.histories, id).break_group();
+      get_buffer_mut(&mut state.buffers, id).add_caret_vertically(false);
+    }
+    EditorAction::AddCaretBelow(id) => {
+      get_history_mut(&mut state.histories, id).break_group();
+      get_buffer_mut(&mut state.buffers, id).add_caret_vertically(true);
+    }
+    EditorAction::SelectNextOccurrence(id) => {
+      get_history_mut(&mut state.histories, id).break_group();
+      get_buffer_mut(&mut state.buffers, id).select_next_occurrence();
+    }
+    EditorAction::ClearExtraCarets(id) => {
+      get_buffer_mut(&mut state.buffers, id).clear_extra_carets();
+    }
+    EditorAction::Undo(id) => {
+      let buffer = get_buffer_mut(&mut state.buffers, id);
+      get_history_mut(&mut state.histories, id).undo(buffer);
+    }
+    EditorAction::Redo(id) => {
+      let buffer = get_buffer_mut(&mut state.buffers, id);
+      get_history_mut(&mut state.histories, id).redo(buffer);
+    }
+    EditorAction::StartSearch(id) => {
+      get_history_mut(&mut state.histories, id).break_group();
+      // Search & replace only works w/ the primary caret.
+      let buffer = get_buffer_mut(&mut state.buffers, id);
+      buffer.clear_extra_carets();
+      let caret = buffer.caret;
+      state.search = Some(EditorSearch::new(id, caret));
+    }
+    EditorAction::EndSearch => state.search = None,
+    EditorAction::CancelSearch => {
+      if let Some(search) = state.search.take() {
+        let buffer = get_buffer_mut(&mut state.buffers, &search.id);
+        buffer.caret = search.origin;
+        buffer.selection_anchor = None;
+      }
+    }
+    EditorAction::SearchInsertChar(ch) => update_search(state, |search| {
+      search.insert_text(ch.encode_utf8(&mut [0; 4]))
+    }),
+    EditorAction::SearchBackspace => update_search(state, EditorSearch::backspace),
+    EditorAction::SearchToggleField => update_search(state, EditorSearch::toggle_field),
+    EditorAction::SearchMoveCursor(direction) => {
+      if let Some(search) = state.search.as_mut() {
+        search.move_cursor(*direction);
+      }
+    }
+    EditorAction::SearchKill => {
+      if let Some(search) = state.search.as_mut() {
+        state.kill_ring.push(search.kill_to_end());
+        update_search(state, |_| {});
+      }
+    }
+    EditorAction::SearchYank => {
+      if let Some(text) = state.kill_ring.entries.back().cloned() {
+        update_search(state, |search| search.insert_text(&text));
+      }
+    }
+    EditorAction::SearchToggleRegex => update_search(state, |search| {
+      search.is_regex = !search.is_regex;
+    }),
+    EditorAction::SearchToggleCaseSensitive => update_search(state, |search| {
+      search.is_case_sensitive = !search.is_case_sensitive;
+    }),
+    EditorAction::SearchNext => {
+      if let Some(search) = &state.search {
+        search.move_caret_to_next_match(get_buffer_mut(&mut state.buffers, &search.id));
+      }
+    }
+    EditorAction::SearchPrev => {
+      if let Some(search) = &state.search {
+        search.move_caret_to_prev_match(get_buffer_mut(&mut state.buffers, &search.id));
+      }
+    }
+    EditorAction::ReplaceOne => {
+      if let Some(search) = state.search.clone() {
+        apply_edit(state, &search.id, EditKind::Other, |buffer| {
+          search.replace_one(buffer)
+        });
+      }
+    }
+    EditorAction::ReplaceAll => {
+      if let Some(search) = state.search.clone() {
+        apply_edit(state, &search.id, EditKind::Other, |buffer| {
+          search.replace_all(buffer)
+        });
+      }
+    }
+    EditorAction::SetBuffer(id, buffer) => {
+      state.buffers.insert(id.clone(), buffer.clone());
+      state.histories.remove(id);
+      state.hex_buffers.remove(id);
+    }
+    EditorAction::ToggleFold(id) => {
+      if let Some(message) = get_buffer_mut(&mut state.buffers, id).toggle_fold() {
+        state.message = Some(message);
+        return;
+      }
+    }
+    EditorAction::FoldAll(id) => get_buffer_mut(&mut state.buffers, id).fold_all(),
+    EditorAction::UnfoldAll(id) => get_buffer_mut(&mut state.buffers, id).unfold_all(),
+    EditorAction::SetHexBuffer(id, hex_buffer) => {
+      state.hex_buffers.insert(id.clone(), hex_buffer.clone());
+      state.buffers.remove(id);
+      state.histories.remove(id);
+    }
+    EditorAction::HexSetCursor(id, offset) => {
+      update_hex_buffer(state, id, |hex_buffer| hex_buffer.set_cursor(*offset))
+    }
+    EditorAction::HexWriteNibble(id, digit) => {
+      update_hex_buffer(state, id, |hex_buffer| hex_buffer.write_nibble(*digit))
+    }
+    EditorAction::HexWriteByte(id, byte) => {
+      update_hex_buffer(state, id, |hex_buffer| hex_buffer.write_byte(*byte))
+    }
+    EditorAction::HexTogglePane(id) => update_hex_buffer(state, id, |hex_buffer| {
+      hex_buffer.pane = match hex_buffer.pane {
+        HexPane::Hex => HexPane::Ascii,
+        HexPane::Ascii => HexPane::Hex,
+      };
+      hex_buffer.is_low_nibble = false;
+    }),
+    EditorAction::HexOpenPrompt(id, kind) => update_hex_buffer(state, id, |hex_buffer| {
+      hex_buffer.maybe_prompt = Some(HexPrompt {
+        kind: *kind,
+        input: String::new(),
+      })
+    }),
+    EditorAction::HexPromptInsertChar(id, ch) => update_hex_buffer(state, id, |hex_buffer| {
+      if let Some(prompt) = hex_buffer.maybe_prompt.as_mut() {
+        prompt.input.push(*ch);
+      }
+    }),
+    EditorAction::HexPromptBackspace(id) => update_hex_buffer(state, id, |hex_buffer| {
+      if let Some(prompt) = hex_buffer.maybe_prompt.as_mut() {
+        prompt.input.pop();
+      }
+    }),
+    EditorAction::HexPromptCancel(id) => {
+      update_hex_buffer(state, id, |hex_buffer| hex_buffer.maybe_prompt = None)
+    }
+    EditorAction::HexPromptSubmit(id) => {
+      if let Some(hex_buffer) = state.hex_buffers.get_mut(id) {
+        if let Some(message) = hex_buffer.submit_prompt() {
+          state.message = Some(message);
+          return;
+        }
+      }
+    }
+    EditorAction::HexFindNext(id) => {
+      if let Some(hex_buffer) = state.hex_buffers.get_mut(id) {
+        if let Some(message) = hex_buffer.find_next() {
+          state.message = Some(message);
+          return;
+        }
+      }
+    }
+    EditorAction::AppendLines(id, lines) => {
+      let buffer = get_buffer_mut(&mut state.buffers, id);
+      buffer.lines.extend(lines.iter().cloned());
+      buffer.change_count += 1;
+      // The buffer can't be edited while it is loading, so its lines are still the file's.
+      buffer.saved_lines = Some(buffer.lines.clone());
+      return;
+    }
+    EditorAction::LoadFinished(id, maybe_error_message) => {
+      let buffer = get_buffer_mut(&mut state.buffers, id);
+      buffer.is_loading = false;
+      if let Some(error_message) = maybe_error_message {
+        // Only the start of the file is in the buffer, so it must not be saved over the file.
+        buffer.file_path = None;
+        buffer.saved_lines = None;
+        state.message = Some(error_message.clone());
+      }
+      return;
+    }
+    EditorAction::FileSaved(id, file_path, change_count) => {
+      // Edits that were made while the file was being written aren't saved yet.
+      if let Some(hex_buffer) = state.hex_buffers.get_mut(id) {
+        hex_buffer.file_path = Some(file_path.clone());
+        if hex_buffer.change_count == *change_count {
+          hex_buffer.is_dirty = false;
+        }
+      } else {
+        let buffer = get_buffer_mut(&mut state.buffers, id);
+        buffer.file_path = Some(file_path.clone());
+        // The content that was written isn't known anymore if it was edited since.
+        buffer.saved_lines = None;
+        if buffer.change_count == *change_count {
+          buffer.is_dirty = false;
+          buffer.saved_lines = Some(buffer.lines.clone());
+        }
+      }
+      state.message = Some(format!("Saved {}", file_path));
+      return;
+    }
+    EditorAction::StartCollab(id, role) => {
+      let is_host = matches!(role, CollabRole::Host(_));
+      if !is_host {
+        // The host's buffer replaces this one.
+        state.buffers.insert(id.clone(), EditorBuffer::default());
+        state.histories.remove(id);
+        state.hex_buffers.remove(id);
+      }
+      state.collab = Some(CollabState::new(id, is_host));
+      state.message = Some(match role {
+        CollabRole::Host(address) => format!("Sharing the buffer at {}", address),
+        CollabRole::Guest(address) => format!("Joined {}", address),
+      });
+      return;
+    }
+    EditorAction::CollabPeerJoined(peer_id) => {
+      if let Some(collab) = state.collab.as_mut() {
+        let text = state
+          .buffers
+          .get(&collab.id)
+          .map(EditorBuffer::get_as_string)
+          .unwrap_or_default();
+        collab.add_guest(*peer_id, &text);
+        state.message = Some(format!("Peer {} joined", peer_id));
+      }
+      return;
+    }
+    EditorAction::CollabReceive(peer_id, message) => {
+      receive_collab_message(state, *peer_id, message);
+      return;
+    }
+    EditorAction::CollabPeerLeft(peer_id) => {
+      if let Some(collab) = state.collab.as_mut() {
+        collab.remove_peer(*peer_id);
+        if collab.is_host {
+          state.message = Some(format!("Peer {} left", peer_id));
+        } else {
+          state.message =
+            Some("Disconnected from the host, the buffer isn't shared anymore".into());
+          state.collab = None;
+        }
+      }
+      return;
+    }
+    EditorAction::ShowMessage(message) => {
+      state.message = Some(message.clone());
+      return;
+    }
+    EditorAction::Batch(actions) => {
+      for action in actions {
+        reduce_mut(state, action);
+      }
+      return;
+    }
+    _ => return,
+  }
+
+  // Any other key press hides the last message.
+  state.message = None;
+}
+
+/// Keep the folds of the buffers in place after an edit that added or removed lines, & open the
+/// ones that a caret ended up in. The edits of peers shift the folds themselves (see
+/// [EditorBuffer::apply_operation]).
+fn update_folds(old_state: &EditorState, state: &mut EditorState, action: &EditorAction) {
+  for (id, buffer) in state.buffers.iter_mut() {
+    if buffer.folds.is_empty() {
+      continue;
+    }
+    if let Some(old_buffer) = old_state.buffers.get(id) {
+      // Loaded lines are appended after the end of the buffer.
+      let is_shifted = matches!(
+        action,
+        EditorAction::AppendLines(..) | EditorAction::CollabReceive(..)
+      );
+      if !is_shifted {
+        shift_folds_at_edits(old_buffer, buffer);
+      }
+    }
+    buffer.unfold_at_carets();
+  }
+}
+
+/// Shift the folds of `buffer` at each place where lines were added or removed since
+/// `old_buffer` (eg: at each caret of a multi-caret edit). Only the lines that changed are
+/// compared (see [LineRope::get_changed_rows]), & the places are shifted from the last to the
+/// first, so that the rows of the ones that are left are still the rows in `old_buffer`.
+fn shift_folds_at_edits(old_buffer: &EditorBuffer, buffer: &mut EditorBuffer) {
+  let (start, old_end, new_end) = match LineRope::get_changed_rows(&old_buffer.lines, &buffer.lines)
+  {
+    Some(rows) => rows,
+    None => return,
+  };
+  let old_lines: Vec<&String> = old_buffer.lines.iter_range(start..old_end).collect();
+  let new_lines: Vec<&String> = buffer.lines.iter_range(start..new_end).collect();
+  for diff_op in capture_diff_slices(Algorithm::Myers, &old_lines, &new_lines)
+    .iter()
+    .rev()
+  {
+    let (old_range, new_range) = (diff_op.old_range(), diff_op.new_range());
+    let delta = new_range.len() as isize - old_range.len() as isize;
+    if delta > 0 {
+      buffer.shift_folds(start + old_range.end, delta);
+    } else if delta < 0 {
+      buffer.shift_folds(start + old_range.start + new_range.len(), delta);
+    }
+  }
+}
+
+/// Send the edits of the shared buffer that were made in this instance to the peers (see
+/// [CollabState]), as the difference between its lines before & after the action (only the lines
+/// that changed are compared, see [TextOperation::from_line_ropes]).
+fn update_collab(old_state: &EditorState, state: &mut EditorState, action: &EditorAction) {
+  if matches!(
+    action,
+    EditorAction::CollabReceive(..) | EditorAction::StartCollab(..)
+  ) {
+    return;
+  }
+  let collab = match state.collab.as_mut() {
+    Some(collab) => collab,
+    None => return,
+  };
+  let (old_buffer, buffer) = match (
+    old_state.buffers.get(&collab.id),
+    state.buffers.get(&collab.id),
+  ) {
+    (Some(old_buffer), Some(buffer)) if old_buffer.change_count != buffer.change_count => {
+      (old_buffer, buffer)
+    }
+    _ => return,
+  };
+  let op = TextOperation::from_line_ropes(&old_buffer.lines, &buffer.lines);
+  if !op.is_noop() {
+    collab.apply_local_edit(&op);
+  }
+}
+
+/// Apply an edit from a peer to the shared buffer. A peer that is out of sync is dropped.
+fn receive_collab_message(state: &mut EditorState, peer_id: u32, message: &CollabMessage) {
+  let collab = match state.collab.as_mut() {
+    Some(collab) => collab,
+    None => return,
+  };
+  let id = collab.id.clone();
+  let op = match collab.receive(peer_id, message) {
+    Ok(Some(op)) => op,
+    Ok(None) => return,
+    Err(error_message) => {
+      collab.remove_peer(peer_id);
+      state.message = Some(error_message);
+      return;
+    }
+  };
+  let buffer = get_buffer_mut(&mut state.buffers, &id);
+  let old_buffer = buffer.clone();
+  if buffer.apply_operation(&op).is_none() {
+    collab.remove_peer(peer_id);
+    state.message = Some(format!(
+      "Out of sync w/ peer {}, it was disconnected",
+      peer_id
+    ));
+    return;
+  }
+  // Undo only takes back the edits of this instance.
+  if let Some(history) = state.histories.get_mut(&id) {
+    history.transform(&old_buffer, &op);
+  }
+}
+
+/// Id of the buffer that `action` edits, if it is an edit.
+fn get_edited_buffer_id<'a>(state: &'a EditorState, action: &'a EditorAction) -> Option<&'a str> {
+  match action {
+    EditorAction::InsertChar(id, _)
+    | EditorAction::InsertNewLine(id)
+    | EditorAction::Delete(id)
+    | EditorAction::Backspace(id)
+    | EditorAction::Cut(id)
+    | EditorAction::Paste(id, _)
+    | EditorAction::InsertText(id, _)
+    | EditorAction::Kill(id)
+    | EditorAction::Yank(id)
+    | EditorAction::YankPop(id)
+    | EditorAction::Undo(id)
+    | EditorAction::Redo(id) => Some(id),
+    EditorAction::ReplaceOne | EditorAction::ReplaceAll => {
+      state.search.as_ref().map(|it| it.id.as_str())
+    }
+    _ => None,
+  }
+}
+
+/// Edits go through the history of the buffer, so that they can be undone.
+fn apply_edit(
+  state: &mut EditorState, id: &str, kind: EditKind, edit: impl FnOnce(&mut EditorBuffer),
+) {
+  let buffer = get_buffer_mut(&mut state.buffers, id);
+  get_history_mut(&mut state.histories, id).apply_edit(buffer, kind, edit);
+}
+
+/// Same as [apply_edit], `edit` is run at each caret of the buffer (as a single undo step).
+fn apply_edit_at_carets(
+  state: &mut EditorState, id: &str, kind: EditKind, edit_selection: EditSelection,
+  edit: impl FnMut(&mut EditorBuffer),
+) {
+  apply_edit(state, id, kind, |buffer| {
+    buffer.edit_at_carets(edit_selection, edit)
+  });
+}
+
+/// Run `update` on the search bar state (if it is open), then search incrementally from where the
+/// search started.
+fn update_search(state: &mut EditorState, update: impl FnOnce(&mut EditorSearch)) {
+  if let Some(search) = state.search.as_mut() {
+    update(search);
+    search.move_caret_to_first_match(get_buffer_mut(&mut state.buffers, &search.id));
+  }
+}
+
+/// Hex buffers are only created by [EditorAction::SetHexBuffer], actions for other ids are
+/// ignored.
+fn update_hex_buffer(state: &mut EditorState, id: &str, update: impl FnOnce(&mut HexBuffer)) {
+  if let Some(hex_buffer) = state.hex_buffers.get_mut(id) {
+    update(hex_buffer);
+  }
+}
+
+/// Buffers are created lazily, the first time an action is dispatched for an id.
+fn get_buffer_mut<'a>(
+  buffers: &'a mut HashMap<String, EditorBuffer>, id: &str,
+) -> &'a mut EditorBuffer {
+  buffers.entry(id.to_string()).or_default()
+}
+
+fn get_history_mut<'a>(
+  histories: &'a mut HashMap<String, EditorHistory>, id: &str,
+) -> &'a mut EditorHistory {
+  histories.entry(id.to_string()).or_default()
+}
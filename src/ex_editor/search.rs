@@ -0,0 +1,480 @@
+/*
+ *   Copyright (c) 2022 R3BL LLC
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use std::{fmt::{Display, Formatter},
+          ops::Range};
+
+use regex::{NoExpand, Regex, RegexBuilder};
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::*;
+
+/// Which input field of the search bar receives typed characters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SearchField {
+  Query,
+  Replacement,
+}
+
+/// A match of the search query in a single line of an [EditorBuffer]. `start` & `end` are grapheme
+/// cluster indices (`end` is exclusive) that are used to move the caret & paint the highlight, and
+/// `byte_range` is used to replace the match.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SearchMatch {
+  pub row: usize,
+  pub start: usize,
+  pub end: usize,
+  pub byte_range: Range<usize>,
+}
+
+impl SearchMatch {
+  pub fn start_pos(&self) -> CaretPos {
+    CaretPos {
+      row: self.row,
+      col: self.start,
+    }
+  }
+}
+
+/// State of the search bar, while searching in the buffer for `id`. `origin` is where the caret
+/// was when the search started, incremental search finds the first match after it. `cursor` is the
+/// grapheme cluster index of the cursor in the active field.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EditorSearch {
+  pub id: String,
+  pub query: String,
+  pub replacement: String,
+  pub active_field: SearchField,
+  pub cursor: usize,
+  pub is_regex: bool,
+  pub is_case_sensitive: bool,
+  pub origin: CaretPos,
+}
+
+impl Display for EditorSearch {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "Find: {} │ Replace: {} │ {} {}",
+      self.get_field_with_cursor(SearchField::Query),
+      self.get_field_with_cursor(SearchField::Replacement),
+      if self.is_regex { "[.*]" } else { "[ab]" },
+      if self.is_case_sensitive {
+        "[Aa]"
+      } else {
+        "[aa]"
+      },
+    )
+  }
+}
+
+impl EditorSearch {
+  pub fn new(id: &str, origin: CaretPos) -> Self {
+    Self {
+      id: id.into(),
+      query: String::new(),
+      replacement: String::new(),
+      active_field: SearchField::Query,
+      cursor: 0,
+      is_regex: false,
+      is_case_sensitive: false,
+      origin,
+    }
+  }
+
+  pub fn get_active_field(&self) -> &str {
+    match self.active_field {
+      SearchField::Query => &self.query,
+      SearchField::Replacement => &self.replacement,
+    }
+  }
+
+  pub fn get_active_field_mut(&mut self) -> &mut String {
+    match self.active_field {
+      SearchField::Query => &mut self.query,
+      SearchField::Replacement => &mut self.replacement,
+    }
+  }
+
+  /// Switch between the query & replacement fields, the cursor goes to the end of the field.
+  pub fn toggle_field(&mut self) {
+    self.active_field = match self.active_field {
+      SearchField::Query => SearchField::Replacement,
+      SearchField::Replacement => SearchField::Query,
+    };
+    self.cursor = self.get_active_field().grapheme_count();
+  }
+
+  /// Insert `text` at the cursor of the active field. The fields are single line, so only the
+  /// first line of `text` is inserted.
+  pub fn insert_text(&mut self, text: &str) {
+    let text = text.lines().next().unwrap_or_default();
+    let cursor = self.cursor;
+    let field = self.get_active_field_mut();
+    field.insert_str(field.byte_index_of_grapheme(cursor), text);
+    self.cursor += text.grapheme_count();
+  }
+
+  pub fn backspace(&mut self) {
+    if self.cursor == 0 {
+      return;
+    }
+    self.cursor -= 1;
+    let cursor = self.cursor;
+    let field = self.get_active_field_mut();
+    let range = field.byte_index_of_grapheme(cursor)..field.byte_index_of_grapheme(cursor + 1);
+    field.replace_range(range, "");
+  }
+
+  /// Move the cursor in the active field, like the caret in a single line [EditorBuffer].
+  pub fn move_cursor(&mut self, direction: CaretDirection) {
+    let line = EditorBuffer::from_content(self.get_active_field());
+    let from = CaretPos {
+      row: 0,
+      col: self.cursor,
+    };
+    self.cursor = line.get_moved_caret_pos(from, direction).col;
+  }
+
+  /// Delete the text from the cursor to the end of the active field, & return it.
+  pub fn kill_to_end(&mut self) -> String {
+    let cursor = self.cursor;
+    let field = self.get_active_field_mut();
+    field.split_off(field.byte_index_of_grapheme(cursor))
+  }
+
+  fn get_field_with_cursor(&self, search_field: SearchField) -> String {
+    let field = match search_field {
+      SearchField::Query => &self.query,
+      SearchField::Replacement => &self.replacement,
+    };
+    match search_field == self.active_field {
+      true => {
+        let byte_index = field.byte_index_of_grapheme(self.cursor);
+        format!("{}▏{}", &field[..byte_index], &field[byte_index..])
+      }
+      false => field.clone(),
+    }
+  }
+
+  /// Returns `Ok(None)` if the query is empty, and an error message if it is not a valid regex.
+  pub fn build_regex(&self) -> Result<Option<Regex>, String> {
+    if self.query.is_empty() {
+      return Ok(None);
+    }
+    let pattern = if self.is_regex {
+      self.query.clone()
+    } else {
+      regex::escape(&self.query)
+    };
+    RegexBuilder::new(&pattern)
+      .case_insensitive(!self.is_case_sensitive)
+      .build()
+      .map(Some)
+      .map_err(|_| "Invalid regex".to_string())
+  }
+
+  /// All the matches in the buffer, in order.
+  pub fn find_matches(&self, buffer: &EditorBuffer) -> Vec<SearchMatch> {
+    match self.build_regex() {
+      Ok(Some(regex)) => buffer
+        .lines
+        .iter()
+        .enumerate()
+        .flat_map(|(row, line)| find_matches_in_line(&regex, row, line))
+        .collect(),
+      _ => vec![],
+    }
+  }
+
+  /// Move the caret to the first match at or after `origin` (wrapping around to the start of the
+  /// buffer), or back to `origin` if there are no matches. Used as the query is typed.
+  pub fn move_caret_to_first_match(&self, buffer: &mut EditorBuffer) {
+    let pos = self
+      .find_next_match(buffer, self.origin, true)
+      .map(|it| it.start_pos())
+      .unwrap_or(self.origin);
+    move_caret(buffer, pos);
+  }
+
+  /// Move the caret to the next match after it (wrapping around).
+  pub fn move_caret_to_next_match(&self, buffer: &mut EditorBuffer) {
+    if let Some(next) = self.find_next_match(buffer, buffer.caret, false) {
+      move_caret(buffer, next.start_pos());
+    }
+  }
+
+  /// Move the caret to the previous match before it (wrapping around).
+  pub fn move_caret_to_prev_match(&self, buffer: &mut EditorBuffer) {
+    if let Some(prev) = self.find_prev_match(buffer, buffer.caret) {
+      move_caret(buffer, prev.start_pos());
+    }
+  }
+
+  /// First match after `pos` (or at it, w/ `is_inclusive`), wrapping around to the start of the
+  /// buffer. Lines are searched one at a time, so that it stops at the first match (instead of
+  /// finding all the matches in a large buffer).
+  fn find_next_match(
+    &self, buffer: &EditorBuffer, pos: CaretPos, is_inclusive: bool,
+  ) -> Option<SearchMatch> {
+    let regex = &self.build_regex().ok()??;
+    let pos = buffer.clamp_caret_pos(pos);
+    let find_in_rows = move |rows: Range<usize>| {
+      rows
+        .clone()
+        .zip(buffer.lines.iter_range(rows))
+        .flat_map(move |(row, line)| find_matches_in_line(regex, row, line))
+    };
+    find_in_rows(pos.row..buffer.line_count())
+      .find(|it| match is_inclusive {
+        true => it.start_pos() >= pos,
+        false => it.start_pos() > pos,
+      })
+      .or_else(|| find_in_rows(0..pos.row + 1).next())
+  }
+
+  /// Same as [EditorSearch::find_next_match], for the last match before `pos` (wrapping around to
+  /// the end of the buffer).
+  fn find_prev_match(&self, buffer: &EditorBuffer, pos: CaretPos) -> Option<SearchMatch> {
+    let regex = &self.build_regex().ok()??;
+    let pos = buffer.clamp_caret_pos(pos);
+    let find_in_rows_rev = move |rows: Range<usize>| {
+      rows.rev().flat_map(move |row| {
+        find_matches_in_line(regex, row, &buffer.lines[row])
+          .into_iter()
+          .rev()
+      })
+    };
+    find_in_rows_rev(0..pos.row + 1)
+      .find(|it| it.start_pos() < pos)
+      .or_else(|| find_in_rows_rev(pos.row..buffer.line_count()).next())
+  }
+
+  /// Replace the match at the caret (if there is one) & move to the next match. In regex mode, the
+  /// replacement can refer to capture groups (eg: `$1`).
+  pub fn replace_one(&self, buffer: &mut EditorBuffer) {
+    let regex = match self.build_regex() {
+      Ok(Some(regex)) => regex,
+      _ => return,
+    };
+    let caret = buffer.caret;
+    let maybe_match = find_matches_in_line(&regex, caret.row, &buffer.lines[caret.row])
+      .into_iter()
+      .find(|it| it.start == caret.col);
+    if let Some(search_match) = maybe_match {
+      let replacement = self.expand_replacement(&regex, &buffer.lines[caret.row], &search_match);
+      buffer.replace_in_line(caret.row, search_match.byte_range, &replacement);
+    }
+    self.move_caret_to_next_match(buffer);
+  }
+
+  /// Replace all the matches in the buffer.
+  pub fn replace_all(&self, buffer: &mut EditorBuffer) {
+    let regex = match self.build_regex() {
+      Ok(Some(regex)) => regex,
+      _ => return,
+    };
+    let mut is_changed = false;
+    // Only the lines that have matches are written, so the chunks of the buffer's lines that
+    // don't have any aren't copied (see [LineRope]).
+    for row in 0..buffer.line_count() {
+      let line = &buffer.lines[row];
+      let new_line = if self.is_regex {
+        regex.replace_all(line, self.replacement.as_str())
+      } else {
+        regex.replace_all(line, NoExpand(&self.replacement))
+      };
+      if new_line != *line {
+        let new_line = new_line.into_owned();
+        buffer.lines[row] = new_line;
+        is_changed = true;
+      }
+    }
+    if is_changed {
+      let caret = buffer.clamp_caret_pos(buffer.caret);
+      move_caret(buffer, caret);
+      buffer.mark_changed();
+    }
+  }
+
+  fn expand_replacement(&self, regex: &Regex, line: &str, search_match: &SearchMatch) -> String {
+    if !self.is_regex {
+      return self.replacement.clone();
+    }
+    let mut expanded = String::new();
+    if let Some(captures) = regex
+      .captures_iter(line)
+      .find(|it| it.get(0).map(|it| it.start()) == Some(search_match.byte_range.start))
+    {
+      captures.expand(&self.replacement, &mut expanded);
+    }
+    expanded
+  }
+}
+
+/// Move the caret of `buffer` to `pos` & clear the selection, which would otherwise stretch from
+/// where it was started to the match.
+fn move_caret(buffer: &mut EditorBuffer, pos: CaretPos) {
+  buffer.caret = pos;
+  buffer.selection_anchor = None;
+}
+
+/// Matches of `regex` in `line`. Matches that start or end in the middle of a grapheme cluster are
+/// widened to cover the whole cluster. Empty matches are skipped.
+pub fn find_matches_in_line(regex: &Regex, row: usize, line: &str) -> Vec<SearchMatch> {
+  let grapheme_starts: Vec<usize> = line
+    .grapheme_indices(true)
+    .map(|(byte_index, _)| byte_index)
+    .collect();
+  // Index of the grapheme cluster that contains the byte at `byte_index`.
+  let grapheme_index_of = |byte_index: usize| match grapheme_starts.binary_search(&byte_index) {
+    Ok(index) => index,
+    Err(index) => index - 1,
+  };
+
+  regex
+    .find_iter(line)
+    .filter(|it| !it.range().is_empty())
+    .map(|it| SearchMatch {
+      row,
+      start: grapheme_index_of(it.start()),
+      end: grapheme_index_of(it.end() - 1) + 1,
+      byte_range: it.range(),
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn make_search(query: &str) -> EditorSearch {
+    EditorSearch {
+      query: query.into(),
+      ..EditorSearch::new("id", CaretPos::default())
+    }
+  }
+
+  #[test]
+  fn test_find_next_wraps_around() {
+    let mut buffer = EditorBuffer::from_content("foo bar\nbar foo\nbaz");
+    let search = make_search("foo");
+    search.move_caret_to_first_match(&mut buffer);
+    assert_eq!(buffer.caret, CaretPos { row: 0, col: 0 });
+    search.move_caret_to_next_match(&mut buffer);
+    assert_eq!(buffer.caret, CaretPos { row: 1, col: 4 });
+    search.move_caret_to_next_match(&mut buffer);
+    assert_eq!(buffer.caret, CaretPos { row: 0, col: 0 });
+  }
+
+  #[test]
+  fn test_find_prev_wraps_around() {
+    let mut buffer = EditorBuffer::from_content("foo bar\nbar foo\nbaz");
+    let search = make_search("foo");
+    search.move_caret_to_prev_match(&mut buffer);
+    assert_eq!(buffer.caret, CaretPos { row: 1, col: 4 });
+    search.move_caret_to_prev_match(&mut buffer);
+    assert_eq!(buffer.caret, CaretPos { row: 0, col: 0 });
+  }
+
+  #[test]
+  fn test_find_first_match_from_origin() {
+    let mut buffer = EditorBuffer::from_content("ab ab ab");
+    let search = EditorSearch {
+      origin: CaretPos { row: 0, col: 4 },
+      ..make_search("ab")
+    };
+    search.move_caret_to_first_match(&mut buffer);
+    assert_eq!(buffer.caret, CaretPos { row: 0, col: 6 });
+    // W/out any matches, the caret goes back to where the search started.
+    let search = EditorSearch {
+      query: "x".into(),
+      ..search
+    };
+    search.move_caret_to_first_match(&mut buffer);
+    assert_eq!(buffer.caret, CaretPos { row: 0, col: 4 });
+  }
+
+  #[test]
+  fn test_case_sensitivity() {
+    let buffer = EditorBuffer::from_content("Foo foo FOO");
+    let mut search = make_search("foo");
+    assert_eq!(search.find_matches(&buffer).len(), 3);
+    search.is_case_sensitive = true;
+    let matches = search.find_matches(&buffer);
+    assert_eq!(matches.len(), 1);
+    assert_eq!(matches[0].start_pos(), CaretPos { row: 0, col: 4 });
+  }
+
+  #[test]
+  fn test_replace_all() {
+    let mut buffer = EditorBuffer::from_content("a.b a.b\naxb\na.b");
+    let search = EditorSearch {
+      replacement: "-".into(),
+      ..make_search("a.b")
+    };
+    assert_eq!(search.find_matches(&buffer).len(), 3);
+    search.replace_all(&mut buffer);
+    assert_eq!(buffer.get_as_string(), "- -\naxb\n-");
+    assert!(search.find_matches(&buffer).is_empty());
+  }
+
+  #[test]
+  fn test_replace_all_regex_w_capture_groups() {
+    let mut buffer = EditorBuffer::from_content("x=1, y=2");
+    let search = EditorSearch {
+      replacement: "$2=$1".into(),
+      is_regex: true,
+      ..make_search(r"(\w)=(\d)")
+    };
+    search.replace_all(&mut buffer);
+    assert_eq!(buffer.get_as_string(), "1=x, 2=y");
+  }
+
+  #[test]
+  fn test_replace_one_moves_to_next_match() {
+    let mut buffer = EditorBuffer::from_content("ab ab");
+    let search = EditorSearch {
+      replacement: "xyz".into(),
+      ..make_search("ab")
+    };
+    search.move_caret_to_first_match(&mut buffer);
+    search.replace_one(&mut buffer);
+    assert_eq!(buffer.get_as_string(), "xyz ab");
+    assert_eq!(buffer.caret, CaretPos { row: 0, col: 4 });
+  }
+
+  #[test]
+  fn test_moving_to_a_match_clears_the_selection() {
+    let mut buffer = EditorBuffer::from_content("foo\nbar foo");
+    buffer.selection_anchor = Some(CaretPos { row: 0, col: 1 });
+    let search = make_search("foo");
+    search.move_caret_to_next_match(&mut buffer);
+    assert_eq!(buffer.caret, CaretPos { row: 1, col: 4 });
+    assert_eq!(buffer.selection_anchor, None);
+  }
+
+  #[test]
+  fn test_matches_cover_whole_grapheme_clusters() {
+    // `e` & a combining acute accent are a single grapheme cluster.
+    let buffer = EditorBuffer::from_content("cafe\u{301} cafe");
+    let matches = make_search("e").find_matches(&buffer);
+    assert_eq!(matches.len(), 2);
+    assert_eq!((matches[0].start, matches[0].end), (3, 4));
+    assert_eq!((matches[1].start, matches[1].end), (8, 9));
+  }
+}
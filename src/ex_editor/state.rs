@@ -0,0 +1,68 @@
+/*
+ *   Copyright (c) 2022 R3BL LLC
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use std::{collections::HashMap,
+          fmt::{Display, Formatter}};
+
+use super::*;
+
+/// State. Each editor component has its own [EditorBuffer], keyed by the component's id.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct EditorState {
+  pub buffers: HashMap<String, EditorBuffer>,
+  /// Buffers of the files that are open in the hex view, keyed by the same id (instead of an
+  /// [EditorBuffer]).
+  pub hex_buffers: HashMap<String, HexBuffer>,
+  /// Undo & redo history for each buffer, keyed by the same id.
+  pub histories: HashMap<String, EditorHistory>,
+  /// Message for the user (eg: a file could not be saved), shown at the bottom of the window.
+  pub message: Option<String>,
+  /// Search bar state, while it is open.
+  pub search: Option<EditorSearch>,
+  /// In process clipboard, shared by all the buffers.
+  pub clipboard: Option<String>,
+  /// Emacs style kill ring, shared by all the buffers & the search bar.
+  pub kill_ring: KillRing,
+  /// The buffer that is shared w/ other instances, if any (see [CollabRole]).
+  pub collab: Option<CollabState>,
+}
+
+impl Display for EditorState {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    let mut ids: Vec<&String> = self.buffers.keys().collect();
+    ids.sort();
+    write!(f, "State {{ buffers: [")?;
+    for id in ids {
+      write!(f, " {}: {}", id, self.buffers[id])?;
+    }
+    write!(
+      f,
+      " ], message: {:?}, search: {:?}, clipboard: {:?} }}",
+      self.message, self.search, self.clipboard
+    )
+  }
+}
+
+impl EditorState {
+  pub fn get_buffer(&self, id: &str) -> Option<&EditorBuffer> { self.buffers.get(id) }
+
+  pub fn get_hex_buffer(&self, id: &str) -> Option<&HexBuffer> { self.hex_buffers.get(id) }
+
+  pub fn has_dirty_buffers(&self) -> bool {
+    self.buffers.values().any(|it| it.is_dirty) || self.hex_buffers.values().any(|it| it.is_dirty)
+  }
+}
@@ -0,0 +1,107 @@
+/*
+ *   Copyright (c) 2022 R3BL LLC
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use r3bl_rs_utils::*;
+
+use super::*;
+
+/// Paint `text` in the status line box. It is padded w/ spaces, so that the background color of
+/// the box fills it.
+pub fn render_status_line(status_box: &TWBox, text: &str) -> TWCommandQueue {
+  let width = status_box.bounding_size.cols as usize;
+  let mut text = text.clip_to_display_cols(0, width);
+  let padding = width.saturating_sub(text.display_width());
+  text.push_str(&" ".repeat(padding));
+
+  tw_command_queue!(
+    TWCommand::MoveCursorPositionRelTo(status_box.origin_pos, Position { col: 0, row: 0 }),
+    TWCommand::ApplyColors(status_box.get_computed_style()),
+    TWCommand::PrintWithAttributes(text, status_box.get_computed_style()),
+    TWCommand::ResetColor
+  )
+}
+
+/// Status of a buffer: file name (& ` [+]` if it has unsaved changes, or ` [loading]` while the
+/// rest of the file is loading), caret position (1 based, & the number of carets if there are
+/// several), encoding, line ending & mode. Followed by the message in [EditorState] (if any).
+pub fn get_status_text(buffer: &EditorBuffer, mode: &str, message: Option<&str>) -> String {
+  let file_name = buffer.get_file_name().unwrap_or("[No Name]");
+  let dirty_flag = match (buffer.is_dirty, buffer.is_loading) {
+    (true, _) => " [+]",
+    (false, true) => " [loading]",
+    (false, false) => "",
+  };
+  let mut caret = format!("{}:{}", buffer.caret.row + 1, buffer.caret.col + 1);
+  if buffer.has_extra_carets() {
+    caret.push_str(&format!(" ({} carets)", buffer.extra_carets.len() + 1));
+  }
+
+  let mut status = format!(
+    " {}{} │ {} │ UTF-8 │ {} │ {}",
+    file_name, dirty_flag, caret, buffer.line_ending, mode
+  );
+  if let Some(message) = message {
+    status.push_str(" │ ");
+    status.push_str(message);
+  }
+  status
+}
+
+/// Status of a buffer in the hex view: file name (& ` [+]` if it has unsaved changes), offset of
+/// the cursor & size of the file (in hex), & the pane that has the cursor. Followed by the prompt
+/// (while it is open) or the message in [EditorState] (if any).
+pub fn get_hex_status_text(hex_buffer: &HexBuffer, message: Option<&str>) -> String {
+  let file_name = hex_buffer.get_file_name().unwrap_or("[No Name]");
+  let dirty_flag = if hex_buffer.is_dirty { " [+]" } else { "" };
+  let pane = match hex_buffer.pane {
+    HexPane::Hex => "HEX",
+    HexPane::Ascii => "HEX (ASCII)",
+  };
+
+  let mut status = format!(
+    " {}{} │ 0x{:x} / 0x{:x} │ {}",
+    file_name,
+    dirty_flag,
+    hex_buffer.cursor,
+    hex_buffer.len(),
+    pane
+  );
+  let maybe_prompt = hex_buffer.maybe_prompt.as_ref().map(HexPrompt::to_string);
+  if let Some(text) = maybe_prompt.as_deref().or(message) {
+    status.push_str(" │ ");
+    status.push_str(text);
+  }
+  status
+}
+
+/// The search bar (see [EditorSearch]) followed by the number of matches in its buffer, or the
+/// error if the query is not a valid regex. Matches aren't counted in large buffers, since that
+/// would search the whole buffer on each render.
+pub fn get_search_bar_text(state: &EditorState, search: &EditorSearch) -> String {
+  let maybe_buffer = state.get_buffer(&search.id);
+  let result = match search.build_regex() {
+    Err(error_message) => error_message,
+    Ok(_) if maybe_buffer.map(EditorBuffer::is_large) == Some(true) => "Large file".to_string(),
+    Ok(_) => {
+      let match_count = maybe_buffer
+        .map(|buffer| search.find_matches(buffer).len())
+        .unwrap_or(0);
+      format!("{} matches", match_count)
+    }
+  };
+  format!(" {} │ {}", search, result)
+}
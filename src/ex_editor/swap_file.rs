@@ -0,0 +1,311 @@
+/*
+ *   Copyright (c) 2022 R3BL LLC
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use std::{collections::HashMap,
+          env,
+          fs,
+          io::{self, Write},
+          path::{Path, PathBuf},
+          sync::{Arc, Mutex, MutexGuard, PoisonError},
+          time::Duration};
+
+use similar::TextDiff;
+use tokio::task::JoinHandle;
+
+use super::*;
+
+/// How often the dirty buffers are written to their swap files.
+const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Longer swap file names (w/out `.swp`) are shortened (see [get_swap_file_path]), so that they &
+/// their temporary files fit in the 255 bytes that most file systems allow for a name.
+const MAX_SWAP_NAME_LEN: usize = 200;
+
+/// Writes the content of the dirty buffers to swap files every [AUTOSAVE_INTERVAL] (see
+/// [Autosaver::spawn]), & when the app panics, so that unsaved changes can be recovered (see
+/// [recover_swap_file]) if the editor doesn't exit normally. It gets the buffers on each render,
+/// cloning them is cheap (see [LineRope]), & errors writing the swap files are shown then.
+#[derive(Clone, Debug, Default)]
+pub struct Autosaver {
+  inner: Arc<Mutex<AutosaverInner>>,
+}
+
+#[derive(Debug, Default)]
+struct AutosaverInner {
+  /// Latest buffers, keyed by file path (buffers w/out a file don't have a swap file).
+  buffers: HashMap<String, EditorBuffer>,
+  /// Change count of the buffer in each swap file that was written, keyed by file path.
+  written_change_counts: HashMap<String, u64>,
+  /// Error of the last time the swap files were written, if one of them couldn't be.
+  maybe_write_error: Option<String>,
+  /// Error that was last shown in the status line (see [Autosaver::take_write_error]).
+  maybe_shown_write_error: Option<String>,
+}
+
+impl Autosaver {
+  pub fn update(&self, state: &EditorState) {
+    lock(&self.inner).buffers = state
+      .buffers
+      .values()
+      .filter_map(|buffer| Some((buffer.file_path.clone()?, buffer.clone())))
+      .collect();
+  }
+
+  /// Error writing the swap files, if it wasn't shown yet (the same error is only shown once,
+  /// until the swap files can be written again).
+  pub fn take_write_error(&self) -> Option<String> {
+    let mut inner = lock(&self.inner);
+    if inner.maybe_write_error == inner.maybe_shown_write_error {
+      return None;
+    }
+    inner.maybe_shown_write_error = inner.maybe_write_error.clone();
+    inner.maybe_write_error.clone()
+  }
+
+  /// Spawn the task that writes the swap files of the dirty buffers that changed since the last
+  /// time, & removes the ones of the buffers that aren't dirty anymore (eg: they were saved).
+  pub fn spawn(&self) -> JoinHandle<()> {
+    let autosaver = self.clone();
+    tokio::spawn(async move {
+      let mut interval = tokio::time::interval(AUTOSAVE_INTERVAL);
+      loop {
+        interval.tick().await;
+        let autosaver = autosaver.clone();
+        // Writing a large file takes a while, don't block the other tasks.
+        let _ = tokio::task::spawn_blocking(move || autosaver.write_swap_files()).await;
+      }
+    })
+  }
+
+  /// Write the swap files of all the dirty buffers right away (eg: when the app panics), & return
+  /// the paths of their files.
+  pub fn write_swap_files(&self) -> Vec<String> {
+    let buffers = lock(&self.inner).buffers.clone();
+    let mut written_file_paths = vec![];
+    let mut maybe_write_error = None;
+    for (file_path, buffer) in buffers {
+      let written_change_count = lock(&self.inner)
+        .written_change_counts
+        .get(&file_path)
+        .copied();
+      match buffer.is_dirty {
+        true if written_change_count == Some(buffer.change_count) => {
+          written_file_paths.push(file_path)
+        }
+        true => match write_swap_file(&file_path, &buffer.get_as_file_content()) {
+          Ok(()) => {
+            lock(&self.inner)
+              .written_change_counts
+              .insert(file_path.clone(), buffer.change_count);
+            written_file_paths.push(file_path);
+          }
+          Err(error) => {
+            maybe_write_error = Some(format!(
+              "Can't write the swap file of {}: {}",
+              file_path, error
+            ));
+          }
+        },
+        false if written_change_count.is_some() => {
+          let _ = fs::remove_file(get_swap_file_path(&file_path));
+          lock(&self.inner).written_change_counts.remove(&file_path);
+        }
+        false => {}
+      }
+    }
+    let mut inner = lock(&self.inner);
+    if maybe_write_error.is_none() {
+      inner.maybe_shown_write_error = None;
+    }
+    inner.maybe_write_error = maybe_write_error;
+    written_file_paths
+  }
+
+  /// Remove the swap files that were written, when the editor exits normally (unsaved changes are
+  /// only thrown away on purpose, eg: Ctrl+Q twice). Swap files that were left over by an earlier
+  /// run & kept at launch aren't removed.
+  pub fn remove_swap_files(&self) {
+    let mut inner = lock(&self.inner);
+    for file_path in inner.written_change_counts.keys() {
+      let _ = fs::remove_file(get_swap_file_path(file_path));
+    }
+    inner.written_change_counts.clear();
+  }
+}
+
+/// Path of the swap file for `file_path`. It is in `$XDG_STATE_HOME/r3bl-cmdr/swap` (or
+/// `~/.local/state/r3bl-cmdr/swap`), named after the absolute path of the file w/ each `%` & `/`
+/// percent encoded (`%25` & `%2F`), so that different paths never share a swap file. W/out a home
+/// directory, it is next to the file (`.<name>.swp`). Names longer than [MAX_SWAP_NAME_LEN] are
+/// cut & end w/ a hash of the whole name instead (see [shorten_swap_name]).
+pub fn get_swap_file_path(file_path: &str) -> PathBuf {
+  let path = Path::new(file_path);
+  match get_app_dir("XDG_STATE_HOME", ".local/state") {
+    Some(app_dir) => {
+      let name = get_absolute_path(path)
+        .to_string_lossy()
+        .replace('%', "%25")
+        .replace(std::path::MAIN_SEPARATOR, "%2F");
+      app_dir
+        .join("swap")
+        .join(format!("{}.swp", shorten_swap_name(name)))
+    }
+    None => {
+      let name = path.file_name().unwrap_or_default().to_string_lossy();
+      path.with_file_name(format!(".{}.swp", shorten_swap_name(name.into())))
+    }
+  }
+}
+
+/// `name` if it isn't longer than [MAX_SWAP_NAME_LEN], otherwise its start followed by a hash of
+/// all of it (FNV-1a, which doesn't change between Rust versions like std's hasher may, so that
+/// the swap file is found by the next run).
+fn shorten_swap_name(name: String) -> String {
+  if name.len() <= MAX_SWAP_NAME_LEN {
+    return name;
+  }
+  let hash = name.bytes().fold(0xcbf29ce484222325_u64, |hash, byte| {
+    (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+  });
+  // `-` & 16 hex digits.
+  let mut prefix_len = MAX_SWAP_NAME_LEN - 17;
+  while !name.is_char_boundary(prefix_len) {
+    prefix_len -= 1;
+  }
+  format!("{}-{:016x}", &name[..prefix_len], hash)
+}
+
+/// If there is a swap file for `file_path` that is left over from an earlier run (eg: the editor
+/// crashed), ask the user whether to recover the unsaved changes in it, show the diff between the
+/// file & the swap file, or discard it. This is done before the terminal is in raw mode. Returns
+/// the recovered buffer, which has unsaved changes. A swap file that is the same as the file is
+/// removed w/out asking.
+pub fn recover_swap_file(file_path: &str) -> Option<EditorBuffer> {
+  let swap_file_path = get_swap_file_path(file_path);
+  let swap_content = fs::read_to_string(&swap_file_path).ok()?;
+  let file_content = fs::read_to_string(file_path).unwrap_or_default();
+  if swap_content == file_content {
+    let _ = fs::remove_file(&swap_file_path);
+    return None;
+  }
+
+  println!(
+    "Found a swap file for {} w/ unsaved changes ({}).",
+    file_path,
+    swap_file_path.display()
+  );
+  loop {
+    print!(
+      "(r)ecover the changes, (s)how the diff, (d)iscard them, or (k)eep the swap file & open the \
+       file? "
+    );
+    let _ = io::stdout().flush();
+    let mut answer = String::new();
+    if io::stdin().read_line(&mut answer).unwrap_or(0) == 0 {
+      return None;
+    }
+    match answer.trim() {
+      "r" => {
+        let mut buffer = EditorBuffer::from_content(&swap_content);
+        buffer.file_path = Some(file_path.into());
+        buffer.mark_changed();
+        return Some(buffer);
+      }
+      "s" => print!(
+        "{}",
+        TextDiff::from_lines(&file_content, &swap_content)
+          .unified_diff()
+          .header(file_path, &swap_file_path.to_string_lossy())
+      ),
+      "d" => {
+        let _ = fs::remove_file(&swap_file_path);
+        return None;
+      }
+      "k" => return None,
+      _ => {}
+    }
+  }
+}
+
+/// Write `content` to a temporary file & rename it to the swap file of `file_path`, so that a
+/// crash while it is written doesn't leave half a swap file.
+fn write_swap_file(file_path: &str, content: &str) -> io::Result<()> {
+  let swap_file_path = get_swap_file_path(file_path);
+  if let Some(swap_dir) = swap_file_path.parent() {
+    fs::create_dir_all(swap_dir)?;
+  }
+  let tmp_file_path = swap_file_path.with_extension("swp.tmp");
+  fs::write(&tmp_file_path, content)?;
+  fs::rename(&tmp_file_path, &swap_file_path)
+}
+
+/// Absolute path of `path`, w/ its directory resolved (the file itself may not exist yet).
+fn get_absolute_path(path: &Path) -> PathBuf {
+  let dir = match path.parent() {
+    Some(dir) if !dir.as_os_str().is_empty() => dir,
+    _ => Path::new("."),
+  };
+  match (fs::canonicalize(dir), path.file_name()) {
+    (Ok(dir), Some(file_name)) => dir.join(file_name),
+    _ => env::current_dir()
+      .map(|current_dir| current_dir.join(path))
+      .unwrap_or_else(|_| path.to_path_buf()),
+  }
+}
+
+/// A panic while the lock is held doesn't stop the swap files from being written (or the edits of
+/// a [CollabSession] from being sent).
+pub(crate) fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+  mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_short_swap_name_is_kept() {
+    let name = "%2Fhome%2Fuser%2Fmain.rs".to_string();
+    assert_eq!(shorten_swap_name(name.clone()), name);
+  }
+
+  #[test]
+  fn test_long_swap_names_are_shortened_w_a_hash() {
+    let dir = "%2Fdir".repeat(40);
+    let name_1 = shorten_swap_name(format!("{}%2Fa.rs", dir));
+    let name_2 = shorten_swap_name(format!("{}%2Fb.rs", dir));
+    assert!(name_1.len() <= MAX_SWAP_NAME_LEN);
+    assert!(name_1.starts_with("%2Fdir%2Fdir"));
+    assert_ne!(name_1, name_2);
+    assert_eq!(name_1, shorten_swap_name(format!("{}%2Fa.rs", dir)));
+  }
+
+  #[test]
+  fn test_long_swap_name_is_cut_at_a_char_boundary() {
+    let name = shorten_swap_name("é".repeat(MAX_SWAP_NAME_LEN));
+    assert!(name.len() <= MAX_SWAP_NAME_LEN);
+    assert!(name.starts_with('é'));
+  }
+
+  #[test]
+  fn test_write_error_is_shown_once() {
+    let autosaver = Autosaver::default();
+    lock(&autosaver.inner).maybe_write_error = Some("error".into());
+    assert_eq!(autosaver.take_write_error(), Some("error".into()));
+    assert_eq!(autosaver.take_write_error(), None);
+  }
+}
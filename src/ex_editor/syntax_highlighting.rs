@@ -0,0 +1,246 @@
+/*
+ *   Copyright (c) 2022 R3BL LLC
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use std::path::Path;
+
+use syntect::{highlighting::{FontStyle,
+                             HighlightIterator,
+                             HighlightState,
+                             Highlighter,
+                             Style,
+                             Theme,
+                             ThemeSet},
+              parsing::{ParseState, ScopeStack, SyntaxDefinition, SyntaxReference, SyntaxSet}};
+
+use super::*;
+
+/// syntect's default syntax set doesn't have a TOML grammar, so one is bundled w/ the binary.
+const TOML_SYNTAX: &str = include_str!("syntaxes/TOML.sublime-syntax");
+
+const THEME_NAME: &str = "base16-ocean.dark";
+
+/// A [HighlightCache] keeps the parser state at the start of every this many lines, so that an edit
+/// only re-parses the lines from the last one of these before it.
+const STATE_INTERVAL: usize = 32;
+
+/// Style of a [StyledSpan]. Only the foreground color is used from the theme, so that the
+/// background color of the box that the text is painted in is preserved.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SpanStyle {
+  pub fg_rgb: (u8, u8, u8),
+  pub bold: bool,
+  pub underline: bool,
+}
+
+impl From<Style> for SpanStyle {
+  fn from(style: Style) -> Self {
+    Self {
+      fg_rgb: (style.foreground.r, style.foreground.g, style.foreground.b),
+      bold: style.font_style.contains(FontStyle::BOLD),
+      underline: style.font_style.contains(FontStyle::UNDERLINE),
+    }
+  }
+}
+
+/// A span of text in a line w/ the style to paint it in. The spans of a line add up to exactly the
+/// raw text of the line, so grapheme cluster indices & display columns that are computed from the
+/// raw text (eg: for the caret) are the same in the styled output.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StyledSpan {
+  pub text: String,
+  pub style: SpanStyle,
+}
+
+/// Highlighted lines of a buffer, from the first line down to the last line that has been painted
+/// so far. Parsing is stateful (eg: a block comment can span many lines) so lines are highlighted
+/// in order, and the parser state at the end of the cache is kept, so that it can be extended when
+/// the viewport scrolls down.
+///
+/// When the buffer changes, only the lines from the first row that changed are highlighted again,
+/// starting w/ the parser state that was kept for it in `states` (see [STATE_INTERVAL]). The rows
+/// that changed are found by comparing `buffer_lines` w/ the lines of the buffer, they share the
+/// chunks of the lines that weren't edited (see [LineRope::get_changed_rows]).
+#[derive(Clone, Debug, Default)]
+pub struct HighlightCache {
+  pub change_count: u64,
+  pub file_path: Option<String>,
+  pub lines: Vec<Vec<StyledSpan>>,
+  buffer_lines: LineRope,
+  /// Parser state at the start of row `index * STATE_INTERVAL`, for each of these rows in `lines`.
+  states: Vec<LineParserState>,
+  state: Option<LineParserState>,
+}
+
+impl HighlightCache {
+  /// Drop the lines from `row` on, & go back to the parser state at the start of the first one of
+  /// them that is highlighted again.
+  fn invalidate_from(&mut self, row: usize) {
+    if row >= self.lines.len() {
+      return;
+    }
+    let index = row / STATE_INTERVAL;
+    self.lines.truncate(index * STATE_INTERVAL);
+    self.states.truncate(index + 1);
+    self.state = self.states.pop();
+  }
+}
+
+/// Markdown files are highlighted w/ [MarkdownRules], all other files w/ a syntect syntax.
+#[derive(Clone, Debug)]
+enum LineParserState {
+  Syntax(ParseState, HighlightState),
+  Markdown(MarkdownState),
+}
+
+/// Syntaxes & the theme are expensive to load, so this is created once & shared by all the editor
+/// components.
+#[derive(Debug)]
+pub struct SyntaxHighlighter {
+  pub syntax_set: SyntaxSet,
+  pub theme: Theme,
+  pub markdown_rules: MarkdownRules,
+}
+
+impl Default for SyntaxHighlighter {
+  fn default() -> Self {
+    let mut builder = SyntaxSet::load_defaults_newlines().into_builder();
+    if let Ok(toml_syntax) = SyntaxDefinition::load_from_str(TOML_SYNTAX, true, None) {
+      builder.add(toml_syntax);
+    }
+    let theme = ThemeSet::load_defaults()
+      .themes
+      .remove(THEME_NAME)
+      .unwrap_or_default();
+    Self {
+      syntax_set: builder.build(),
+      markdown_rules: MarkdownRules::new(&theme),
+      theme,
+    }
+  }
+}
+
+impl SyntaxHighlighter {
+  /// Find the syntax for a file based on its extension.
+  pub fn find_syntax(&self, file_path: Option<&str>) -> Option<&SyntaxReference> {
+    let extension = Path::new(file_path?).extension()?.to_str()?;
+    self.find_syntax_by_token(extension)
+  }
+
+  /// Name of the mode that a file is edited in (shown in the status line): Markdown, the name of
+  /// its syntax, or plain text.
+  pub fn get_mode_name(&self, file_path: Option<&str>) -> &str {
+    if is_markdown_file(file_path) {
+      return "Markdown";
+    }
+    self
+      .find_syntax(file_path)
+      .map(|syntax| syntax.name.as_str())
+      .unwrap_or("Plain Text")
+  }
+
+  /// Find a syntax by file extension or name (eg: the language of a Markdown code fence). `fish`
+  /// uses the shell syntax.
+  pub fn find_syntax_by_token(&self, token: &str) -> Option<&SyntaxReference> {
+    let token = match token {
+      "" => return None,
+      "fish" => "sh",
+      it => it,
+    };
+    self.syntax_set.find_syntax_by_token(token)
+  }
+
+  /// Styled spans for the lines of `buffer` from the first one up to (and including) `last_row`.
+  /// Returns `None` if there is no syntax for the buffer's file, in which case it should be painted
+  /// as plain text. After an edit, only the lines from the edit down to `last_row` are parsed
+  /// again (see [HighlightCache]). But all the lines above `last_row` are parsed the first time
+  /// that it is painted, so large buffers (see [EditorBuffer::is_large]) aren't highlighted.
+  pub fn highlight<'a>(
+    &self, buffer: &EditorBuffer, last_row: usize, cache: &'a mut HighlightCache,
+  ) -> Option<&'a [Vec<StyledSpan>]> {
+    if buffer.is_large() {
+      return None;
+    }
+    let file_path = buffer.file_path.as_deref();
+    let maybe_syntax = match is_markdown_file(file_path) {
+      true => None,
+      false => Some(self.find_syntax(file_path)?),
+    };
+
+    if cache.file_path != buffer.file_path {
+      *cache = HighlightCache {
+        file_path: buffer.file_path.clone(),
+        ..Default::default()
+      };
+    }
+    if cache.change_count != buffer.change_count {
+      if let Some((start, ..)) = LineRope::get_changed_rows(&cache.buffer_lines, &buffer.lines) {
+        cache.invalidate_from(start);
+      }
+      cache.change_count = buffer.change_count;
+      cache.buffer_lines = buffer.lines.clone();
+    }
+
+    let highlighter = Highlighter::new(&self.theme);
+    let mut state = cache.state.take().unwrap_or_else(|| match maybe_syntax {
+      Some(syntax) => LineParserState::Syntax(
+        ParseState::new(syntax),
+        HighlightState::new(&highlighter, ScopeStack::new()),
+      ),
+      None => LineParserState::Markdown(MarkdownState::default()),
+    });
+
+    let end_row = (last_row + 1).min(buffer.lines.len());
+    let start_row = cache.lines.len();
+    for (row, line) in (start_row..end_row).zip(buffer.lines.iter_range(start_row..end_row)) {
+      if row % STATE_INTERVAL == 0 {
+        cache.states.push(state.clone());
+      }
+      let spans = match &mut state {
+        LineParserState::Syntax(parse_state, highlight_state) => {
+          self.highlight_line(line, parse_state, highlight_state, &highlighter)
+        }
+        LineParserState::Markdown(markdown_state) => {
+          self.highlight_markdown_line(line, markdown_state, &highlighter)
+        }
+      };
+      cache.lines.push(spans);
+    }
+
+    cache.state = Some(state);
+    Some(&cache.lines[..end_row.min(cache.lines.len())])
+  }
+
+  /// Styled spans for a line w/ a syntect syntax, the states are the ones at the end of the
+  /// previous line & they are updated for the next one.
+  pub fn highlight_line(
+    &self, line: &str, parse_state: &mut ParseState, highlight_state: &mut HighlightState,
+    highlighter: &Highlighter,
+  ) -> Vec<StyledSpan> {
+    // The default syntaxes expect lines to end w/ a new line.
+    let line = format!("{}\n", line);
+    let ops = parse_state
+      .parse_line(&line, &self.syntax_set)
+      .unwrap_or_default();
+    HighlightIterator::new(highlight_state, &ops, &line, highlighter)
+      .map(|(style, text)| StyledSpan {
+        text: text.trim_end_matches('\n').to_string(),
+        style: style.into(),
+      })
+      .filter(|span| !span.text.is_empty())
+      .collect()
+  }
+}
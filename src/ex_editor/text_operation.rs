@@ -0,0 +1,410 @@
+/*
+ *   Copyright (c) 2022 R3BL LLC
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use std::ops::Range;
+
+use serde::{Deserialize, Serialize};
+
+use super::*;
+
+/// One part of a [TextOperation]. Lengths & indices are in `char`s (not bytes or grapheme
+/// clusters), so that they mean the same thing in every instance.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OpComponent {
+  /// Keep the next `n` chars.
+  Retain(usize),
+  Insert(String),
+  /// Delete the next `n` chars.
+  Delete(usize),
+}
+
+/// An edit of a whole text (as in operational transformation), that goes over the text from the
+/// start to the end. Concurrent operations are merged w/ [TextOperation::transform], so that
+/// instances that apply them in different orders end up w/ the same text.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TextOperation {
+  pub components: Vec<OpComponent>,
+  /// Length of the text that the operation applies to.
+  pub base_len: usize,
+  /// Length of the text after the operation is applied.
+  pub target_len: usize,
+}
+
+impl TextOperation {
+  /// The operation that turns `old` into `new`, as a single change between their common prefix &
+  /// suffix.
+  pub fn from_diff(old: &str, new: &str) -> TextOperation {
+    let prefix_len = old
+      .chars()
+      .zip(new.chars())
+      .take_while(|(lhs, rhs)| lhs == rhs)
+      .count();
+    let old_len = old.chars().count();
+    let new_len = new.chars().count();
+    let max_suffix_len = old_len.min(new_len) - prefix_len;
+    let suffix_len = old
+      .chars()
+      .rev()
+      .zip(new.chars().rev())
+      .take(max_suffix_len)
+      .take_while(|(lhs, rhs)| lhs == rhs)
+      .count();
+
+    let mut operation = TextOperation::default();
+    operation.retain(prefix_len);
+    operation.delete(old_len - prefix_len - suffix_len);
+    operation.insert(
+      &new
+        .chars()
+        .skip(prefix_len)
+        .take(new_len - prefix_len - suffix_len)
+        .collect::<String>(),
+    );
+    operation.retain(suffix_len);
+    operation
+  }
+
+  /// Same as [TextOperation::from_diff], for `old` & `new` that take the place of each other at the
+  /// char `offset` of a text that is `base_len` chars long.
+  pub fn from_diff_at(offset: usize, old: &str, new: &str, base_len: usize) -> TextOperation {
+    let diff = TextOperation::from_diff(old, new);
+    let mut operation = TextOperation::default();
+    operation.retain(offset);
+    for component in diff.components {
+      operation.push(component);
+    }
+    operation.retain(base_len - offset - diff.base_len);
+    operation
+  }
+
+  /// The operation that turns the lines of `old` into the ones of `new` (joined w/ new lines). Only
+  /// the lines that differ are compared (see [LineRope::get_changed_rows]), so the cost of an edit
+  /// doesn't depend on the size of the text.
+  pub fn from_line_ropes(old: &LineRope, new: &LineRope) -> TextOperation {
+    let base_len = old.get_char_len();
+    let (mut start, mut old_end, mut new_end) = match LineRope::get_changed_rows(old, new) {
+      Some(rows) => rows,
+      None => return TextOperation::from_diff_at(0, "", "", base_len),
+    };
+    // Lines that were only added (or removed) are compared w/ the line next to them, so that the
+    // new line between them is part of the diff.
+    if start == old_end || start == new_end {
+      match start > 0 {
+        true => start -= 1,
+        false if old_end < old.len() && new_end < new.len() => {
+          old_end += 1;
+          new_end += 1;
+        }
+        false => {}
+      }
+    }
+    let join = |rope: &LineRope, end: usize| {
+      let lines: Vec<&str> = rope.iter_range(start..end).map(String::as_str).collect();
+      lines.join("\n")
+    };
+    TextOperation::from_diff_at(
+      old.get_char_offset(start),
+      &join(old, old_end),
+      &join(new, new_end),
+      base_len,
+    )
+  }
+
+  /// Chars of the text that the operation changes (from the first one that it inserts at or
+  /// deletes, to the last one), `None` if it doesn't change anything.
+  pub fn get_changed_range(&self) -> Option<Range<usize>> {
+    if self.is_noop() {
+      return None;
+    }
+    let start = match self.components.first() {
+      Some(OpComponent::Retain(n)) => *n,
+      _ => 0,
+    };
+    let end = match self.components.last() {
+      Some(OpComponent::Retain(n)) => self.base_len - n,
+      _ => self.base_len,
+    };
+    Some(start..end)
+  }
+
+  pub fn is_noop(&self) -> bool {
+    self
+      .components
+      .iter()
+      .all(|it| matches!(it, OpComponent::Retain(_)))
+  }
+
+  pub fn retain(&mut self, n: usize) {
+    if n == 0 {
+      return;
+    }
+    self.base_len += n;
+    self.target_len += n;
+    match self.components.last_mut() {
+      Some(OpComponent::Retain(last)) => *last += n,
+      _ => self.components.push(OpComponent::Retain(n)),
+    }
+  }
+
+  /// Inserts are kept before deletes at the same position, so that equal operations have equal
+  /// components.
+  pub fn insert(&mut self, text: &str) {
+    if text.is_empty() {
+      return;
+    }
+    self.target_len += text.chars().count();
+    let len = self.components.len();
+    match self.components.as_mut_slice() {
+      [.., OpComponent::Insert(last)] => last.push_str(text),
+      [.., OpComponent::Insert(before_last), OpComponent::Delete(_)] => before_last.push_str(text),
+      [.., OpComponent::Delete(_)] => self
+        .components
+        .insert(len - 1, OpComponent::Insert(text.into())),
+      _ => self.components.push(OpComponent::Insert(text.into())),
+    }
+  }
+
+  pub fn push(&mut self, component: OpComponent) {
+    match component {
+      OpComponent::Retain(n) => self.retain(n),
+      OpComponent::Insert(inserted) => self.insert(&inserted),
+      OpComponent::Delete(n) => self.delete(n),
+    }
+  }
+
+  pub fn delete(&mut self, n: usize) {
+    if n == 0 {
+      return;
+    }
+    self.base_len += n;
+    match self.components.last_mut() {
+      Some(OpComponent::Delete(last)) => *last += n,
+      _ => self.components.push(OpComponent::Delete(n)),
+    }
+  }
+
+  /// Apply the operation to `text`, `None` if it doesn't apply to a text of that length.
+  pub fn apply(&self, text: &str) -> Option<String> {
+    let mut chars = text.chars();
+    if text.chars().count() != self.base_len {
+      return None;
+    }
+    let mut result = String::with_capacity(text.len());
+    for component in &self.components {
+      match component {
+        OpComponent::Retain(n) => result.extend(chars.by_ref().take(*n)),
+        OpComponent::Insert(inserted) => result.push_str(inserted),
+        OpComponent::Delete(n) => {
+          chars.by_ref().take(*n).for_each(drop);
+        }
+      }
+    }
+    Some(result)
+  }
+
+  /// Where the char index `index` of the text ends up after the operation is applied (eg: to move
+  /// a caret). Text that is inserted right at `index` ends up after it.
+  pub fn transform_index(&self, index: usize) -> usize {
+    let mut new_index = index;
+    let mut pos = 0;
+    for component in &self.components {
+      if pos > index {
+        break;
+      }
+      match component {
+        OpComponent::Retain(n) => pos += n,
+        OpComponent::Insert(inserted) if pos < index => new_index += inserted.chars().count(),
+        OpComponent::Insert(_) => {}
+        OpComponent::Delete(n) => {
+          new_index -= (*n).min(index - pos);
+          pos += n;
+        }
+      }
+    }
+    new_index
+  }
+
+  /// Transform the concurrent operations `lhs` & `rhs` (which apply to the same text) into `(lhs',
+  /// rhs')`, so that applying `lhs` then `rhs'` gives the same text as applying `rhs` then `lhs'`.
+  /// Text that both insert at the same position is put in the order `lhs`, `rhs`. `None` if they
+  /// don't apply to the same text.
+  pub fn transform(
+    lhs: &TextOperation, rhs: &TextOperation,
+  ) -> Option<(TextOperation, TextOperation)> {
+    if lhs.base_len != rhs.base_len {
+      return None;
+    }
+    let mut lhs_prime = TextOperation::default();
+    let mut rhs_prime = TextOperation::default();
+    let mut lhs_components = lhs.components.iter().cloned();
+    let mut rhs_components = rhs.components.iter().cloned();
+    let mut maybe_lhs = lhs_components.next();
+    let mut maybe_rhs = rhs_components.next();
+
+    loop {
+      match (maybe_lhs.take(), maybe_rhs.take()) {
+        (None, None) => break,
+        (Some(OpComponent::Insert(inserted)), maybe_rhs_component) => {
+          lhs_prime.insert(&inserted);
+          rhs_prime.retain(inserted.chars().count());
+          maybe_lhs = lhs_components.next();
+          maybe_rhs = maybe_rhs_component;
+        }
+        (maybe_lhs_component, Some(OpComponent::Insert(inserted))) => {
+          lhs_prime.retain(inserted.chars().count());
+          rhs_prime.insert(&inserted);
+          maybe_lhs = maybe_lhs_component;
+          maybe_rhs = rhs_components.next();
+        }
+        (None, _) | (_, None) => return None,
+        (Some(lhs_component), Some(rhs_component)) => {
+          let len = get_len(&lhs_component).min(get_len(&rhs_component));
+          match (&lhs_component, &rhs_component) {
+            (OpComponent::Retain(_), OpComponent::Retain(_)) => {
+              lhs_prime.retain(len);
+              rhs_prime.retain(len);
+            }
+            (OpComponent::Delete(_), OpComponent::Retain(_)) => lhs_prime.delete(len),
+            (OpComponent::Retain(_), OpComponent::Delete(_)) => rhs_prime.delete(len),
+            _ => {} // Both delete the same text.
+          }
+          maybe_lhs = shorten(lhs_component, len).or_else(|| lhs_components.next());
+          maybe_rhs = shorten(rhs_component, len).or_else(|| rhs_components.next());
+        }
+      }
+    }
+    Some((lhs_prime, rhs_prime))
+  }
+}
+
+/// Length of a retain or delete.
+fn get_len(component: &OpComponent) -> usize {
+  match component {
+    OpComponent::Retain(n) | OpComponent::Delete(n) => *n,
+    OpComponent::Insert(inserted) => inserted.chars().count(),
+  }
+}
+
+/// What is left of a retain or delete after its first `len` chars, if anything.
+fn shorten(component: OpComponent, len: usize) -> Option<OpComponent> {
+  match component {
+    OpComponent::Retain(n) if n > len => Some(OpComponent::Retain(n - len)),
+    OpComponent::Delete(n) if n > len => Some(OpComponent::Delete(n - len)),
+    _ => None,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Replace the `delete_len` chars at `offset` of a text that is `base_len` chars long w/ `text`.
+  fn make_op(base_len: usize, offset: usize, delete_len: usize, text: &str) -> TextOperation {
+    let mut op = TextOperation::default();
+    op.retain(offset);
+    op.insert(text);
+    op.delete(delete_len);
+    op.retain(base_len - offset - delete_len);
+    op
+  }
+
+  /// Apply `lhs` & `rhs` in both orders, & check that they give the same text, which is returned.
+  fn apply_both_orders(text: &str, lhs: &TextOperation, rhs: &TextOperation) -> String {
+    let (lhs_prime, rhs_prime) = TextOperation::transform(lhs, rhs).unwrap();
+    let lhs_first = rhs_prime.apply(&lhs.apply(text).unwrap()).unwrap();
+    let rhs_first = lhs_prime.apply(&rhs.apply(text).unwrap()).unwrap();
+    assert_eq!(lhs_first, rhs_first, "lhs: {:?}, rhs: {:?}", lhs, rhs);
+    lhs_first
+  }
+
+  #[test]
+  fn test_transform_insert_at_same_offset() {
+    let text = "abcd";
+    let lhs = make_op(4, 2, 0, "X");
+    let rhs = make_op(4, 2, 0, "Y");
+    assert_eq!(apply_both_orders(text, &lhs, &rhs), "abXYcd");
+    assert_eq!(apply_both_orders(text, &rhs, &lhs), "abYXcd");
+  }
+
+  #[test]
+  fn test_transform_overlapping_deletes() {
+    let text = "abcdefgh";
+    let lhs = make_op(8, 1, 4, "");
+    let rhs = make_op(8, 3, 4, "");
+    assert_eq!(apply_both_orders(text, &lhs, &rhs), "ah");
+    assert_eq!(apply_both_orders(text, &lhs, &lhs), "afgh");
+  }
+
+  #[test]
+  fn test_transform_insert_inside_deleted_range() {
+    let text = "abcdefgh";
+    let lhs = make_op(8, 4, 0, "XY");
+    let rhs = make_op(8, 2, 4, "");
+    assert_eq!(apply_both_orders(text, &lhs, &rhs), "abXYgh");
+    assert_eq!(apply_both_orders(text, &rhs, &lhs), "abXYgh");
+  }
+
+  #[test]
+  fn test_transform_all_pairs_of_edits() {
+    let text = "aé🦜d";
+    let len = text.chars().count();
+    let mut ops = vec![];
+    for offset in 0..=len {
+      for delete_len in 0..=len - offset {
+        for inserted in ["", "X", "YZ"] {
+          ops.push(make_op(len, offset, delete_len, inserted));
+        }
+      }
+    }
+    for lhs in &ops {
+      for rhs in &ops {
+        apply_both_orders(text, lhs, rhs);
+      }
+    }
+  }
+
+  #[test]
+  fn test_transform_different_base_len() {
+    let lhs = make_op(4, 0, 0, "X");
+    let rhs = make_op(5, 0, 0, "Y");
+    assert_eq!(TextOperation::transform(&lhs, &rhs), None);
+  }
+
+  #[test]
+  fn test_transform_index() {
+    // Insert "XY" at 2 of "abcdef".
+    let op = make_op(6, 2, 0, "XY");
+    assert_eq!(op.transform_index(0), 0);
+    assert_eq!(op.transform_index(2), 2);
+    assert_eq!(op.transform_index(3), 5);
+    assert_eq!(op.transform_index(6), 8);
+
+    // Delete "cde" of "abcdef".
+    let op = make_op(6, 2, 3, "");
+    assert_eq!(op.transform_index(1), 1);
+    assert_eq!(op.transform_index(2), 2);
+    assert_eq!(op.transform_index(4), 2);
+    assert_eq!(op.transform_index(5), 2);
+    assert_eq!(op.transform_index(6), 3);
+
+    // Replace "cd" of "abcdef" w/ "X".
+    let op = make_op(6, 2, 2, "X");
+    assert_eq!(op.transform_index(3), 3);
+    assert_eq!(op.transform_index(4), 3);
+    assert_eq!(op.transform_index(5), 4);
+  }
+}
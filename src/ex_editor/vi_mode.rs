@@ -0,0 +1,684 @@
+/*
+ *   Copyright (c) 2022 R3BL LLC
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use std::fmt::{Display, Formatter};
+
+use crossterm::event::*;
+use r3bl_rs_utils::*;
+
+use super::*;
+
+/// Counts are capped, so that a typo like `99999999j` doesn't hang the editor.
+const MAX_COUNT: usize = 9999;
+
+/// Mode of the vi key bindings (see [ViState]).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ViMode {
+  #[default]
+  Normal,
+  Insert,
+  Visual,
+  CommandLine,
+}
+
+impl Display for ViMode {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    let name = match self {
+      ViMode::Normal => "NORMAL",
+      ViMode::Insert => "INSERT",
+      ViMode::Visual => "VISUAL",
+      ViMode::CommandLine => "COMMAND",
+    };
+    write!(f, "{}", name)
+  }
+}
+
+/// Operator that waits for a motion (eg: the `d` in `dw`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ViOperator {
+  Delete,
+  Change,
+  Yank,
+}
+
+/// Where a motion goes to, & how an operator uses it (eg: `dj` deletes both lines, `de` deletes
+/// the last char of the word).
+#[derive(Clone, Copy, Debug)]
+struct ViMotion {
+  target: CaretPos,
+  is_linewise: bool,
+  is_inclusive: bool,
+}
+
+impl ViMotion {
+  fn exclusive(target: CaretPos) -> Self {
+    ViMotion {
+      target,
+      is_linewise: false,
+      is_inclusive: false,
+    }
+  }
+
+  fn inclusive(target: CaretPos) -> Self {
+    ViMotion {
+      is_inclusive: true,
+      ..ViMotion::exclusive(target)
+    }
+  }
+
+  fn linewise(target: CaretPos) -> Self {
+    ViMotion {
+      is_linewise: true,
+      ..ViMotion::exclusive(target)
+    }
+  }
+}
+
+/// Vi style modal key bindings, a layer in front of the default key bindings of an
+/// [EditorComponent]. Input events are turned into [EditorAction]s depending on the [ViMode]:
+/// - Normal      : motions `h j k l w b e 0 $ gg G`, operators `d c y` w/ a motion (or doubled
+///   for whole lines), `x D C p P u Ctrl+R`, `i a I A o O` to insert, `v` for visual & `:` for
+///   the command line, `za zM zR` to toggle a fold, fold all & open all. Motions & operators take
+///   a count (eg: `3dw`, `d3w`)
+/// - Insert      : the default key bindings, Esc goes back to normal mode
+/// - Visual      : motions extend the selection, `d x c y` operate on it
+/// - CommandLine : `:w`, `:q`, `:q!`, `:wq`, `:x` & `:<line>`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ViState {
+  pub mode: ViMode,
+  /// Count typed so far (eg: the `3` in `3dw`).
+  pub maybe_count: Option<usize>,
+  /// Operator that waits for a motion, w/ the count typed before it.
+  pub maybe_operator: Option<(ViOperator, usize)>,
+  /// `g` was typed, the 2nd `g` of `gg` is expected.
+  pub is_g_pending: bool,
+  /// `z` was typed, the 2nd key of a fold command (eg: `za`) is expected.
+  pub is_z_pending: bool,
+  /// Text typed after `:` in command line mode.
+  pub command_line: String,
+}
+
+impl ViState {
+  /// Mode (or the command line while it is being typed), for the status line.
+  pub fn get_status(&self) -> String {
+    match self.mode {
+      ViMode::CommandLine => format!(":{}", self.command_line),
+      mode => mode.to_string(),
+    }
+  }
+
+  pub fn handle_event(
+    &mut self, input_event: &TWInputEvent, id: &str, buffer: &EditorBuffer,
+    maybe_clipboard: Option<&str>,
+  ) -> KeyBindingOutcome {
+    match self.mode {
+      ViMode::Insert => self.handle_insert_mode_event(input_event, id, buffer),
+      ViMode::CommandLine => self.handle_command_line_mode_event(input_event, id),
+      ViMode::Normal | ViMode::Visual => {
+        let typed_char = match input_event {
+          TWInputEvent::DisplayableKeypress(typed_char) => *typed_char,
+          TWInputEvent::NonDisplayableKeypress(key_event) => {
+            match (key_event.code, key_event.modifiers) {
+              (KeyCode::Esc, KeyModifiers::NONE) => return self.escape(id),
+              (KeyCode::Char('r'), KeyModifiers::CONTROL) => {
+                let count = self.maybe_count.take().unwrap_or(1);
+                return KeyBindingOutcome::Dispatch(vec![EditorAction::Redo(id.into()); count]);
+              }
+              (KeyCode::Enter, KeyModifiers::NONE) => 'j',
+              (KeyCode::Backspace, KeyModifiers::NONE) => 'h',
+              (KeyCode::Delete, KeyModifiers::NONE) => 'x',
+              _ => return KeyBindingOutcome::NotHandled,
+            }
+          }
+          _ => return KeyBindingOutcome::NotHandled,
+        };
+        self.handle_normal_mode_char(typed_char, id, buffer, maybe_clipboard)
+      }
+    }
+  }
+
+  /// Back to normal mode, & drop anything that is pending & the selection.
+  fn escape(&mut self, id: &str) -> KeyBindingOutcome {
+    *self = ViState::default();
+    KeyBindingOutcome::Dispatch(vec![EditorAction::ClearExtraCarets(id.into())])
+  }
+
+  fn handle_insert_mode_event(
+    &mut self, input_event: &TWInputEvent, id: &str, buffer: &EditorBuffer,
+  ) -> KeyBindingOutcome {
+    match input_event {
+      TWInputEvent::NonDisplayableKeypress(KeyEvent {
+        code: KeyCode::Esc,
+        modifiers: KeyModifiers::NONE,
+      }) => {
+        self.mode = ViMode::Normal;
+        // Like vi, the caret moves back onto the last typed char.
+        match buffer.caret.col > 0 {
+          true => KeyBindingOutcome::Dispatch(vec![EditorAction::MoveCaret(
+            id.into(),
+            CaretDirection::Left,
+          )]),
+          false => KeyBindingOutcome::Dispatch(vec![]),
+        }
+      }
+      _ => KeyBindingOutcome::NotHandled,
+    }
+  }
+
+  fn handle_command_line_mode_event(
+    &mut self, input_event: &TWInputEvent, id: &str,
+  ) -> KeyBindingOutcome {
+    match input_event {
+      TWInputEvent::DisplayableKeypress(typed_char) => self.command_line.push(*typed_char),
+      TWInputEvent::NonDisplayableKeypress(key_event) => match key_event.code {
+        KeyCode::Backspace if self.command_line.is_empty() => self.mode = ViMode::Normal,
+        KeyCode::Backspace => {
+          self.command_line.pop();
+        }
+        KeyCode::Esc => self.mode = ViMode::Normal,
+        KeyCode::Enter => {
+          self.mode = ViMode::Normal;
+          let command_line = std::mem::take(&mut self.command_line);
+          return run_command_line(command_line.trim(), id);
+        }
+        _ => {}
+      },
+      _ => return KeyBindingOutcome::NotHandled,
+    }
+    KeyBindingOutcome::Dispatch(vec![])
+  }
+
+  fn handle_normal_mode_char(
+    &mut self, typed_char: char, id: &str, buffer: &EditorBuffer, maybe_clipboard: Option<&str>,
+  ) -> KeyBindingOutcome {
+    // Counts (a leading `0` is a motion).
+    if let Some(digit) = typed_char.to_digit(10) {
+      if digit > 0 || self.maybe_count.is_some() {
+        let count = self.maybe_count.unwrap_or(0) * 10 + digit as usize;
+        self.maybe_count = Some(count.min(MAX_COUNT));
+        return KeyBindingOutcome::Dispatch(vec![]);
+      }
+    }
+
+    let maybe_count = self.maybe_count.take();
+    let operator_count = self.maybe_operator.map(|(_, it)| it).unwrap_or(1);
+    let count = (maybe_count.unwrap_or(1) * operator_count).min(MAX_COUNT);
+    let caret = buffer.caret;
+    let last_row = buffer.line_count() - 1;
+
+    // Folds.
+    if std::mem::take(&mut self.is_z_pending) {
+      let maybe_action = match typed_char {
+        'a' => Some(EditorAction::ToggleFold(id.into())),
+        'M' => Some(EditorAction::FoldAll(id.into())),
+        'R' => Some(EditorAction::UnfoldAll(id.into())),
+        _ => None,
+      };
+      return KeyBindingOutcome::Dispatch(maybe_action.into_iter().collect());
+    }
+
+    // Motions.
+    let is_g_pending = std::mem::take(&mut self.is_g_pending);
+    let maybe_motion = match typed_char {
+      'g' if is_g_pending => Some(ViMotion::linewise(CaretPos {
+        row: maybe_count.unwrap_or(1).saturating_sub(1).min(last_row),
+        col: 0,
+      })),
+      'g' => {
+        self.is_g_pending = true;
+        self.maybe_count = maybe_count;
+        return KeyBindingOutcome::Dispatch(vec![]);
+      }
+      'G' => Some(ViMotion::linewise(CaretPos {
+        row: maybe_count
+          .map(|it| it.saturating_sub(1))
+          .unwrap_or(last_row)
+          .min(last_row),
+        col: 0,
+      })),
+      // Like vi, `cw` changes to the end of the word (not up to the next one).
+      'w' if matches!(self.maybe_operator, Some((ViOperator::Change, _))) => {
+        get_motion(buffer, caret, 'e', count)
+      }
+      _ => get_motion(buffer, caret, typed_char, count),
+    };
+    if let Some(motion) = maybe_motion {
+      let id = id.to_string();
+      return match self.maybe_operator.take() {
+        Some((operator, _)) => self.apply_operator(operator, &id, buffer, caret, motion),
+        None if self.mode == ViMode::Visual => {
+          KeyBindingOutcome::Dispatch(vec![EditorAction::ExtendSelectionTo(id, motion.target)])
+        }
+        None => KeyBindingOutcome::Dispatch(vec![EditorAction::SetCaret(id, motion.target)]),
+      };
+    }
+
+    let maybe_operator = match typed_char {
+      'd' => Some(ViOperator::Delete),
+      'c' => Some(ViOperator::Change),
+      'y' => Some(ViOperator::Yank),
+      'x' if self.mode == ViMode::Visual => Some(ViOperator::Delete),
+      _ => None,
+    };
+
+    if self.mode == ViMode::Visual {
+      // Like vi, the selection includes the grapheme at its end (& the one at the caret when
+      // nothing else is selected).
+      let (start, end) = buffer.get_selection().unwrap_or((caret, caret));
+      return match maybe_operator {
+        Some(operator) => {
+          self.mode = ViMode::Normal;
+          self.apply_operator(operator, id, buffer, start, ViMotion::inclusive(end))
+        }
+        None if typed_char == 'v' => self.escape(id),
+        None => KeyBindingOutcome::Dispatch(vec![]),
+      };
+    }
+
+    // Operators, doubled for whole lines (eg: `dd`).
+    if let Some(operator) = maybe_operator {
+      return match self.maybe_operator.take() {
+        Some((pending_operator, _)) if pending_operator == operator => {
+          let target = CaretPos {
+            row: (caret.row + count - 1).min(last_row),
+            col: 0,
+          };
+          self.apply_operator(operator, id, buffer, caret, ViMotion::linewise(target))
+        }
+        Some(_) => KeyBindingOutcome::Dispatch(vec![]),
+        None => {
+          self.maybe_operator = Some((operator, count));
+          KeyBindingOutcome::Dispatch(vec![])
+        }
+      };
+    }
+
+    // Any other key cancels a pending operator.
+    if self.maybe_operator.take().is_some() {
+      return KeyBindingOutcome::Dispatch(vec![]);
+    }
+
+    let line_len = buffer.line_len(caret.row);
+    let line_end = CaretPos {
+      row: caret.row,
+      col: line_len,
+    };
+    let move_caret = |direction| EditorAction::MoveCaret(id.into(), direction);
+    let actions = match typed_char {
+      'x' => {
+        let target = CaretPos {
+          row: caret.row,
+          col: (caret.col + count).min(line_len),
+        };
+        return self.apply_operator(
+          ViOperator::Delete,
+          id,
+          buffer,
+          caret,
+          ViMotion::exclusive(target),
+        );
+      }
+      'D' => {
+        return self.apply_operator(
+          ViOperator::Delete,
+          id,
+          buffer,
+          caret,
+          ViMotion::exclusive(line_end),
+        );
+      }
+      'C' => {
+        return self.apply_operator(
+          ViOperator::Change,
+          id,
+          buffer,
+          caret,
+          ViMotion::exclusive(line_end),
+        );
+      }
+      'p' | 'P' => match maybe_clipboard {
+        Some(clipboard) => get_paste_actions(id, buffer, clipboard, count, typed_char == 'P'),
+        None => vec![],
+      },
+      'u' => vec![EditorAction::Undo(id.into()); count],
+      'i' => vec![],
+      'a' if caret.col < line_len => vec![move_caret(CaretDirection::Right)],
+      'a' => vec![],
+      'A' => vec![move_caret(CaretDirection::LineEnd)],
+      'I' => vec![move_caret(CaretDirection::LineStart)],
+      'o' => vec![
+        move_caret(CaretDirection::LineEnd),
+        EditorAction::InsertNewLine(id.into()),
+      ],
+      'O' => {
+        // The new line is indented like the caret's line.
+        let indent = get_leading_whitespace(&buffer.lines[caret.row]);
+        let mut actions = vec![
+          move_caret(CaretDirection::LineStart),
+          EditorAction::InsertNewLine(id.into()),
+          move_caret(CaretDirection::Up),
+        ];
+        if !indent.is_empty() {
+          actions.push(EditorAction::Paste(id.into(), indent.into()));
+        }
+        actions
+      }
+      'v' => {
+        self.mode = ViMode::Visual;
+        vec![]
+      }
+      'z' => {
+        self.is_z_pending = true;
+        vec![]
+      }
+      ':' => {
+        self.mode = ViMode::CommandLine;
+        self.command_line.clear();
+        vec![]
+      }
+      _ => vec![],
+    };
+    if matches!(typed_char, 'i' | 'a' | 'A' | 'I' | 'o' | 'O') {
+      self.mode = ViMode::Insert;
+    }
+    KeyBindingOutcome::Dispatch(actions)
+  }
+
+  /// Run `operator` on the text between `from` & the target of `motion`. The text is put in the
+  /// clipboard (w/ [EditorAction::SetClipboard], so whole lines end w/ a new line & are pasted as
+  /// lines), unless there is none (eg: `x` on an empty line), so the clipboard isn't cleared.
+  fn apply_operator(
+    &mut self, operator: ViOperator, id: &str, buffer: &EditorBuffer, from: CaretPos,
+    motion: ViMotion,
+  ) -> KeyBindingOutcome {
+    let id = id.to_string();
+    let (start, end) = (from.min(motion.target), from.max(motion.target));
+    let (start, end, text) = match motion.is_linewise {
+      true => get_linewise_range(buffer, start.row, end.row, operator == ViOperator::Change),
+      false => {
+        let end = match motion.is_inclusive {
+          true => CaretPos {
+            row: end.row,
+            col: (end.col + 1).min(buffer.line_len(end.row)),
+          },
+          false => end,
+        };
+        (start, end, buffer.get_text_in_range(start, end))
+      }
+    };
+
+    let mut actions = vec![];
+    match operator {
+      ViOperator::Yank => {
+        // Linewise yanks (eg: `yj`) don't move the caret.
+        let caret = match motion.is_linewise {
+          true => from,
+          false => start,
+        };
+        actions.push(EditorAction::SetCaret(id.clone(), caret));
+      }
+      ViOperator::Delete | ViOperator::Change => {
+        actions.push(EditorAction::SetCaret(id.clone(), start));
+        if !text.is_empty() {
+          actions.push(EditorAction::ExtendSelectionTo(id.clone(), end));
+          actions.push(EditorAction::Cut(id.clone()));
+        }
+      }
+    }
+    // After deleting whole lines, the caret goes to the start of the line that took their place.
+    if operator == ViOperator::Delete && motion.is_linewise {
+      let deleted_line_count = end.row - start.row;
+      let row = from.min(motion.target).row;
+      let row = row.min(buffer.line_count().saturating_sub(deleted_line_count + 1));
+      actions.push(EditorAction::SetCaret(id.clone(), CaretPos { row, col: 0 }));
+    }
+    if operator == ViOperator::Change {
+      self.mode = ViMode::Insert;
+    }
+    if !text.is_empty() {
+      actions.push(EditorAction::SetClipboard(text));
+    }
+    KeyBindingOutcome::Dispatch(actions)
+  }
+}
+
+/// Target of the motion for `typed_char` from `from`, repeated `count` times. `h` & `l` don't
+/// leave the line (like vi).
+fn get_motion(
+  buffer: &EditorBuffer, from: CaretPos, typed_char: char, count: usize,
+) -> Option<ViMotion> {
+  let move_by =
+    |direction| (0..count).fold(from, |pos, _| buffer.get_moved_caret_pos(pos, direction));
+  let motion = match typed_char {
+    'h' => ViMotion::exclusive(CaretPos {
+      row: from.row,
+      col: from.col.saturating_sub(count),
+    }),
+    'l' => ViMotion::exclusive(CaretPos {
+      row: from.row,
+      col: (from.col + count).min(buffer.line_len(from.row)),
+    }),
+    'j' => ViMotion::linewise(move_by(CaretDirection::Down)),
+    'k' => ViMotion::linewise(move_by(CaretDirection::Up)),
+    'w' => ViMotion::exclusive(move_by(CaretDirection::WordForward)),
+    'b' => ViMotion::exclusive(move_by(CaretDirection::WordBackward)),
+    'e' => ViMotion::inclusive(move_by(CaretDirection::WordEnd)),
+    '0' => ViMotion::exclusive(buffer.get_moved_caret_pos(from, CaretDirection::LineStart)),
+    '$' => ViMotion::exclusive(buffer.get_moved_caret_pos(from, CaretDirection::LineEnd)),
+    _ => return None,
+  };
+  Some(motion)
+}
+
+/// Range that a linewise operator (eg: `dd`) removes for the rows `first..=last`, & the text of
+/// those lines (ending w/ a new line). `c` keeps an empty line to type in.
+fn get_linewise_range(
+  buffer: &EditorBuffer, first: usize, last: usize, is_change: bool,
+) -> (CaretPos, CaretPos, String) {
+  let first_line_start = CaretPos { row: first, col: 0 };
+  let last_line_end = CaretPos {
+    row: last,
+    col: buffer.line_len(last),
+  };
+  let text = buffer.get_text_in_range(first_line_start, last_line_end) + "\n";
+  if is_change {
+    (first_line_start, last_line_end, text)
+  } else if last + 1 < buffer.line_count() {
+    (
+      first_line_start,
+      CaretPos {
+        row: last + 1,
+        col: 0,
+      },
+      text,
+    )
+  } else if first > 0 {
+    // The last line has no new line after it, take the one before it instead.
+    let prev_line_end = CaretPos {
+      row: first - 1,
+      col: buffer.line_len(first - 1),
+    };
+    (prev_line_end, last_line_end, text)
+  } else {
+    (first_line_start, last_line_end, text)
+  }
+}
+
+/// `p` pastes after the caret & `P` before it. Whole lines (text that ends w/ a new line, eg: from
+/// `yy`) are pasted below or above the line of the caret instead.
+fn get_paste_actions(
+  id: &str, buffer: &EditorBuffer, clipboard: &str, count: usize, is_before: bool,
+) -> Vec<EditorAction> {
+  let id = id.to_string();
+  let caret = buffer.caret;
+  match clipboard.strip_suffix('\n') {
+    Some(lines) => {
+      let lines = vec![lines; count].join("\n");
+      let line_start = CaretPos {
+        row: caret.row,
+        col: 0,
+      };
+      match is_before {
+        true => vec![
+          EditorAction::SetCaret(id.clone(), line_start),
+          EditorAction::Paste(id.clone(), lines + "\n"),
+          EditorAction::SetCaret(id, line_start),
+        ],
+        false => vec![
+          EditorAction::SetCaret(
+            id.clone(),
+            CaretPos {
+              row: caret.row,
+              col: buffer.line_len(caret.row),
+            },
+          ),
+          EditorAction::Paste(id.clone(), "\n".to_string() + &lines),
+          EditorAction::SetCaret(
+            id,
+            CaretPos {
+              row: caret.row + 1,
+              col: 0,
+            },
+          ),
+        ],
+      }
+    }
+    None => {
+      let mut actions = vec![];
+      if !is_before && caret.col < buffer.line_len(caret.row) {
+        actions.push(EditorAction::MoveCaret(id.clone(), CaretDirection::Right));
+      }
+      actions.push(EditorAction::Paste(id, clipboard.repeat(count)));
+      actions
+    }
+  }
+}
+
+fn run_command_line(command_line: &str, id: &str) -> KeyBindingOutcome {
+  match command_line {
+    "" => KeyBindingOutcome::Dispatch(vec![]),
+    "w" => KeyBindingOutcome::Run(EditorCommand::Write),
+    "q" => KeyBindingOutcome::Run(EditorCommand::Quit),
+    "q!" => KeyBindingOutcome::Run(EditorCommand::ForceQuit),
+    "wq" | "x" => KeyBindingOutcome::Run(EditorCommand::WriteQuit),
+    _ => match command_line.parse::<usize>() {
+      Ok(line_number) => KeyBindingOutcome::Dispatch(vec![EditorAction::SetCaret(
+        id.into(),
+        CaretPos {
+          row: line_number.saturating_sub(1),
+          col: 0,
+        },
+      )]),
+      Err(_) => KeyBindingOutcome::Dispatch(vec![EditorAction::ShowMessage(format!(
+        "Not an editor command: {}",
+        command_line
+      ))]),
+    },
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Actions dispatched for `typed_chars`, starting in the mode of `vi_state`.
+  fn type_chars(
+    vi_state: &mut ViState, buffer: &EditorBuffer, typed_chars: &str,
+  ) -> Vec<EditorAction> {
+    let mut actions = vec![];
+    for typed_char in typed_chars.chars() {
+      let input_event = TWInputEvent::DisplayableKeypress(typed_char);
+      if let KeyBindingOutcome::Dispatch(it) =
+        vi_state.handle_event(&input_event, "id", buffer, None)
+      {
+        actions.extend(it);
+      }
+    }
+    actions
+  }
+
+  fn get_clipboard(actions: &[EditorAction]) -> Option<&str> {
+    actions.iter().find_map(|it| match it {
+      EditorAction::SetClipboard(text) => Some(text.as_str()),
+      _ => None,
+    })
+  }
+
+  fn has_cut(actions: &[EditorAction]) -> bool {
+    actions.iter().any(|it| matches!(it, EditorAction::Cut(_)))
+  }
+
+  #[test]
+  fn test_x_deletes_the_char_at_the_caret() {
+    let buffer = EditorBuffer::from_content("abc");
+    let actions = type_chars(&mut ViState::default(), &buffer, "x");
+    assert!(has_cut(&actions));
+    assert_eq!(get_clipboard(&actions), Some("a"));
+  }
+
+  #[test]
+  fn test_x_on_empty_line_keeps_the_clipboard() {
+    let buffer = EditorBuffer::from_content("");
+    let actions = type_chars(&mut ViState::default(), &buffer, "x");
+    assert!(!has_cut(&actions));
+    assert_eq!(get_clipboard(&actions), None);
+  }
+
+  #[test]
+  fn test_d_at_line_end_keeps_the_clipboard() {
+    let mut buffer = EditorBuffer::from_content("abc");
+    buffer.caret = CaretPos { row: 0, col: 3 };
+    let actions = type_chars(&mut ViState::default(), &buffer, "D");
+    assert!(!has_cut(&actions));
+    assert_eq!(get_clipboard(&actions), None);
+  }
+
+  #[test]
+  fn test_dd_yanks_the_line_w_its_new_line() {
+    let buffer = EditorBuffer::from_content("abc\ndef");
+    let actions = type_chars(&mut ViState::default(), &buffer, "dd");
+    assert!(has_cut(&actions));
+    assert_eq!(get_clipboard(&actions), Some("abc\n"));
+  }
+
+  #[test]
+  fn test_visual_yank_includes_the_char_at_the_end() {
+    let mut buffer = EditorBuffer::from_content("abcdef");
+    buffer.selection_anchor = Some(CaretPos { row: 0, col: 1 });
+    buffer.caret = CaretPos { row: 0, col: 3 };
+    let mut vi_state = ViState {
+      mode: ViMode::Visual,
+      ..Default::default()
+    };
+    let actions = type_chars(&mut vi_state, &buffer, "y");
+    assert_eq!(get_clipboard(&actions), Some("bcd"));
+    assert_eq!(vi_state.mode, ViMode::Normal);
+  }
+
+  #[test]
+  fn test_visual_delete_wo_selection_deletes_the_char_at_the_caret() {
+    let mut buffer = EditorBuffer::from_content("abc");
+    buffer.caret = CaretPos { row: 0, col: 1 };
+    let mut vi_state = ViState {
+      mode: ViMode::Visual,
+      ..Default::default()
+    };
+    let actions = type_chars(&mut vi_state, &buffer, "d");
+    assert!(has_cut(&actions));
+    assert_eq!(get_clipboard(&actions), Some("b"));
+  }
+}
@@ -0,0 +1,224 @@
+/*
+ *   Copyright (c) 2022 R3BL LLC
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use std::fmt::{Display, Formatter};
+
+use super::*;
+
+/// Scroll offset of the viewport (the part of an [EditorBuffer] that is visible inside of a
+/// [TWBox](r3bl_rs_utils::TWBox)). `row` is the index of the first visible line and `col` is the
+/// first visible display column. When the lines are soft wrapped, `col` is always 0 and the first
+/// visible line is shown from its first visual line.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ScrollOffset {
+  pub row: usize,
+  pub col: usize,
+}
+
+impl Display for ScrollOffset {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(f, "[row: {}, col: {}]", self.row, self.col)
+  }
+}
+
+impl ScrollOffset {
+  /// Adjust the offset (by as little as possible) so that the caret is inside of the viewport.
+  /// - `caret_row` & `caret_display_col` are the position of the caret in the buffer.
+  /// - `caret_width` is the display width of the grapheme cluster under the caret (at least 1).
+  /// - `viewport_rows` & `viewport_cols` are the size of the box that the buffer is painted in.
+  pub fn scroll_to_caret(
+    &mut self, caret_row: usize, caret_display_col: usize, caret_width: usize,
+    viewport_rows: usize, viewport_cols: usize,
+  ) {
+    // Vertical.
+    if caret_row < self.row {
+      self.row = caret_row;
+    } else if viewport_rows > 0 && caret_row >= self.row + viewport_rows {
+      self.row = caret_row + 1 - viewport_rows;
+    }
+
+    self.scroll_to_caret_col(caret_display_col, caret_width, viewport_cols);
+  }
+
+  /// The horizontal part of [ScrollOffset::scroll_to_caret].
+  pub fn scroll_to_caret_col(
+    &mut self, caret_display_col: usize, caret_width: usize, viewport_cols: usize,
+  ) {
+    let caret_width = caret_width.max(1);
+    if caret_display_col < self.col {
+      self.col = caret_display_col;
+    } else if viewport_cols > 0 && caret_display_col + caret_width > self.col + viewport_cols {
+      self.col = (caret_display_col + caret_width).saturating_sub(viewport_cols);
+    }
+  }
+
+  /// Same as [ScrollOffset::scroll_to_caret], for lines that are soft wrapped at `wrap_width`. Each
+  /// line takes up at least one row, so only the lines between the first visible line and the
+  /// caret are wrapped.
+  pub fn scroll_to_caret_wrapped(
+    &mut self, buffer: &EditorBuffer, caret: CaretPos, viewport_rows: usize, wrap_width: usize,
+  ) {
+    self.col = 0;
+    if caret.row < self.row {
+      self.row = caret.row;
+      return;
+    }
+    if caret.row >= self.row + viewport_rows {
+      self.row = caret.row + 1 - viewport_rows.max(1);
+    }
+
+    let caret_line = buffer.lines[caret.row].as_str();
+    let caret_visual_index = wrapped_line_index_of_display_col(
+      &caret_line.wrap_to_display_cols(wrap_width),
+      caret_line.display_col_of_grapheme(caret.col),
+    );
+    let wrapped_rows: Vec<usize> = buffer
+      .lines
+      .iter_range(self.row..caret.row)
+      .map(|line| line.wrap_to_display_cols(wrap_width).len())
+      .collect();
+    let mut rows_above_caret: usize = wrapped_rows.iter().sum::<usize>() + caret_visual_index;
+    for rows in wrapped_rows {
+      if rows_above_caret < viewport_rows {
+        break;
+      }
+      rows_above_caret -= rows;
+      self.row += 1;
+    }
+  }
+
+  /// Same as [ScrollOffset::scroll_to_caret] (vertically), for a buffer w/ folds: hidden lines take
+  /// up no rows & each fold takes up one for its placeholder. Lines are soft wrapped if
+  /// `maybe_wrap_width` is set.
+  pub fn scroll_to_caret_folded(
+    &mut self, buffer: &EditorBuffer, caret: CaretPos, viewport_rows: usize,
+    maybe_wrap_width: Option<usize>,
+  ) {
+    let get_line_rows = |row: usize| -> usize {
+      let line_rows = match maybe_wrap_width {
+        Some(wrap_width) => buffer.lines[row].wrap_to_display_cols(wrap_width).len(),
+        None => 1,
+      };
+      line_rows + usize::from(buffer.get_fold_at(row).is_some())
+    };
+
+    let caret_row = buffer.get_unhidden_row(caret.row);
+    self.row = buffer.get_unhidden_row(self.row);
+    if caret_row <= self.row {
+      self.row = caret_row;
+      return;
+    }
+
+    // Walk up from the caret's line, while the lines above it fit in the viewport.
+    let mut rows_above_caret = match maybe_wrap_width {
+      Some(wrap_width) => {
+        let caret_line = buffer.lines[caret_row].as_str();
+        wrapped_line_index_of_display_col(
+          &caret_line.wrap_to_display_cols(wrap_width),
+          caret_line.display_col_of_grapheme(caret.col),
+        )
+      }
+      None => 0,
+    };
+    let mut first_row = caret_row;
+    while let Some(prev_row) = buffer.get_prev_unhidden_row(first_row) {
+      let rows = get_line_rows(prev_row);
+      if prev_row < self.row || rows_above_caret + rows >= viewport_rows {
+        break;
+      }
+      rows_above_caret += rows;
+      first_row = prev_row;
+    }
+    self.row = self.row.max(first_row);
+  }
+}
+
+/// A row of the viewport that shows the display columns `start_col..end_col` of line `row` of the
+/// buffer. `is_end_of_line` is set when nothing of the line comes after this row (so the caret at
+/// the end of the line is painted in it). If `folded_line_count` isn't 0, then the row is the
+/// placeholder of the fold after line `row` (which hides that many lines) instead.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VisualLine {
+  pub row: usize,
+  pub start_col: usize,
+  pub end_col: usize,
+  pub is_end_of_line: bool,
+  pub folded_line_count: usize,
+}
+
+impl VisualLine {
+  pub fn is_fold_placeholder(&self) -> bool { self.folded_line_count > 0 }
+
+  pub fn contains(&self, row: usize, display_col: usize) -> bool {
+    row == self.row
+      && !self.is_fold_placeholder()
+      && display_col >= self.start_col
+      && (display_col < self.end_col || self.is_end_of_line)
+  }
+}
+
+/// The lines of `buffer` that are visible in a viewport of `viewport_rows` x `viewport_cols`,
+/// scrolled to `scroll_offset`, one [VisualLine] per row of the viewport (top to bottom). W/
+/// `is_soft_wrap`, lines that are wider than the viewport take up several rows. Lines that are
+/// hidden by a fold are skipped, & the fold takes up a row for its placeholder.
+pub fn get_visual_lines(
+  buffer: &EditorBuffer, scroll_offset: ScrollOffset, viewport_rows: usize, viewport_cols: usize,
+  is_soft_wrap: bool,
+) -> Vec<VisualLine> {
+  let mut visual_lines = vec![];
+  let first_row = buffer.get_unhidden_row(scroll_offset.row);
+  let mut maybe_row = (first_row < buffer.line_count()).then_some(first_row);
+  while let Some(row) = maybe_row {
+    if visual_lines.len() >= viewport_rows {
+      break;
+    }
+    let line = buffer.lines[row].as_str();
+    if !is_soft_wrap {
+      visual_lines.push(VisualLine {
+        row,
+        start_col: scroll_offset.col,
+        end_col: scroll_offset.col + viewport_cols,
+        is_end_of_line: true,
+        folded_line_count: 0,
+      });
+    } else {
+      let ranges = line.wrap_to_display_cols(viewport_cols);
+      let last_index = ranges.len() - 1;
+      for (index, range) in ranges.into_iter().enumerate() {
+        visual_lines.push(VisualLine {
+          row,
+          start_col: range.start,
+          end_col: range.end,
+          is_end_of_line: index == last_index,
+          folded_line_count: 0,
+        });
+      }
+    }
+    if let Some(hidden) = buffer.get_fold_at(row) {
+      visual_lines.push(VisualLine {
+        row,
+        start_col: 0,
+        end_col: viewport_cols,
+        is_end_of_line: true,
+        folded_line_count: hidden.len(),
+      });
+    }
+    maybe_row = buffer.get_next_unhidden_row(row);
+  }
+  visual_lines.truncate(viewport_rows);
+  visual_lines
+}
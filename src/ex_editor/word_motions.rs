@@ -0,0 +1,191 @@
+/*
+ *   Copyright (c) 2022 R3BL LLC
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use super::*;
+
+/// Class of a grapheme cluster for word motions. A word is a run of grapheme clusters of the same
+/// class (other than [CharClass::Blank]), so `foo.bar` is 3 words (like vi).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum CharClass {
+  /// Whitespace, or the end of a line.
+  Blank,
+  /// Letters, digits & `_`.
+  Word,
+  Punctuation,
+}
+
+/// Word motions (vi `w`, `b` & `e`, emacs `M-f` & `M-b`), see [CaretDirection::WordForward],
+/// [CaretDirection::WordBackward], [CaretDirection::WordEnd], [CaretDirection::WordRight] &
+/// [CaretDirection::WordLeft]. They cross line boundaries, and for the vi ones an empty line counts
+/// as a word.
+impl EditorBuffer {
+  pub(crate) fn get_next_word_start(&self, from: CaretPos) -> CaretPos {
+    let mut pos = from;
+    let start_class = self.get_char_class(pos);
+
+    // Skip the rest of the word that the caret is in.
+    if start_class != CharClass::Blank {
+      while self.get_char_class(pos) == start_class {
+        match self.get_next_pos(pos) {
+          Some(next_pos) => pos = next_pos,
+          None => return pos,
+        }
+      }
+    }
+
+    // Skip blanks (& line ends), but stop at an empty line.
+    loop {
+      let is_empty_line = pos.row != from.row && self.line_len(pos.row) == 0;
+      if self.get_char_class(pos) != CharClass::Blank || is_empty_line {
+        return pos;
+      }
+      match self.get_next_pos(pos) {
+        Some(next_pos) => pos = next_pos,
+        None => return pos,
+      }
+    }
+  }
+
+  pub(crate) fn get_prev_word_start(&self, from: CaretPos) -> CaretPos {
+    let mut pos = match self.get_prev_pos(from) {
+      Some(prev_pos) => prev_pos,
+      None => return from,
+    };
+
+    // Skip blanks (& line ends) backwards, but stop at an empty line.
+    while self.get_char_class(pos) == CharClass::Blank && self.line_len(pos.row) > 0 {
+      match self.get_prev_pos(pos) {
+        Some(prev_pos) => pos = prev_pos,
+        None => return pos,
+      }
+    }
+
+    // Move to the start of the word.
+    let class = self.get_char_class(pos);
+    while pos.col > 0 {
+      let prev_pos = CaretPos {
+        row: pos.row,
+        col: pos.col - 1,
+      };
+      if self.get_char_class(prev_pos) != class {
+        break;
+      }
+      pos = prev_pos;
+    }
+    pos
+  }
+
+  pub(crate) fn get_word_end(&self, from: CaretPos) -> CaretPos {
+    let mut pos = match self.get_next_pos(from) {
+      Some(next_pos) => next_pos,
+      None => return from,
+    };
+
+    // Skip blanks (& line ends).
+    while self.get_char_class(pos) == CharClass::Blank {
+      match self.get_next_pos(pos) {
+        Some(next_pos) => pos = next_pos,
+        None => return pos,
+      }
+    }
+
+    // Move to the last grapheme cluster of the word.
+    let class = self.get_char_class(pos);
+    while pos.col + 1 < self.line_len(pos.row) {
+      let next_pos = CaretPos {
+        row: pos.row,
+        col: pos.col + 1,
+      };
+      if self.get_char_class(next_pos) != class {
+        break;
+      }
+      pos = next_pos;
+    }
+    pos
+  }
+
+  pub(crate) fn get_word_right(&self, from: CaretPos) -> CaretPos {
+    let mut pos = from;
+    let mut is_in_word = false;
+    loop {
+      let is_word = self.get_char_class(pos) == CharClass::Word;
+      if is_in_word && !is_word {
+        return pos;
+      }
+      is_in_word = is_word;
+      match self.get_next_pos(pos) {
+        Some(next_pos) => pos = next_pos,
+        None => return pos,
+      }
+    }
+  }
+
+  pub(crate) fn get_word_left(&self, from: CaretPos) -> CaretPos {
+    let mut pos = from;
+    let mut is_in_word = false;
+    while let Some(prev_pos) = self.get_prev_pos(pos) {
+      let is_word = self.get_char_class(prev_pos) == CharClass::Word;
+      if is_in_word && !is_word {
+        break;
+      }
+      is_in_word = is_word;
+      pos = prev_pos;
+    }
+    pos
+  }
+
+  fn get_char_class(&self, pos: CaretPos) -> CharClass {
+    match self.lines[pos.row].grapheme_at(pos.col) {
+      None => CharClass::Blank,
+      Some(grapheme) if grapheme.chars().all(char::is_whitespace) => CharClass::Blank,
+      Some(grapheme) if grapheme.chars().any(|it| it.is_alphanumeric() || it == '_') => {
+        CharClass::Word
+      }
+      Some(_) => CharClass::Punctuation,
+    }
+  }
+
+  /// Next position, where the end of a line is a position of its own.
+  fn get_next_pos(&self, pos: CaretPos) -> Option<CaretPos> {
+    match pos {
+      _ if pos.col < self.line_len(pos.row) => Some(CaretPos {
+        row: pos.row,
+        col: pos.col + 1,
+      }),
+      _ if pos.row + 1 < self.lines.len() => Some(CaretPos {
+        row: pos.row + 1,
+        col: 0,
+      }),
+      _ => None,
+    }
+  }
+
+  /// Previous position, where the end of a line is a position of its own.
+  fn get_prev_pos(&self, pos: CaretPos) -> Option<CaretPos> {
+    match pos {
+      _ if pos.col > 0 => Some(CaretPos {
+        row: pos.row,
+        col: pos.col - 1,
+      }),
+      _ if pos.row > 0 => Some(CaretPos {
+        row: pos.row - 1,
+        col: self.line_len(pos.row - 1),
+      }),
+      _ => None,
+    }
+  }
+}
@@ -0,0 +1,260 @@
+/*
+ *   Copyright (c) 2022 R3BL LLC
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+/// Attributes of the text (set by SGR escape sequences, eg: `ESC[1m`) that lolcat can keep, when
+/// it replaces the colors of text that is already colored (eg: by `cargo --color=always`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TextAttributes {
+  pub is_bold: bool,
+  pub is_underlined: bool,
+}
+
+/// A run of the visible text of a line, & the attributes it is printed w/.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AnsiSpan<'a> {
+  pub text: &'a str,
+  pub attributes: TextAttributes,
+}
+
+/// Split `line` into the runs of text between its ANSI escape sequences, which are dropped. SGR
+/// sequences change the [TextAttributes] of the runs after them, the rest (eg: cursor movement or
+/// OSC hyperlinks) are just removed.
+pub fn parse_ansi_line(line: &str) -> Vec<AnsiSpan<'_>> {
+  let mut spans = vec![];
+  let mut attributes = TextAttributes::default();
+  let mut span_start = 0;
+  let mut chars = line.char_indices().peekable();
+
+  while let Some((index, char)) = chars.next() {
+    if char != '\x1b' {
+      continue;
+    }
+    if span_start < index {
+      spans.push(AnsiSpan {
+        text: &line[span_start..index],
+        attributes,
+      });
+    }
+    match chars.next() {
+      // CSI: parameter & intermediate bytes, ended by a final byte in `@`..=`~`.
+      Some((params_start, '[')) => {
+        let params_start = params_start + 1;
+        for (params_end, char) in chars.by_ref() {
+          if ('@'..='~').contains(&char) {
+            if char == 'm' {
+              apply_sgr(&mut attributes, &line[params_start..params_end]);
+            }
+            break;
+          }
+        }
+      }
+      // OSC (eg: a hyperlink), ended by BEL or ST (`ESC \`).
+      Some((_, ']')) => {
+        while let Some((_, char)) = chars.next() {
+          if char == '\x07' {
+            break;
+          }
+          if char == '\x1b' {
+            chars.next_if(|(_, char)| *char == '\\');
+            break;
+          }
+        }
+      }
+      // Character set designation (eg: `ESC ( B`) has one more byte.
+      Some((_, '(' | ')' | '*' | '+')) => {
+        chars.next();
+      }
+      _ => {}
+    }
+    span_start = chars.peek().map(|(index, _)| *index).unwrap_or(line.len());
+  }
+
+  if span_start < line.len() {
+    spans.push(AnsiSpan {
+      text: &line[span_start..],
+      attributes,
+    });
+  }
+  spans
+}
+
+fn apply_sgr(attributes: &mut TextAttributes, params: &str) {
+  let mut params = params.split(';');
+  while let Some(param) = params.next() {
+    // Sub-parameters (eg: `38:2::255:0:0`) belong to the parameter before the first `:`.
+    let code = match param.split(':').next().unwrap_or_default() {
+      "" => 0,
+      code => match code.parse::<u16>() {
+        Ok(code) => code,
+        Err(_) => return,
+      },
+    };
+    match code {
+      0 => *attributes = TextAttributes::default(),
+      1 => attributes.is_bold = true,
+      4 | 21 => attributes.is_underlined = true,
+      22 => attributes.is_bold = false,
+      24 => attributes.is_underlined = false,
+      // Extended colors, skip their arguments (unless they are sub-parameters), so that eg: the
+      // `1` in `38;2;1;2;3` isn't taken for bold.
+      38 | 48 | 58 if !param.contains(':') => match params.next() {
+        Some("5") => {
+          params.next();
+        }
+        Some("2") => {
+          params.by_ref().take(3).for_each(drop);
+        }
+        _ => {}
+      },
+      _ => {}
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const PLAIN: TextAttributes = TextAttributes {
+    is_bold: false,
+    is_underlined: false,
+  };
+  const BOLD: TextAttributes = TextAttributes {
+    is_bold: true,
+    is_underlined: false,
+  };
+  const UNDERLINED: TextAttributes = TextAttributes {
+    is_bold: false,
+    is_underlined: true,
+  };
+
+  /// The text & attributes of the spans of `line`.
+  fn parse(line: &str) -> Vec<(&str, TextAttributes)> {
+    parse_ansi_line(line)
+      .into_iter()
+      .map(|span| (span.text, span.attributes))
+      .collect()
+  }
+
+  /// The attributes after the SGR sequence w/ `params`, starting from `attributes`.
+  fn get_attributes_after_sgr(mut attributes: TextAttributes, params: &str) -> TextAttributes {
+    apply_sgr(&mut attributes, params);
+    attributes
+  }
+
+  #[test]
+  fn test_reset() {
+    assert_eq!(
+      parse("\x1b[1;4mA\x1b[0mB"),
+      vec![
+        (
+          "A",
+          TextAttributes {
+            is_bold: true,
+            is_underlined: true,
+          }
+        ),
+        ("B", PLAIN),
+      ]
+    );
+    // No params is the same as `0`.
+    assert_eq!(parse("\x1b[1mA\x1b[mB"), vec![("A", BOLD), ("B", PLAIN)]);
+    assert_eq!(get_attributes_after_sgr(BOLD, ";4"), UNDERLINED);
+  }
+
+  #[test]
+  fn test_bold_on_off() {
+    assert_eq!(parse("\x1b[1mA\x1b[22mB"), vec![("A", BOLD), ("B", PLAIN)]);
+  }
+
+  #[test]
+  fn test_underline_on_off() {
+    assert_eq!(get_attributes_after_sgr(PLAIN, "4"), UNDERLINED);
+    assert_eq!(get_attributes_after_sgr(PLAIN, "21"), UNDERLINED);
+    assert_eq!(get_attributes_after_sgr(UNDERLINED, "24"), PLAIN);
+  }
+
+  #[test]
+  fn test_dim_and_italic_are_not_kept() {
+    for params in ["2", "3", "23"] {
+      assert_eq!(get_attributes_after_sgr(PLAIN, params), PLAIN);
+      assert_eq!(get_attributes_after_sgr(BOLD, params), BOLD);
+    }
+    // Normal intensity turns off dim & bold.
+    assert_eq!(get_attributes_after_sgr(BOLD, "2;22"), PLAIN);
+  }
+
+  #[test]
+  fn test_basic_colors_are_dropped() {
+    for code in (30..=37).chain(90..=97).chain(40..=47).chain(100..=107) {
+      let line = format!("\x1b[{}mA\x1b[39mB", code);
+      assert_eq!(parse(&line), vec![("A", PLAIN), ("B", PLAIN)]);
+    }
+    assert_eq!(get_attributes_after_sgr(PLAIN, "1;31"), BOLD);
+  }
+
+  #[test]
+  fn test_256_color_args_are_skipped() {
+    // The `1` & `4` are colors, not bold & underline.
+    assert_eq!(get_attributes_after_sgr(PLAIN, "38;5;1"), PLAIN);
+    assert_eq!(get_attributes_after_sgr(PLAIN, "48;5;4;1"), BOLD);
+  }
+
+  #[test]
+  fn test_truecolor_args_are_skipped() {
+    assert_eq!(get_attributes_after_sgr(PLAIN, "38;2;1;4;21"), PLAIN);
+    assert_eq!(get_attributes_after_sgr(PLAIN, "48;2;1;4;21;4"), UNDERLINED);
+    // Sub-parameters are part of the color.
+    assert_eq!(get_attributes_after_sgr(PLAIN, "38:2::1:4:21;1"), BOLD);
+  }
+
+  #[test]
+  fn test_truncated_extended_color() {
+    for params in ["38", "38;2", "38;2;1", "38;2;1;4", "38;5"] {
+      assert_eq!(get_attributes_after_sgr(PLAIN, params), PLAIN);
+    }
+    assert_eq!(parse("A\x1b[38;2mB"), vec![("A", PLAIN), ("B", PLAIN)]);
+    // A sequence that is cut off at the end of the line is dropped.
+    assert_eq!(parse("A\x1b[38;2;1"), vec![("A", PLAIN)]);
+    assert_eq!(parse("A\x1b"), vec![("A", PLAIN)]);
+  }
+
+  #[test]
+  fn test_invalid_params_are_ignored() {
+    assert_eq!(get_attributes_after_sgr(BOLD, "x;4"), BOLD);
+  }
+
+  #[test]
+  fn test_non_sgr_sequences_are_removed() {
+    // Erase line & cursor position (CSI), a hyperlink (OSC) & a character set (`ESC ( B`).
+    assert_eq!(
+      parse("A\x1b[2KB\x1b[1;4HC"),
+      vec![("A", PLAIN), ("B", PLAIN), ("C", PLAIN),]
+    );
+    assert_eq!(
+      parse("\x1b]8;;https://example.com\x07A\x1b]8;;\x1b\\B\x1b(BC"),
+      vec![("A", PLAIN), ("B", PLAIN), ("C", PLAIN)]
+    );
+    assert_eq!(parse("\x1b[1mA\x1b[2KB"), vec![("A", BOLD), ("B", BOLD)]);
+  }
+
+  #[test]
+  fn test_text_w_o_escapes() {
+    assert_eq!(parse("héllo 🦜"), vec![("héllo 🦜", PLAIN)]);
+    assert!(parse("").is_empty());
+  }
+}
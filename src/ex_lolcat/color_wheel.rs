@@ -0,0 +1,169 @@
+/*
+ *   Copyright (c) 2022 R3BL LLC
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use std::{f64::consts::PI,
+          fmt::Write,
+          time::{SystemTime, UNIX_EPOCH}};
+
+use crossterm::style::{Attribute,
+                       Color,
+                       ResetColor,
+                       SetAttribute,
+                       SetBackgroundColor,
+                       SetForegroundColor};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::*;
+
+/// Options of the lolcat filter (see [run_app](crate::ex_lolcat::run_app)).
+#[derive(Clone, Debug, PartialEq)]
+pub struct LolcatOptions {
+  /// Files to color, in order. Stdin is read if there are none, or for `-`.
+  pub file_paths: Vec<String>,
+  /// Where the rainbow starts, a random one is picked if there is none.
+  pub maybe_seed: Option<u32>,
+  /// How many grapheme clusters each color is spread over.
+  pub spread: f64,
+  /// How fast the colors change.
+  pub frequency: f64,
+  /// Color the background instead of the text.
+  pub is_inverted: bool,
+  /// Keep the bold & underline of text that is already colored (its colors are always replaced).
+  pub keeps_attributes: bool,
+  pub has_line_numbers: bool,
+}
+
+impl Default for LolcatOptions {
+  fn default() -> Self {
+    Self {
+      file_paths: vec![],
+      maybe_seed: None,
+      spread: 3.0,
+      frequency: 0.1,
+      is_inverted: false,
+      keeps_attributes: false,
+      has_line_numbers: true,
+    }
+  }
+}
+
+/// Rainbow colors, like the classic `lolcat`: each grapheme cluster of a line gets the color at its
+/// position on a wheel of sine waves (for red, green & blue), & each line starts one step further
+/// along the wheel than the one before it.
+#[derive(Clone, Debug)]
+pub struct ColorWheel {
+  pub spread: f64,
+  pub frequency: f64,
+  pub is_inverted: bool,
+  pub keeps_attributes: bool,
+  /// The rainbow is made of the nearest colors that the terminal has, or left out if it has none.
+  pub color_support: ColorSupport,
+  /// Position of the start of the next line on the wheel.
+  pub offset: f64,
+}
+
+impl From<&LolcatOptions> for ColorWheel {
+  fn from(options: &LolcatOptions) -> Self {
+    let seed = options.maybe_seed.unwrap_or_else(|| {
+      let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|it| it.subsec_nanos())
+        .unwrap_or_default();
+      nanos % 256
+    });
+    Self {
+      spread: options.spread.max(f64::EPSILON),
+      frequency: options.frequency,
+      is_inverted: options.is_inverted,
+      keeps_attributes: options.keeps_attributes,
+      color_support: ColorSupport::detect(),
+      offset: seed as f64,
+    }
+  }
+}
+
+impl Default for ColorWheel {
+  /// The rainbow of `lolcat` w/ the default options.
+  fn default() -> Self { Self::from(&LolcatOptions::default()) }
+}
+
+impl ColorWheel {
+  /// `line` w/ each of its visible grapheme clusters colored (w/ ANSI escape sequences), & the
+  /// colors reset at the end (if any were used). The escape sequences already in `line` are
+  /// dropped (see [parse_ansi_line]), so they don't take up colors.
+  pub fn paint_line(&mut self, line: &str) -> String {
+    let mut painted_line = String::new();
+    let mut is_styled = false;
+    let mut current_attributes = TextAttributes::default();
+    let visible_graphemes = parse_ansi_line(line).into_iter().flat_map(|span| {
+      span
+        .text
+        .graphemes(true)
+        .map(move |it| (it, span.attributes))
+    });
+    for (index, (grapheme, attributes)) in visible_graphemes.enumerate() {
+      if self.keeps_attributes && attributes != current_attributes {
+        write_attributes_change(&mut painted_line, current_attributes, attributes);
+        current_attributes = attributes;
+        is_styled = true;
+      }
+      let color = self.get_color(self.offset + index as f64 / self.spread);
+      if let Some(color) = self.color_support.degrade(color) {
+        let _ = match self.is_inverted {
+          true => write!(painted_line, "{}", SetBackgroundColor(color)),
+          false => write!(painted_line, "{}", SetForegroundColor(color)),
+        };
+        is_styled = true;
+      }
+      painted_line.push_str(grapheme);
+    }
+    if is_styled {
+      let _ = write!(painted_line, "{}", ResetColor);
+    }
+    self.offset += 1.0;
+    painted_line
+  }
+
+  fn get_color(&self, position: f64) -> Color {
+    let get_component =
+      |phase: f64| ((self.frequency * position + phase).sin() * 127.0 + 128.0) as u8;
+    Color::Rgb {
+      r: get_component(0.0),
+      g: get_component(2.0 * PI / 3.0),
+      b: get_component(4.0 * PI / 3.0),
+    }
+  }
+}
+
+fn write_attributes_change(output: &mut String, from: TextAttributes, to: TextAttributes) {
+  if from.is_bold != to.is_bold {
+    let attribute = if to.is_bold {
+      Attribute::Bold
+    } else {
+      Attribute::NormalIntensity
+    };
+    let _ = write!(output, "{}", SetAttribute(attribute));
+  }
+  if from.is_underlined != to.is_underlined {
+    let attribute = if to.is_underlined {
+      Attribute::Underlined
+    } else {
+      Attribute::NoUnderline
+    };
+    let _ = write!(output, "{}", SetAttribute(attribute));
+  }
+}
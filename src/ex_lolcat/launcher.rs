@@ -15,27 +15,67 @@
  *   limitations under the License.
  */
 
-use std::io::{BufRead, BufReader};
-
-use tokio::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Write};
 
 use crate::*;
 
-pub async fn run_app() -> CommonResult<()> {
-  let mut my_lolcat = Lolcat::new();
-
-  println!("{:?}", my_lolcat);
+/// Color the lines of the files in `options` (or of stdin) w/ a rainbow (see [ColorWheel]), & print
+/// them as they are read.
+pub async fn run_app(options: LolcatOptions) -> CommonResult<()> {
+  let mut color_wheel = ColorWheel::from(&options);
 
-  let file = File::open("Cargo.lock").await?;
-  let file = file.into_std().await;
+  // Open all the files first, so that a missing one is reported before anything is printed.
+  let mut inputs: Vec<Box<dyn Read + Send>> = vec![];
+  for file_path in &options.file_paths {
+    match file_path.as_str() {
+      "-" => inputs.push(Box::new(io::stdin())),
+      _ => {
+        let file = tokio::fs::File::open(file_path)
+          .await
+          .map_err(|error| io::Error::new(error.kind(), format!("{}: {}", file_path, error)))?;
+        inputs.push(Box::new(file.into_std().await));
+      }
+    }
+  }
+  if inputs.is_empty() {
+    inputs.push(Box::new(io::stdin()));
+  }
 
-  tokio::task::spawn_blocking(move || {
-    let buffer_reader = BufReader::new(file);
-    for (index, line) in buffer_reader.lines().enumerate() {
-      let line = line.unwrap();
-      println!("{}. {}", index + 1, my_lolcat.format_str(&line));
+  let has_line_numbers = options.has_line_numbers;
+  let result = tokio::task::spawn_blocking(move || -> io::Result<()> {
+    let mut stdout = io::stdout().lock();
+    let mut line_number = 0;
+    let mut bytes = vec![];
+    for input in inputs {
+      let mut buffer_reader = BufReader::new(input);
+      loop {
+        // Read bytes (not a `String`) so that a line that isn't valid UTF-8 is printed lossily,
+        // instead of ending the whole stream.
+        bytes.clear();
+        if buffer_reader.read_until(b'\n', &mut bytes)? == 0 {
+          break;
+        }
+        if bytes.ends_with(b"\n") {
+          bytes.pop();
+          if bytes.ends_with(b"\r") {
+            bytes.pop();
+          }
+        }
+        let line = String::from_utf8_lossy(&bytes);
+        line_number += 1;
+        match has_line_numbers {
+          true => writeln!(stdout, "{}. {}", line_number, color_wheel.paint_line(&line))?,
+          false => writeln!(stdout, "{}", color_wheel.paint_line(&line))?,
+        }
+      }
     }
-  });
+    stdout.flush()
+  })
+  .await?;
 
-  Ok(())
+  // The reader on the other end of a pipe going away (eg: `| head`) isn't an error.
+  match result {
+    Err(error) if error.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+    result => Ok(result?),
+  }
 }
@@ -16,7 +16,11 @@
  */
 
 // Attach.
+pub mod ansi_text;
+pub mod color_wheel;
 pub mod launcher;
 
 // Re-export.
+pub use ansi_text::*;
+pub use color_wheel::*;
 pub use launcher::*;
@@ -35,6 +35,22 @@
 //!   - Redux for state management (fully async, concurrent & parallel).
 //!   - A lolcat implementation w/ a rainbow color-wheel palette.
 
+// The sources use the things from r3bl_rs_utils through `crate::*`.
+use r3bl_rs_utils::*;
+
 // Attach sources.
+pub mod color_support;
+pub mod ex_app_no_layout;
+pub mod ex_app_with_layout;
+pub mod ex_editor;
+pub mod ex_lolcat;
 
-// Re-export.
+// Re-export. The examples have modules & items w/ the same names (eg: `state`, `run_app`), which
+// are used via the paths of their examples.
+pub use color_support::*;
+#[allow(ambiguous_glob_reexports)]
+pub use ex_app_no_layout::*;
+#[allow(ambiguous_glob_reexports)]
+pub use ex_app_with_layout::*;
+pub use ex_editor::*;
+pub use ex_lolcat::*;
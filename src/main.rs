@@ -17,19 +17,8 @@
 
 use std::borrow::Cow;
 
+use r3bl_cmdr::*;
 use r3bl_rs_utils::*;
-
-// Attach sources.
-pub mod ex_app_no_layout;
-pub mod ex_app_with_layout;
-pub mod ex_editor;
-pub mod ex_lolcat;
-
-// Use things from sources.
-pub use ex_app_no_layout::*;
-pub use ex_app_with_layout::*;
-pub use ex_editor::*;
-pub use ex_lolcat::*;
 use reedline::*;
 
 const HELP_MSG: &str = "\
@@ -40,23 +29,143 @@ Type a number to run corresponding example:
   4. Text editor 📜
 or type Ctrl+C / Ctrl+D / 'x' to exit";
 
+const EDIT_FILE_MSG: &str = "\
+Type the path of the file to edit, or press Enter for an empty buffer";
+
+const LOLCAT_HELP_MSG: &str = "\
+Usage: r3bl-cmdr lolcat [OPTIONS] [FILE]...
+Color the lines of the files (or of stdin, also for `-`) w/ a rainbow.
+  -s, --seed <N>         Where the rainbow starts (random by default)
+  -p, --spread <F>       How many chars each color is spread over (default: 3.0)
+  -F, --freq <F>         How fast the colors change (default: 0.1)
+  -i, --invert           Color the background instead of the text
+  -a, --keep-attributes  Keep the bold & underline of text that is already colored
+  -n, --no-line-numbers  Don't number the lines
+  -h, --help             Print this message";
+
 #[tokio::main]
 async fn main() -> CommonResult<()> {
   throws!({
+    // Run the editor directly if started w/ `r3bl-cmdr edit [file]...`, & lolcat w/ `r3bl-cmdr
+    // lolcat [file]...`.
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some((command, file_paths)) = args.split_first() {
+      if command == "lolcat" {
+        match parse_lolcat_args(file_paths) {
+          Ok(Some(options)) => ex_lolcat::run_app(options).await?,
+          Ok(None) => println!("{}", LOLCAT_HELP_MSG),
+          Err(message) => {
+            eprintln!("{}\n{}", message, LOLCAT_HELP_MSG);
+            std::process::exit(2);
+          }
+        }
+        return Ok(());
+      }
+      if command == "edit" {
+        match parse_edit_args(file_paths) {
+          Ok((file_paths, maybe_key_bindings, maybe_collab_role)) => {
+            ex_editor::run_app(file_paths, maybe_key_bindings, maybe_collab_role).await?
+          }
+          Err(message) => {
+            eprintln!("{}", message);
+            std::process::exit(2);
+          }
+        }
+        return Ok(());
+      }
+    }
+
     println!("{}", HELP_MSG);
     let selection = get_user_selection();
     run_ex_for_user_selection(selection).await?;
   })
 }
 
+/// The file paths, the key bindings & the collab role of `edit` (see [parse_edit_args]).
+type EditArgs = (Vec<String>, Option<KeyBindings>, Option<CollabRole>);
+
+/// Split the args of `edit` into the file paths, the key bindings (`--keys <name>`), which
+/// override the ones from the config file, & how the first file is shared (`--share <address>` or
+/// `--join <address>`).
+fn parse_edit_args(args: &[String]) -> Result<EditArgs, String> {
+  let mut file_paths = vec![];
+  let mut maybe_key_bindings = None;
+  let mut maybe_collab_role = None;
+  let mut args = args.iter();
+  while let Some(arg) = args.next() {
+    match arg.as_str() {
+      "--keys" => {
+        let name = args
+          .next()
+          .ok_or("Missing the name of the key bindings after --keys")?;
+        maybe_key_bindings = Some(name.parse()?);
+      }
+      "--share" | "--join" => {
+        let address = args
+          .next()
+          .ok_or(format!("Missing the address after {}", arg))?
+          .clone();
+        maybe_collab_role = Some(match arg.as_str() {
+          "--share" => CollabRole::Host(address),
+          _ => CollabRole::Guest(address),
+        });
+      }
+      _ => file_paths.push(arg.clone()),
+    }
+  }
+  Ok((file_paths, maybe_key_bindings, maybe_collab_role))
+}
+
+/// The options of `lolcat`, `None` if the help message was asked for.
+fn parse_lolcat_args(args: &[String]) -> Result<Option<LolcatOptions>, String> {
+  let mut options = LolcatOptions::default();
+  let mut args = args.iter();
+  while let Some(arg) = args.next() {
+    let mut get_value = || {
+      args
+        .next()
+        .ok_or(format!("Missing the value after {}", arg))
+    };
+    let parse_error = |value: &str| format!("Invalid value for {}: {}", arg, value);
+    match arg.as_str() {
+      "-s" | "--seed" => {
+        let value = get_value()?;
+        options.maybe_seed = Some(value.parse().map_err(|_| parse_error(value))?);
+      }
+      "-p" | "--spread" => {
+        let value = get_value()?;
+        options.spread = value.parse().map_err(|_| parse_error(value))?;
+      }
+      "-F" | "--freq" => {
+        let value = get_value()?;
+        options.frequency = value.parse().map_err(|_| parse_error(value))?;
+      }
+      "-i" | "--invert" => options.is_inverted = true,
+      "-a" | "--keep-attributes" => options.keeps_attributes = true,
+      "-n" | "--no-line-numbers" => options.has_line_numbers = false,
+      "-h" | "--help" => return Ok(None),
+      "-" => options.file_paths.push(arg.clone()),
+      _ if arg.starts_with('-') => return Err(format!("Unknown option: {}", arg)),
+      _ => options.file_paths.push(arg.clone()),
+    }
+  }
+  Ok(Some(options))
+}
+
 async fn run_ex_for_user_selection(selection: Cow<'_, str>) -> CommonResult<()> {
   throws!({
     if !selection.is_empty() {
       match selection.as_ref() {
         "1" => throws!(ex_app_no_layout::run_app().await?),
         "2" => throws!(ex_app_with_layout::run_app().await?),
-        "3" => throws!(ex_lolcat::run_app().await?),
-        "4" => todo!("TODO: implement editor ex!"),
+        "3" => throws!(
+          ex_lolcat::run_app(LolcatOptions {
+            file_paths: vec!["Cargo.lock".into()],
+            ..Default::default()
+          })
+          .await?
+        ),
+        "4" => throws!(ex_editor::run_app(get_file_path_to_edit(), None, None).await?),
         _ => unimplemented!(),
       }
     }
@@ -65,14 +174,14 @@ async fn run_ex_for_user_selection(selection: Cow<'_, str>) -> CommonResult<()>
 
 fn get_user_selection<'a>() -> Cow<'a, str> {
   let mut line_editor = Reedline::create();
-  let prompt = DefaultPrompt::default();
+  let prompt = DefaultPrompt;
   let mut selection: Cow<str> = Cow::from("");
 
   loop {
     let maybe_signal = &line_editor.read_line(&prompt);
     if let Ok(Signal::Success(user_input_str)) = maybe_signal {
       match user_input_str.as_str() {
-        code @ ("1" | "2" | "3") => {
+        code @ ("1" | "2" | "3" | "4") => {
           selection.to_mut().push_str(code);
           break;
         }
@@ -86,3 +195,16 @@ fn get_user_selection<'a>() -> Cow<'a, str> {
 
   selection
 }
+
+fn get_file_path_to_edit() -> Vec<String> {
+  println!("{}", EDIT_FILE_MSG);
+  let mut line_editor = Reedline::create();
+  let prompt = DefaultPrompt;
+
+  match line_editor.read_line(&prompt) {
+    Ok(Signal::Success(user_input_str)) if !user_input_str.trim().is_empty() => {
+      vec![user_input_str.trim().to_string()]
+    }
+    _ => vec![],
+  }
+}
@@ -0,0 +1,204 @@
+/*
+ *   Copyright (c) 2022 R3BL LLC
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+//! A host & a guest that share a buffer over TCP (see [CollabSession]), & edit it at the same time.
+
+use std::{sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use r3bl_cmdr::*;
+use r3bl_rs_utils::*;
+use tokio::{sync::{Notify, RwLock},
+            time::timeout};
+
+const ID: &str = EDITOR_IDS[0];
+
+/// How long the peers have to get in sync, before the test fails.
+const SYNC_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Wakes up [sync] whenever the state of a peer changes (eg: when a message from the other peer
+/// is received), which is when the app renders & sends the edits & the carets.
+struct StateChangeNotifier(Arc<Notify>);
+
+#[async_trait]
+impl AsyncSubscriber<EditorState> for StateChangeNotifier {
+  async fn run(&self, _state: EditorState) { self.0.notify_one(); }
+}
+
+struct Peer {
+  shared_store: SharedStore<EditorState, EditorAction>,
+  session: CollabSession,
+  state_changed: Arc<Notify>,
+}
+
+impl Peer {
+  async fn start(
+    content: &str, session: CollabSession, role: CollabRole, state_changed: &Arc<Notify>,
+  ) -> Peer {
+    let mut store: Store<EditorState, EditorAction> = Store::default();
+    store.add_reducer(EditorReducer::new()).await;
+    store
+      .add_subscriber(Box::new(StateChangeNotifier(state_changed.clone())))
+      .await;
+    let buffer = EditorBuffer::from_content(content);
+    store
+      .dispatch_action(EditorAction::SetBuffer(ID.into(), buffer))
+      .await;
+    store
+      .dispatch_action(EditorAction::StartCollab(ID.into(), role))
+      .await;
+    let shared_store = Arc::new(RwLock::new(store));
+    session.spawn(&shared_store);
+    Peer {
+      shared_store,
+      session,
+      state_changed: state_changed.clone(),
+    }
+  }
+
+  async fn get_state(&self) -> EditorState { self.shared_store.read().await.get_state() }
+
+  /// Replace the chars in `start..end` of the first line w/ `text`. It is only sent to the other
+  /// peer by [Peer::send].
+  async fn replace(&self, start: usize, end: usize, text: &str) {
+    let action = EditorAction::Batch(vec![
+      EditorAction::SetCaret(ID.into(), CaretPos { row: 0, col: start }),
+      EditorAction::ExtendSelectionTo(ID.into(), CaretPos { row: 0, col: end }),
+      EditorAction::Paste(ID.into(), text.into()),
+    ]);
+    self
+      .shared_store
+      .write()
+      .await
+      .dispatch_action(action)
+      .await;
+  }
+
+  /// Send the edits & the carets that the other peer doesn't have yet (as on each render).
+  async fn send(&self) { self.session.update(&self.get_state().await); }
+
+  async fn get_text(&self) -> String {
+    self
+      .get_state()
+      .await
+      .get_buffer(ID)
+      .map(EditorBuffer::get_as_string)
+      .unwrap_or_default()
+  }
+
+  /// Whether the peer is sharing the buffer, & the other peer has acknowledged all its edits.
+  async fn is_acknowledged(&self) -> bool {
+    match self.get_state().await.collab {
+      Some(collab) => {
+        !collab.links.is_empty() && collab.links.values().all(|it| it.pending_edits.is_empty())
+      }
+      None => false,
+    }
+  }
+}
+
+/// A host on a free port w/ a buffer that has `content`, & a guest that joined it.
+async fn start_collab(content: &str) -> (Peer, Peer) {
+  let session = CollabSession::connect(&CollabRole::Host("127.0.0.1:0".into()))
+    .await
+    .unwrap();
+  let address = session.get_local_addr().unwrap().to_string();
+  let state_changed = Arc::new(Notify::new());
+  let host = Peer::start(
+    content,
+    session,
+    CollabRole::Host(address.clone()),
+    &state_changed,
+  )
+  .await;
+
+  let role = CollabRole::Guest(address);
+  let session = CollabSession::connect(&role).await.unwrap();
+  let guest = Peer::start("", session, role, &state_changed).await;
+
+  sync(&host, &guest).await;
+  (host, guest)
+}
+
+/// Exchange the messages of the peers until they have the same text, which is returned. The peers
+/// send their messages again each time that the state of one of them changes.
+async fn sync(host: &Peer, guest: &Peer) -> String {
+  let exchange_messages = async {
+    loop {
+      host.send().await;
+      guest.send().await;
+      let text = host.get_text().await;
+      if host.is_acknowledged().await
+        && guest.is_acknowledged().await
+        && text == guest.get_text().await
+      {
+        return text;
+      }
+      host.state_changed.notified().await;
+    }
+  };
+  if let Ok(text) = timeout(SYNC_TIMEOUT, exchange_messages).await {
+    return text;
+  }
+  panic!(
+    "Out of sync, host: {:?}, guest: {:?}",
+    host.get_text().await,
+    guest.get_text().await
+  );
+}
+
+/// The host & the guest make an edit each before they get the one of the other, & end up w/ the
+/// same text.
+async fn assert_concurrent_edits(
+  host_edit: (usize, usize, &str), guest_edit: (usize, usize, &str), expected_text: &str,
+) {
+  let (host, guest) = start_collab("abcdefgh").await;
+  assert_eq!(guest.get_text().await, "abcdefgh");
+
+  host.replace(host_edit.0, host_edit.1, host_edit.2).await;
+  guest
+    .replace(guest_edit.0, guest_edit.1, guest_edit.2)
+    .await;
+  assert_eq!(sync(&host, &guest).await, expected_text);
+}
+
+#[tokio::test]
+async fn test_insert_at_same_offset() {
+  // The text of the host goes first.
+  assert_concurrent_edits((2, 2, "X"), (2, 2, "Y"), "abXYcdefgh").await;
+}
+
+#[tokio::test]
+async fn test_overlapping_deletes() {
+  assert_concurrent_edits((1, 5, ""), (3, 7, ""), "ah").await;
+  assert_concurrent_edits((2, 4, ""), (2, 4, ""), "abefgh").await;
+}
+
+#[tokio::test]
+async fn test_insert_inside_deleted_range() {
+  assert_concurrent_edits((2, 6, ""), (4, 4, "XY"), "abXYgh").await;
+  assert_concurrent_edits((4, 4, "XY"), (2, 6, ""), "abXYgh").await;
+}
+
+#[tokio::test]
+async fn test_edits_after_sync() {
+  let (host, guest) = start_collab("abc\ndef").await;
+  host.replace(3, 3, "\nX").await;
+  assert_eq!(sync(&host, &guest).await, "abc\nX\ndef");
+  guest.replace(0, 1, "").await;
+  assert_eq!(sync(&host, &guest).await, "bc\nX\ndef");
+}